@@ -0,0 +1,22 @@
+//! Check applying combining marks to groups never panics on arbitrary input.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use std::sync::LazyLock;
+use unicodeit::Converter;
+
+static CONVERTER: LazyLock<Converter> = LazyLock::new(|| {
+    Converter::builder()
+        .combining_groups(true)
+        .wide_accents(true)
+        .build()
+});
+
+fuzz_target!(|text: &str| {
+    let output = CONVERTER.replace(text);
+    let tokens: String = CONVERTER.tokens(text).map(|token| token.unicode()).collect();
+    assert_eq!(tokens, output);
+    let (mapped, map) = CONVERTER.replace_with_mapping(text);
+    assert_eq!(mapped, output);
+    assert_eq!(map.output_range(0..text.len()), 0..output.len());
+});
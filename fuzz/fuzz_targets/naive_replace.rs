@@ -0,0 +1,8 @@
+//! Check the naive implementation never panics on arbitrary input.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|text: &str| {
+    let _ = unicodeit::replace_naive(text);
+});
@@ -0,0 +1,13 @@
+//! Check the default implementation never panics on arbitrary input.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|text: &str| {
+    let output = unicodeit::replace_optimized(text);
+    assert_eq!(unicodeit::replace_cow(text), output);
+    let tokens: String = unicodeit::tokens(text).map(|token| token.unicode()).collect();
+    assert_eq!(tokens, output);
+    // diagnostics inspect both the input and the output
+    let _ = unicodeit::try_replace(text);
+});
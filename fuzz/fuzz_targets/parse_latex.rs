@@ -0,0 +1,20 @@
+//! Check the parser and the conversion walking its syntax tree never panic on arbitrary input.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use std::sync::LazyLock;
+use unicodeit::Converter;
+
+static CONVERTER: LazyLock<Converter> =
+    LazyLock::new(|| Converter::builder().parse_latex(true).build());
+
+fuzz_target!(|text: &str| {
+    let nodes = unicodeit::parse(text);
+    // the nodes cover the whole input without gaps
+    assert_eq!(nodes.first().map_or(0, |node| node.span.start), 0);
+    assert_eq!(nodes.last().map_or(0, |node| node.span.end), text.len());
+    let output = CONVERTER.replace(text);
+    let (mapped, map) = CONVERTER.replace_with_mapping(text);
+    assert_eq!(mapped, output);
+    assert_eq!(map.output_range(0..text.len()), 0..output.len());
+});
@@ -0,0 +1,28 @@
+//! Check every fallback for groups of scripts never panics on arbitrary input.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use std::sync::LazyLock;
+use unicodeit::{Converter, ScriptFallback};
+
+static CONVERTERS: LazyLock<Vec<Converter>> = LazyLock::new(|| {
+    [
+        ScriptFallback::Prefix,
+        ScriptFallback::Parenthesized,
+        ScriptFallback::Closest,
+    ]
+    .into_iter()
+    .map(|fallback| Converter::builder().script_fallback(fallback).build())
+    .collect()
+});
+
+fuzz_target!(|text: &str| {
+    for converter in CONVERTERS.iter() {
+        let output = converter.replace(text);
+        let tokens: String = converter.tokens(text).map(|token| token.unicode()).collect();
+        assert_eq!(tokens, output);
+        let (mapped, map) = converter.replace_with_mapping(text);
+        assert_eq!(mapped, output);
+        assert_eq!(map.output_range(0..text.len()), 0..output.len());
+    }
+});
@@ -0,0 +1,193 @@
+#![deny(missing_docs)]
+//! Converts LaTeX string literals to Unicode at compile time,
+//! using the [`unicodeit`](mod@unicodeit) crate.
+//!
+//! Any command which cannot be converted is a compile error,
+//! pointing at the literal:
+//!
+//! ```compile_fail
+//! let label = unicodeit_macros::unicodeit!(r"\alpah");
+//! ```
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use std::collections::{HashMap, HashSet};
+use std::sync::LazyLock;
+use syn::parse::{Parse, ParseStream};
+use syn::{LitStr, Token, parse_macro_input};
+use unicodeit::Converter;
+
+/// The first character of the supplementary private use area B,
+/// used to protect format placeholders from conversion.
+///
+/// Unlike the basic private use area, no LaTeX command converts to these characters.
+const PLACEHOLDER_START: u32 = 0x10_0000;
+/// The last character of the supplementary private use area B.
+const PLACEHOLDER_END: u32 = 0x10_FFFD;
+
+static STRICT_CONVERTER: LazyLock<Converter> =
+    LazyLock::new(|| Converter::builder().strict(true).build());
+
+/// Convert the LaTeX, failing with an error at the span of the literal.
+fn convert(latex: &str, span: Span) -> syn::Result<String> {
+    STRICT_CONVERTER.try_replace(latex).map_err(|error| {
+        let mut message = String::from("failed to convert LaTeX to Unicode");
+        for failure in error.failures() {
+            let source = latex.get(failure.span.clone()).unwrap_or_default();
+            message.push_str(&format!("\n  `{source}`: {}", failure.kind));
+        }
+        syn::Error::new(span, message)
+    })
+}
+
+/// Convert a LaTeX string literal to Unicode at compile time,
+/// giving a `&'static str`.
+///
+/// This is equivalent to [`unicodeit::try_replace`],
+/// with any failure reported as a compile error.
+///
+/// ```
+/// use unicodeit_macros::unicodeit;
+/// const LABEL: &str = unicodeit!(r"\alpha + \beta = x^2");
+/// assert_eq!(LABEL, "\u{3b1} + \u{3b2} = x\u{b2}");
+/// ```
+#[proc_macro]
+pub fn unicodeit(input: TokenStream) -> TokenStream {
+    let literal = parse_macro_input!(input as LitStr);
+    match convert(&literal.value(), literal.span()) {
+        Ok(converted) => {
+            let converted = LitStr::new(&converted, literal.span());
+            quote!(#converted).into()
+        }
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+/// The input to [`uformat!`]: a format string followed by arguments.
+struct FormatInput {
+    format: LitStr,
+    arguments: proc_macro2::TokenStream,
+}
+
+impl Parse for FormatInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let format = input.parse()?;
+        let arguments = if input.is_empty() {
+            proc_macro2::TokenStream::new()
+        } else {
+            input.parse::<Token![,]>()?;
+            input.parse()?
+        };
+        Ok(FormatInput { format, arguments })
+    }
+}
+
+/// Replace each placeholder like `{}` or `{name:?}` with a private use character,
+/// and unescape the literal braces `{{` and `}}`.
+///
+/// Private use characters which appear in the format string itself are skipped,
+/// so they are never mistaken for a placeholder.
+fn protect_placeholders(format: &str, span: Span) -> syn::Result<(String, Vec<(char, &str)>)> {
+    let literal = format.chars().collect::<HashSet<char>>();
+    let mut candidates = (PLACEHOLDER_START..=PLACEHOLDER_END)
+        .filter_map(char::from_u32)
+        .filter(|c| !literal.contains(c));
+    let mut latex = String::with_capacity(format.len());
+    let mut placeholders = Vec::new();
+    let mut rest = format;
+    while let Some(index) = rest.find(['{', '}']) {
+        latex.push_str(&rest[..index]);
+        let brace = &rest[index..];
+        if brace.starts_with("{{") || brace.starts_with("}}") {
+            latex.push_str(&brace[..1]);
+            rest = &brace[2..];
+        } else if brace.starts_with('{') {
+            let Some(end) = brace.find('}') else {
+                return Err(syn::Error::new(span, "unterminated format placeholder"));
+            };
+            let Some(placeholder) = candidates.next() else {
+                return Err(syn::Error::new(span, "too many format placeholders"));
+            };
+            latex.push(placeholder);
+            placeholders.push((placeholder, &brace[..=end]));
+            rest = &brace[end + 1..];
+        } else {
+            return Err(syn::Error::new(span, "unmatched `}` in format string"));
+        }
+    }
+    latex.push_str(rest);
+    Ok((latex, placeholders))
+}
+
+/// Escape the braces in the converted text, and restore the placeholders.
+fn restore_placeholders(
+    converted: &str,
+    placeholders: &[(char, &str)],
+    span: Span,
+) -> syn::Result<String> {
+    let indices = placeholders
+        .iter()
+        .enumerate()
+        .map(|(index, &(placeholder, _))| (placeholder, index))
+        .collect::<HashMap<char, usize>>();
+    let mut format = String::with_capacity(converted.len());
+    let mut seen = vec![false; placeholders.len()];
+    for c in converted.chars() {
+        match c {
+            '{' => format.push_str("{{"),
+            '}' => format.push_str("}}"),
+            _ => match indices.get(&c) {
+                Some(&index) if !seen[index] => {
+                    seen[index] = true;
+                    format.push_str(placeholders[index].1);
+                }
+                _ => format.push(c),
+            },
+        }
+    }
+    if seen.contains(&false) {
+        return Err(syn::Error::new(
+            span,
+            "format placeholder was consumed by a LaTeX command",
+        ));
+    }
+    Ok(format)
+}
+
+/// Convert a LaTeX format string to Unicode at compile time,
+/// then format it like [`format!`].
+///
+/// Literal braces in the LaTeX must be escaped as `{{` and `}}`,
+/// like in any other format string.
+/// Placeholders like `{}` and `{name:?}` are left unconverted,
+/// so the arguments are inserted as they are.
+///
+/// ```
+/// use unicodeit_macros::uformat;
+/// let n = 3;
+/// assert_eq!(uformat!(r"x^{{12}} \leq {n} \cdot {}", "y"), "x\u{b9}\u{b2} \u{2264} 3 \u{22c5} y");
+/// ```
+///
+/// Like [`unicodeit!`], any command which cannot be converted is a compile error:
+///
+/// ```compile_fail
+/// let text = unicodeit_macros::uformat!(r"\alpah = {}", 1);
+/// ```
+#[proc_macro]
+pub fn uformat(input: TokenStream) -> TokenStream {
+    let FormatInput { format, arguments } = parse_macro_input!(input as FormatInput);
+    let span = format.span();
+    let value = format.value();
+    let result = protect_placeholders(&value, span)
+        .and_then(|(latex, placeholders)| {
+            let converted = convert(&latex, span)?;
+            restore_placeholders(&converted, &placeholders, span)
+        })
+        .map(|converted| LitStr::new(&converted, span));
+    match result {
+        Ok(converted) if arguments.is_empty() => quote!(::std::format!(#converted)).into(),
+        Ok(converted) => quote!(::std::format!(#converted, #arguments)).into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
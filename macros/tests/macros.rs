@@ -0,0 +1,32 @@
+//! Tests for the [`unicodeit!`] and [`uformat!`] macros.
+
+use unicodeit_macros::{uformat, unicodeit};
+
+#[test]
+fn matches_replace() {
+    const CONST: &str = unicodeit!(r"\mathbb{R} \rightarrow \hat{a}");
+    assert_eq!(CONST, unicodeit::replace(r"\mathbb{R} \rightarrow \hat{a}"));
+    assert_eq!(unicodeit!("plain text"), "plain text");
+    assert_eq!(unicodeit!("\\alpha_{12}"), "\u{3b1}\u{2081}\u{2082}");
+}
+
+#[test]
+fn format() {
+    let name = "f";
+    assert_eq!(uformat!(r"\alpha"), "\u{3b1}");
+    assert_eq!(
+        uformat!(r"{name}: \mathbb{{R}} \to {}", 0),
+        "f: \u{211d} \u{2192} 0"
+    );
+    assert_eq!(uformat!(r"{:?} \in \{{{}\}}", 'x', 1), "'x' \u{2208} {1}");
+    assert_eq!(uformat!(r"x^{{2}} {0:>3}", 1,), "x\u{b2}   1");
+}
+
+#[test]
+fn format_private_use_characters() {
+    assert_eq!(
+        uformat!("\u{e000}{} \u{100000}{}", 1, 2),
+        "\u{e000}1 \u{100000}2"
+    );
+    assert_eq!(uformat!(r"\blacklozenge {}", 1), "\u{e80b} 1");
+}
@@ -0,0 +1,1250 @@
+//! A configurable version of [`crate::replace`].
+
+use crate::delimiters::Piece;
+use crate::diagnostics::KnownCommands;
+use crate::macros::Definitions;
+use crate::mapping::MappingTracker;
+use crate::optimized_replace::Engine;
+use crate::passes::{BuiltinPass, PassKind, changed_range};
+use crate::{
+    ConversionError, Diagnostic, MacroError, Macros, MathDelimiters, OffsetMap, Passes,
+    ScriptFallback, Tokens,
+};
+use std::borrow::Cow;
+use std::fmt::{self, Display, Formatter};
+use std::ops::Range;
+use std::sync::{LazyLock, OnceLock};
+
+/// A table of `(latex, unicode)` pairs, like those in [`crate::data`].
+///
+/// Longer keys always come first, so they take priority over their prefixes.
+pub(crate) type SymbolTable = Vec<(Cow<'static, str>, Cow<'static, str>)>;
+
+/// The commands for math alphabets like `\mathbb{R}`,
+/// as used by [`ConverterBuilder::math_alphabets`].
+const MATH_ALPHABET_COMMANDS: &[&str] = &[
+    r"\mathsfbfit",
+    r"\mathsfbf",
+    r"\mathsfit",
+    r"\mathbfit",
+    r"\mathbold",
+    r"\mathfrak",
+    r"\mathcal",
+    r"\mathbf",
+    r"\mathbb",
+    r"\mathds",
+    r"\mathit",
+    r"\mathrm",
+    r"\mathsf",
+    r"\mathtt",
+];
+
+/// Wide accents and similar commands, with the combining mark each is converted like,
+/// as used by [`ConverterBuilder::wide_accents`].
+const WIDE_ACCENTS: &[(&str, &str)] = &[
+    (r"\overrightarrow", r"\vec"),
+    (r"\underbrace", r"\underline"),
+    (r"\widetilde", r"\tilde"),
+    (r"\overbrace", r"\overline"),
+    (r"\widehat", r"\hat"),
+    (r"\cancel", r"\slash"),
+];
+
+/// The closest alternatives for scripts with no exact Unicode equivalent,
+/// as used by [`ScriptFallback::Closest`].
+const CLOSEST_SCRIPTS: &[(&str, &str)] = &[
+    // modifier letters missing from the original tables
+    ("^C", "\u{a7f2}"),
+    ("^F", "\u{a7f3}"),
+    ("^Q", "\u{a7f4}"),
+    ("^q", "\u{107a5}"),
+    // small capitals, or small letters if there is no small capital
+    ("^Y", "\u{107b2}"),
+    ("^S", "\u{2e2}"),
+    ("^X", "\u{2e3}"),
+    ("^Z", "\u{1dbb}"),
+    ("_A", "\u{2090}"),
+    ("_E", "\u{2091}"),
+    ("_H", "\u{2095}"),
+    ("_I", "\u{1d62}"),
+    ("_J", "\u{2c7c}"),
+    ("_K", "\u{2096}"),
+    ("_L", "\u{2097}"),
+    ("_M", "\u{2098}"),
+    ("_N", "\u{2099}"),
+    ("_O", "\u{2092}"),
+    ("_P", "\u{209a}"),
+    ("_R", "\u{1d63}"),
+    ("_S", "\u{209b}"),
+    ("_T", "\u{209c}"),
+    ("_U", "\u{1d64}"),
+    ("_V", "\u{1d65}"),
+    ("_X", "\u{2093}"),
+];
+
+/// Determine if a replacement is part of a math alphabet like `\mathbb{R}`.
+fn is_math_alphabet(latex: &str) -> bool {
+    MATH_ALPHABET_COMMANDS.iter().any(|command| {
+        latex
+            .strip_prefix(command)
+            .is_some_and(|rest| rest.starts_with('{'))
+    })
+}
+
+/// The result of [`Converter::convert`].
+pub(crate) struct Conversion {
+    pub(crate) output: String,
+    /// The offset map, if requested.
+    pub(crate) map: Option<OffsetMap>,
+    /// Macro invocations which were left unexpanded because they exceeded a limit.
+    pub(crate) macro_failures: Vec<MacroError>,
+    /// The limit which stopped the conversion, if any.
+    pub(crate) limit_exceeded: Option<ConversionLimit>,
+    /// The conversion of each piece of math and its range in the input,
+    /// if [math delimiters](ConverterBuilder::math_delimiters) are enabled and the map was requested.
+    pub(crate) math: Vec<(Range<usize>, Conversion)>,
+}
+
+/// Append the conversion of a piece of the input,
+/// returning where the input was cut off if the output limit was exceeded.
+fn append_piece(
+    output: &mut String,
+    edits: &mut Vec<(Range<usize>, usize)>,
+    macro_failures: &mut Vec<MacroError>,
+    range: &Range<usize>,
+    conversion: &Conversion,
+) -> Option<usize> {
+    output.push_str(&conversion.output);
+    if let Some(map) = &conversion.map {
+        edits.extend(
+            map.segments()
+                .iter()
+                .filter(|segment| !segment.unchanged)
+                .map(|segment| {
+                    let input = &segment.input;
+                    (
+                        range.start + input.start..range.start + input.end,
+                        segment.output.len(),
+                    )
+                }),
+        );
+    }
+    macro_failures.extend(conversion.macro_failures.iter().map(|failure| MacroError {
+        span: range.start + failure.span.start..range.start + failure.span.end,
+        ..failure.clone()
+    }));
+    conversion.limit_exceeded.map(|_| range.end)
+}
+
+/// The limit exceeded by a conversion,
+/// as reported by [`DiagnosticKind::LimitExceeded`](crate::DiagnosticKind::LimitExceeded).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ConversionLimit {
+    /// The input was too long, set by [`ConverterBuilder::max_input_len`].
+    InputLength,
+    /// The output was too long, set by [`ConverterBuilder::max_output_len`].
+    OutputLength,
+}
+
+impl Display for ConversionLimit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ConversionLimit::InputLength => "input length",
+            ConversionLimit::OutputLength => "output length",
+        })
+    }
+}
+
+pub(crate) static DEFAULT_CONVERTER: LazyLock<Converter> = LazyLock::new(Converter::default);
+pub(crate) static STRICT_CONVERTER: LazyLock<Converter> =
+    LazyLock::new(|| Converter::builder().strict(true).build());
+
+/// Converts LaTeX to Unicode, with individual phases of the conversion configurable.
+///
+/// The default converter behaves exactly like [`crate::replace`].
+/// A converter is intended to be built once and reused,
+/// and can be shared between threads.
+///
+/// ```
+/// use unicodeit::Converter;
+/// let converter = Converter::builder().sub_super_scripts(false).build();
+/// assert_eq!(converter.replace(r"\alpha^2"), "\u{3b1}^2");
+/// ```
+#[derive(Clone, Debug)]
+pub struct Converter {
+    pub(crate) strict: bool,
+    pub(crate) macros: Option<Macros>,
+    pub(crate) replacements: SymbolTable,
+    pub(crate) combining_marks: SymbolTable,
+    pub(crate) sub_super_scripts: SymbolTable,
+    pub(crate) max_input_len: Option<usize>,
+    pub(crate) max_output_len: Option<usize>,
+    pub(crate) math_delimiters: Option<MathDelimiters>,
+    pub(crate) parse_latex: bool,
+    pub(crate) combining_groups: bool,
+    pub(crate) script_fallback: ScriptFallback,
+    pub(crate) passes: Passes,
+    pub(crate) engine: Engine,
+    /// The commands used for diagnostics, built the first time they are needed.
+    pub(crate) known_commands: OnceLock<KnownCommands>,
+}
+
+impl Converter {
+    /// Create a builder to configure a new converter.
+    #[inline]
+    pub fn builder() -> ConverterBuilder {
+        ConverterBuilder::new()
+    }
+
+    /// Run every stage of the conversion,
+    /// adding any macros defined in the text to `local`.
+    ///
+    /// The length limits are not applied,
+    /// since streams are converted a piece at a time.
+    pub(crate) fn convert(&self, text: &str, mapping: bool, local: &mut Definitions) -> Conversion {
+        self.convert_with_limit(text, mapping, None, local)
+    }
+
+    /// Run every stage of the conversion, applying the length limits.
+    fn convert_limited(&self, text: &str, mapping: bool, local: &mut Definitions) -> Conversion {
+        if self
+            .max_input_len
+            .is_some_and(|max_len| text.len() > max_len)
+        {
+            let mut tracker = MappingTracker::new(mapping, text);
+            tracker.record(text.len(), [(0..text.len(), 0)]);
+            return Conversion {
+                output: String::new(),
+                map: tracker.finish(),
+                macro_failures: Vec::new(),
+                limit_exceeded: Some(ConversionLimit::InputLength),
+                math: Vec::new(),
+            };
+        }
+        self.convert_with_limit(text, mapping, self.max_output_len, local)
+    }
+
+    fn convert_with_limit(
+        &self,
+        text: &str,
+        mapping: bool,
+        max_output_len: Option<usize>,
+        local: &mut Definitions,
+    ) -> Conversion {
+        let Some(delimiters) = &self.math_delimiters else {
+            return self.convert_math(text, mapping, max_output_len, local);
+        };
+        self.convert_pieces(text, delimiters.split(text), mapping, max_output_len, local)
+    }
+
+    /// Convert text split into pieces, leaving prose unchanged and removing delimiters.
+    fn convert_pieces(
+        &self,
+        text: &str,
+        pieces: Vec<Piece>,
+        mapping: bool,
+        max_output_len: Option<usize>,
+        local: &mut Definitions,
+    ) -> Conversion {
+        let mut output = String::with_capacity(text.len());
+        let mut edits = Vec::new();
+        let mut macro_failures = Vec::new();
+        let mut math = Vec::new();
+        let mut limit_exceeded = None;
+        for piece in pieces {
+            let remaining = max_output_len.map_or(usize::MAX, |max_len| max_len - output.len());
+            let cut_off = match piece {
+                Piece::Prose(range) => {
+                    let mut end = range.start + range.len().min(remaining);
+                    while !text.is_char_boundary(end) {
+                        end -= 1;
+                    }
+                    output.push_str(&text[range.start..end]);
+                    (end < range.end).then_some(end)
+                }
+                Piece::Escape(range) => {
+                    let escaped = &text[range.start + 1..range.end];
+                    if escaped.len() > remaining {
+                        Some(range.start)
+                    } else {
+                        output.push_str(escaped);
+                        edits.push((range, escaped.len()));
+                        None
+                    }
+                }
+                Piece::Delimiter(range) => {
+                    edits.push((range, 0));
+                    None
+                }
+                Piece::Math(range) => {
+                    let limit = max_output_len.map(|_| remaining);
+                    let conversion = self.convert_math(&text[range.clone()], mapping, limit, local);
+                    let cut_off = append_piece(
+                        &mut output,
+                        &mut edits,
+                        &mut macro_failures,
+                        &range,
+                        &conversion,
+                    );
+                    if mapping {
+                        math.push((range, conversion));
+                    }
+                    cut_off
+                }
+                #[cfg(feature = "markdown")]
+                Piece::Text(range) => {
+                    let limit = max_output_len.map(|_| remaining);
+                    let conversion =
+                        self.convert_with_limit(&text[range.clone()], mapping, limit, local);
+                    append_piece(
+                        &mut output,
+                        &mut edits,
+                        &mut macro_failures,
+                        &range,
+                        &conversion,
+                    )
+                }
+            };
+            if let Some(cut_off) = cut_off {
+                edits.push((cut_off..text.len(), 0));
+                limit_exceeded = Some(ConversionLimit::OutputLength);
+                break;
+            }
+        }
+        let mut tracker = MappingTracker::new(mapping, text);
+        tracker.record(text.len(), edits);
+        Conversion {
+            output,
+            map: tracker.finish(),
+            macro_failures,
+            limit_exceeded,
+            math,
+        }
+    }
+
+    /// Convert text which is entirely math, ignoring any math delimiters,
+    /// by running each of the passes in order.
+    fn convert_math(
+        &self,
+        text: &str,
+        mapping: bool,
+        max_output_len: Option<usize>,
+        local: &mut Definitions,
+    ) -> Conversion {
+        let mut tracker = MappingTracker::new(mapping, text);
+        let mut text = Cow::Borrowed(text);
+        let mut macro_failures = Vec::new();
+        let mut limit_exceeded = None;
+        for pass in &self.passes.passes {
+            match pass {
+                PassKind::Builtin(BuiltinPass::Macros) => {
+                    let Some(macros) = &self.macros else {
+                        continue;
+                    };
+                    let expansion = macros.expand_with(&text, local);
+                    // earlier custom passes may have changed the text the failures were found in
+                    macro_failures.extend(expansion.failures.into_iter().map(|failure| {
+                        MacroError {
+                            span: tracker.input_range(failure.span.clone()),
+                            ..failure
+                        }
+                    }));
+                    tracker.record(text.len(), expansion.edits);
+                    if let Cow::Owned(expanded) = expansion.text {
+                        text = Cow::Owned(expanded);
+                    }
+                }
+                PassKind::Builtin(BuiltinPass::Symbols) => {
+                    let mut output = String::with_capacity(text.len());
+                    let mut edits = Vec::new();
+                    let cut_off = self.engine.replace_with_edits(
+                        &text,
+                        &mut output,
+                        &mut edits,
+                        max_output_len,
+                    );
+                    if cut_off.is_some() {
+                        limit_exceeded = Some(ConversionLimit::OutputLength);
+                    }
+                    tracker.record(text.len(), edits);
+                    text = Cow::Owned(output);
+                }
+                PassKind::Custom(pass) => {
+                    if let Cow::Owned(rewritten) = pass.apply(&text)
+                        && rewritten != *text
+                    {
+                        let (range, new_len) = changed_range(&text, &rewritten);
+                        tracker.record(text.len(), [(range, new_len)]);
+                        text = Cow::Owned(rewritten);
+                    }
+                }
+            }
+        }
+        // custom passes after the symbols may have made the output longer
+        if let Some(max_len) = max_output_len
+            && text.len() > max_len
+        {
+            let mut end = max_len;
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            tracker.record(text.len(), [(end..text.len(), 0)]);
+            text.to_mut().truncate(end);
+            limit_exceeded = Some(ConversionLimit::OutputLength);
+        }
+        Conversion {
+            output: text.into_owned(),
+            map: tracker.finish(),
+            macro_failures,
+            limit_exceeded,
+            math: Vec::new(),
+        }
+    }
+
+    /// Replace the LaTeX characters with Unicode equivalents wherever possible,
+    /// using only the enabled phases of the conversion.
+    ///
+    /// If the [input limit](ConverterBuilder::max_input_len) is exceeded, the output is empty.
+    /// If the [output limit](ConverterBuilder::max_output_len) is exceeded,
+    /// only the part of the input converted before reaching the limit is returned.
+    /// Use [`Converter::try_replace`] to detect either.
+    pub fn replace(&self, text: &str) -> String {
+        self.replace_cow(text).into_owned()
+    }
+
+    /// Replace the LaTeX characters with Unicode equivalents,
+    /// borrowing the input if it cannot contain anything to convert.
+    ///
+    /// The input is borrowed if it contains no `\`, `^` or `_`,
+    /// nor the first character of any other command, like the `-` in `a-b` (which becomes `a−b`).
+    /// With [math delimiters](ConverterBuilder::math_delimiters),
+    /// it is borrowed if it contains no character starting an opening delimiter instead, like `$`.
+    /// Without math delimiters but with custom [passes](ConverterBuilder::passes),
+    /// it is never borrowed.
+    /// The converted text is always the same as [`Converter::replace`].
+    ///
+    /// ```
+    /// use std::borrow::Cow;
+    /// let converter = unicodeit::Converter::default();
+    /// assert!(matches!(converter.replace_cow("plain text"), Cow::Borrowed("plain text")));
+    /// assert_eq!(converter.replace_cow(r"\alpha"), "\u{3b1}");
+    /// ```
+    pub fn replace_cow<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if self.is_unchanged(text) && self.within_limits(text) {
+            Cow::Borrowed(text)
+        } else {
+            Cow::Owned(
+                self.convert_limited(text, false, &mut Definitions::new())
+                    .output,
+            )
+        }
+    }
+
+    /// Append the converted text to `out`.
+    ///
+    /// Unless [macros](ConverterBuilder::macros), [math delimiters](ConverterBuilder::math_delimiters),
+    /// custom [passes](ConverterBuilder::passes) or an [output limit](ConverterBuilder::max_output_len)
+    /// are enabled,
+    /// the text is converted directly into `out`,
+    /// without building the output as a separate string first.
+    /// The converted text is always the same as [`Converter::replace`].
+    pub fn replace_to_string(&self, text: &str, out: &mut String) {
+        if self.is_direct(text) {
+            self.engine
+                .replace_into(text, out)
+                .expect("writing to a string cannot fail");
+        } else {
+            out.push_str(&self.replace_cow(text));
+        }
+    }
+
+    /// Write the converted text to `out`.
+    ///
+    /// Unless [macros](ConverterBuilder::macros), [math delimiters](ConverterBuilder::math_delimiters),
+    /// custom [passes](ConverterBuilder::passes) or an [output limit](ConverterBuilder::max_output_len)
+    /// are enabled,
+    /// the text is converted directly into `out`,
+    /// without building the output as a separate string first.
+    /// The converted text is always the same as [`Converter::replace`].
+    ///
+    /// ```
+    /// use std::fmt::Write;
+    /// let mut label = String::new();
+    /// for (i, latex) in [r"\alpha", r"\beta"].into_iter().enumerate() {
+    ///     write!(label, "{i}: ")?;
+    ///     unicodeit::Converter::default().replace_into(latex, &mut label)?;
+    ///     label.push('\n');
+    /// }
+    /// assert_eq!(label, "0: \u{3b1}\n1: \u{3b2}\n");
+    /// # Ok::<(), std::fmt::Error>(())
+    /// ```
+    pub fn replace_into<W: fmt::Write + ?Sized>(&self, text: &str, out: &mut W) -> fmt::Result {
+        if self.is_direct(text) {
+            self.engine.replace_into(text, out)
+        } else {
+            out.write_str(&self.replace_cow(text))
+        }
+    }
+
+    /// Determine if the text cannot contain anything to convert.
+    fn is_unchanged(&self, text: &str) -> bool {
+        match &self.math_delimiters {
+            Some(delimiters) => delimiters.is_prose(text),
+            None => self.passes.is_default() && self.engine.is_unchanged(text),
+        }
+    }
+
+    /// Determine if the text is within the length limits without converting it.
+    fn within_limits(&self, text: &str) -> bool {
+        [self.max_input_len, self.max_output_len]
+            .into_iter()
+            .flatten()
+            .all(|max_len| text.len() <= max_len)
+    }
+
+    /// Determine if the text can be converted directly into the output,
+    /// without any intermediate text.
+    fn is_direct(&self, text: &str) -> bool {
+        self.macros.is_none()
+            && self.math_delimiters.is_none()
+            && self.passes.is_default()
+            && self.max_output_len.is_none()
+            && self
+                .max_input_len
+                .is_none_or(|max_len| text.len() <= max_len)
+    }
+
+    /// Split the text into [`Token`](crate::Token)s using the enabled phases of the conversion,
+    /// describing which parts are converted and how.
+    ///
+    /// The tokens only describe the conversion of symbols, scripts and combining marks,
+    /// so [macros](ConverterBuilder::macros), [math delimiters](ConverterBuilder::math_delimiters),
+    /// [length limits](ConverterBuilder::max_input_len), [parsing](ConverterBuilder::parse_latex),
+    /// normalization and custom [passes](ConverterBuilder::passes) are ignored.
+    /// Otherwise, concatenating their converted text gives the same output as [`Converter::replace`].
+    ///
+    /// ```
+    /// use unicodeit::{Converter, Token};
+    /// let converter = Converter::builder().sub_super_scripts(false).build();
+    /// let tokens: Vec<Token> = converter.tokens(r"\alpha^2").collect();
+    /// assert_eq!(
+    ///     tokens,
+    ///     [
+    ///         Token::Symbol { latex: r"\alpha", unicode: "α" },
+    ///         Token::Unknown("^"),
+    ///         Token::Literal("2"),
+    ///     ]
+    /// );
+    /// ```
+    pub fn tokens<'a>(&'a self, text: &'a str) -> Tokens<'a> {
+        self.engine.tokens(text)
+    }
+
+    /// Replace the LaTeX characters with Unicode equivalents,
+    /// also returning a map between offsets in the input and the output.
+    ///
+    /// The converted text is always the same as [`Converter::replace`].
+    pub fn replace_with_mapping(&self, text: &str) -> (String, OffsetMap) {
+        let conversion = self.convert_limited(text, true, &mut Definitions::new());
+        (conversion.output, conversion.map.unwrap())
+    }
+
+    /// Replace the LaTeX characters with Unicode equivalents,
+    /// also returning diagnostics for any input which could not be converted.
+    ///
+    /// The converted text is always the same as [`Converter::replace`].
+    /// Diagnostics are sorted by their position in the input,
+    /// and include unknown commands (with suggestions for likely typos),
+    /// combining marks missing their argument,
+    /// unsupported subscripts and superscripts, and unbalanced braces.
+    ///
+    /// ```
+    /// use unicodeit::{Converter, DiagnosticKind};
+    /// let (output, diagnostics) = Converter::default().replace_with_diagnostics(r"\rightarow");
+    /// assert_eq!(output, r"\rightarow");
+    /// assert_eq!(diagnostics[0].span, 0..10);
+    /// assert!(matches!(
+    ///     &diagnostics[0].kind,
+    ///     DiagnosticKind::UnknownCommand { suggestions, .. } if suggestions[0] == r"\rightarrow"
+    /// ));
+    /// ```
+    pub fn replace_with_diagnostics(&self, text: &str) -> (String, Vec<Diagnostic>) {
+        let mut local = Definitions::new();
+        let conversion = self.convert_limited(text, true, &mut local);
+        let diagnostics = crate::diagnostics::check(self, text, &conversion, &local);
+        (conversion.output, diagnostics)
+    }
+
+    /// Replace the LaTeX characters with Unicode equivalents,
+    /// failing if any of the input could not be converted.
+    ///
+    /// If the converter is [strict](ConverterBuilder::strict),
+    /// this fails on any [diagnostic](Converter::replace_with_diagnostics),
+    /// including unknown commands and unsupported subscripts or superscripts.
+    /// Otherwise, this only fails on malformed input with [`Severity::Error`](crate::Severity::Error),
+    /// like combining marks missing their argument and unbalanced braces.
+    ///
+    /// The error lists every failure, not just the first.
+    ///
+    /// ```
+    /// use unicodeit::Converter;
+    /// let converter = Converter::builder().strict(true).build();
+    /// assert_eq!(converter.try_replace(r"\alpha^2").unwrap(), "\u{3b1}\u{b2}");
+    /// let error = converter.try_replace(r"\alpha^Q + \unknown").unwrap_err();
+    /// assert_eq!(error.failures().len(), 2);
+    /// assert_eq!(error.span(), 6..8);
+    /// ```
+    pub fn try_replace(&self, text: &str) -> Result<String, ConversionError> {
+        let (output, diagnostics) = self.replace_with_diagnostics(text);
+        crate::diagnostics::check_failures(output, diagnostics, self.strict)
+    }
+
+    /// Replace the LaTeX characters with Unicode equivalents in a Markdown document,
+    /// only converting text and math.
+    ///
+    /// Code spans, code blocks, HTML, autolinks like `<https://example.com>`,
+    /// bare URLs like `https://example.com/a_1` and front matter
+    /// are left unchanged, as are characters escaped with a backslash like `\_`.
+    /// Math like `$x_1$` or `$$\alpha$$` is converted and its delimiters are removed.
+    /// The rest of the document, including all of the Markdown syntax, is reproduced exactly.
+    ///
+    /// Macros defined in one paragraph can be used in later paragraphs,
+    /// and the [length limits](ConverterBuilder::max_input_len) apply to the whole document.
+    ///
+    /// ```
+    /// let converter = unicodeit::Converter::default();
+    /// assert_eq!(
+    ///     converter.replace_markdown("# The set $\\mathbb{R}$\n\nUse `snake_case` for \\alpha_1."),
+    ///     "# The set \u{211d}\n\nUse `snake_case` for \u{3b1}\u{2081}."
+    /// );
+    /// ```
+    #[cfg(feature = "markdown")]
+    pub fn replace_markdown(&self, text: &str) -> String {
+        if self
+            .max_input_len
+            .is_some_and(|max_len| text.len() > max_len)
+        {
+            return String::new();
+        }
+        let pieces = crate::markdown::split(text);
+        self.convert_pieces(
+            text,
+            pieces,
+            false,
+            self.max_output_len,
+            &mut Definitions::new(),
+        )
+        .output
+    }
+}
+
+impl Default for Converter {
+    #[inline]
+    fn default() -> Self {
+        ConverterBuilder::new().build()
+    }
+}
+
+/// Configures a [`Converter`].
+///
+/// Every phase and symbol category is enabled by default,
+/// while [strict mode](Self::strict) and [macro expansion](Self::macros) are disabled.
+#[derive(Clone, Debug)]
+pub struct ConverterBuilder {
+    strict: bool,
+    macros: Option<Macros>,
+    max_input_len: Option<usize>,
+    max_output_len: Option<usize>,
+    math_delimiters: Option<MathDelimiters>,
+    parse_latex: bool,
+    passes: Passes,
+    not_rewrite: bool,
+    combining_marks: bool,
+    replacements: bool,
+    math_alphabets: bool,
+    script_groups: bool,
+    script_fallback: ScriptFallback,
+    combining_groups: bool,
+    wide_accents: bool,
+    #[cfg(feature = "normalization")]
+    normalization: crate::Normalization,
+    sub_super_scripts: bool,
+    custom_replacements: SymbolTable,
+    custom_combining_marks: SymbolTable,
+    custom_sub_super_scripts: SymbolTable,
+}
+
+impl ConverterBuilder {
+    /// Create a builder with every phase of the conversion enabled.
+    pub fn new() -> Self {
+        ConverterBuilder {
+            strict: false,
+            macros: None,
+            max_input_len: None,
+            max_output_len: None,
+            math_delimiters: None,
+            parse_latex: false,
+            passes: Passes::new(),
+            not_rewrite: true,
+            combining_marks: true,
+            replacements: true,
+            math_alphabets: true,
+            script_groups: true,
+            script_fallback: ScriptFallback::Unchanged,
+            combining_groups: false,
+            wide_accents: false,
+            #[cfg(feature = "normalization")]
+            normalization: crate::Normalization::None,
+            sub_super_scripts: true,
+            custom_replacements: Vec::new(),
+            custom_combining_marks: Vec::new(),
+            custom_sub_super_scripts: Vec::new(),
+        }
+    }
+
+    /// Make [`Converter::try_replace`] fail on any input that could not be converted,
+    /// instead of only on malformed input.
+    ///
+    /// This is disabled by default, and has no effect on [`Converter::replace`].
+    pub fn strict(mut self, enabled: bool) -> Self {
+        self.strict = enabled;
+        self
+    }
+
+    /// Expand macros like `\newcommand{\norm}[1]{\lVert #1 \rVert}` before converting,
+    /// using the definitions in the preamble as well as any in the text itself.
+    ///
+    /// This is disabled by default, so definitions are left unchanged.
+    /// This affects where the input is split when [streaming](crate::ReplaceWriter#splitting).
+    pub fn macros(mut self, macros: Macros) -> Self {
+        self.macros = Some(macros);
+        self
+    }
+
+    /// Refuse to convert input longer than this many bytes.
+    ///
+    /// Conversion takes time linear in the length of the input,
+    /// except that [macros](ConverterBuilder::macros) can expand to more text,
+    /// up to their [length limit](crate::Macros::max_len).
+    /// Services converting untrusted input may still want to reject large payloads outright.
+    /// Longer input converts to an empty string,
+    /// and [`Converter::try_replace`] fails with [`DiagnosticKind::LimitExceeded`](crate::DiagnosticKind::LimitExceeded).
+    ///
+    /// There is no limit by default,
+    /// and the limit does not apply when [streaming](crate::ReplaceWriter).
+    ///
+    /// ```
+    /// let converter = unicodeit::Converter::builder().max_input_len(8).build();
+    /// assert_eq!(converter.replace(r"\alpha"), "\u{3b1}");
+    /// assert!(converter.try_replace(r"\alpha + \beta").is_err());
+    /// ```
+    pub fn max_input_len(mut self, len: usize) -> Self {
+        self.max_input_len = Some(len);
+        self
+    }
+
+    /// Stop converting once the output would be longer than this many bytes.
+    ///
+    /// The output is cut off before the first piece of the input which does not fit,
+    /// and [`Converter::try_replace`] fails with [`DiagnosticKind::LimitExceeded`](crate::DiagnosticKind::LimitExceeded).
+    ///
+    /// There is no limit by default,
+    /// and the limit does not apply when [streaming](crate::ReplaceWriter).
+    ///
+    /// ```
+    /// let converter = unicodeit::Converter::builder().max_output_len(4).build();
+    /// assert_eq!(converter.replace(r"\alpha\beta\gamma"), "\u{3b1}\u{3b2}");
+    /// assert!(converter.try_replace(r"\alpha\beta\gamma").is_err());
+    /// ```
+    pub fn max_output_len(mut self, len: usize) -> Self {
+        self.max_output_len = Some(len);
+        self
+    }
+
+    /// Only convert math between delimiters like `$...$`, leaving the surrounding prose unchanged.
+    ///
+    /// The delimiters are removed from the output,
+    /// so this is suitable for messages which mix prose and math,
+    /// where prose like `snake_case` or `C:\new` must not be converted.
+    /// See [`MathDelimiters`] for the exact rules.
+    ///
+    /// This is disabled by default, so all of the text is treated as math.
+    /// This affects where the input is split when [streaming](crate::ReplaceWriter#splitting).
+    ///
+    /// ```
+    /// use unicodeit::{Converter, MathDelimiters};
+    /// let converter = Converter::builder()
+    ///     .math_delimiters(MathDelimiters::new())
+    ///     .build();
+    /// assert_eq!(converter.replace(r"see C:\new_files for \(a_1\)"), "see C:\\new_files for a\u{2081}");
+    /// ```
+    pub fn math_delimiters(mut self, delimiters: MathDelimiters) -> Self {
+        self.math_delimiters = Some(delimiters);
+        self
+    }
+
+    /// Convert by walking the [syntax tree](crate::parse) of the input,
+    /// instead of matching each command in the text like the original library.
+    ///
+    /// Braces are matched, so combining marks apply to their whole argument like `\hat{ab}`,
+    /// groups of scripts may contain commands and nested groups like `x^{(n+1)}` or `x_{\beta\gamma}`,
+    /// and commands are only replaced as a whole, so `\alphax` is left unchanged.
+    /// Comments like `% note` are left unchanged,
+    /// and unbalanced braces are kept as they are without affecting the rest of the input.
+    ///
+    /// This is disabled by default, since the output differs from [`crate::replace`].
+    /// This affects where the input is split when [streaming](crate::ReplaceWriter#splitting).
+    ///
+    /// ```
+    /// let converter = unicodeit::Converter::builder().parse_latex(true).build();
+    /// assert_eq!(converter.replace(r"\hat{ab}"), "a\u{302}b\u{302}");
+    /// assert_eq!(converter.replace(r"x^{(n+1)}"), "x\u{207d}\u{207f}\u{207a}\u{b9}\u{207e}");
+    /// assert_eq!(unicodeit::replace(r"\hat{ab}"), "\\hat{ab}");
+    /// ```
+    pub fn parse_latex(mut self, enabled: bool) -> Self {
+        self.parse_latex = enabled;
+        self
+    }
+
+    /// Run custom passes before or after the built-in passes, or reorder the built-in passes.
+    ///
+    /// By default, [macros](Self::macros) are expanded and then the symbols are converted,
+    /// as with [`Passes::new`].
+    /// Custom passes are run on each piece of math separately,
+    /// so they never see the prose around [math delimiters](Self::math_delimiters).
+    ///
+    /// ```
+    /// use unicodeit::{BuiltinPass, Converter, Passes};
+    /// let converter = Converter::builder()
+    ///     .passes(Passes::new().add_before(BuiltinPass::Symbols, |text: &str| {
+    ///         text.replace(r"\displaystyle", "")
+    ///     }))
+    ///     .build();
+    /// assert_eq!(converter.replace(r"\displaystyle\alpha"), "\u{3b1}");
+    /// ```
+    pub fn passes(mut self, passes: Passes) -> Self {
+        self.passes = passes;
+        self
+    }
+
+    /// Rewrite negations like `\not\in` into `\slash{\in}`.
+    ///
+    /// This relies on the `\slash` combining mark,
+    /// so has no effect if [combining marks](Self::combining_marks) are disabled.
+    pub fn not_rewrite(mut self, enabled: bool) -> Self {
+        self.not_rewrite = enabled;
+        self
+    }
+
+    /// Apply combining marks like `\hat{a}` and `\dot{\alpha}`.
+    ///
+    /// The mark is placed after the whole extended grapheme cluster of its argument,
+    /// so marks already in the input like `\hat{e\u{301}}` stay with their base.
+    /// Nested marks like `\dot{\hat{a}}` are applied from the innermost outwards,
+    /// after any marks already in the input.
+    ///
+    /// ```
+    /// let converter = unicodeit::Converter::default();
+    /// assert_eq!(converter.replace("\\hat{e\u{301}}"), "e\u{301}\u{302}");
+    /// assert_eq!(converter.replace(r"\dot{\hat{a}}"), "a\u{302}\u{307}");
+    /// ```
+    ///
+    /// If disabled, only the standalone forms with empty braces like `\hat{}`
+    /// are converted, as ordinary [replacements](Self::replacements).
+    pub fn combining_marks(mut self, enabled: bool) -> Self {
+        self.combining_marks = enabled;
+        self
+    }
+
+    /// Replace symbol commands like `\alpha` and `\rightarrow`.
+    pub fn replacements(mut self, enabled: bool) -> Self {
+        self.replacements = enabled;
+        self
+    }
+
+    /// Replace math alphabets like `\mathbb{R}` and `\mathfrak{g}`.
+    ///
+    /// These are a subset of the [replacements](Self::replacements),
+    /// so have no effect if those are disabled.
+    pub fn math_alphabets(mut self, enabled: bool) -> Self {
+        self.math_alphabets = enabled;
+        self
+    }
+
+    /// Expand groups of scripts like `x_{12}` into `x_1_2` before converting them.
+    ///
+    /// If disabled, only single characters like `x_1` are converted.
+    /// This has no effect if [sub/superscripts](Self::sub_super_scripts) are disabled.
+    pub fn script_groups(mut self, enabled: bool) -> Self {
+        self.script_groups = enabled;
+        self
+    }
+
+    /// Choose how to convert groups of scripts like `x^{Q}` or `a_{bc}`,
+    /// where a character has no Unicode subscript or superscript.
+    ///
+    /// By default, these groups are left unchanged like the original library.
+    /// The [closest alternatives](ScriptFallback::Closest) are also used for single scripts like `x^Q`,
+    /// while the other fallbacks only apply to groups.
+    /// Unless [parsing](Self::parse_latex) the input,
+    /// a group containing braces, other scripts or combining marks is always left unchanged.
+    /// With any fallback, characters with a [custom script](Self::add_sub_super_script)
+    /// are also converted inside groups.
+    ///
+    /// This has no effect if [sub/superscripts](Self::sub_super_scripts) are disabled.
+    /// A fallback which keeps part of the group affects where the input is split when [streaming](crate::ReplaceWriter#splitting).
+    ///
+    /// ```
+    /// use unicodeit::{Converter, ScriptFallback};
+    /// let convert = |fallback, text| Converter::builder().script_fallback(fallback).build().replace(text);
+    /// assert_eq!(convert(ScriptFallback::Unchanged, "x^{2Q}"), "x^{2Q}");
+    /// assert_eq!(convert(ScriptFallback::Prefix, "x^{2Q}"), "x\u{b2}^{Q}");
+    /// assert_eq!(convert(ScriptFallback::Parenthesized, "a_{bc}"), "a_(bc)");
+    /// assert_eq!(convert(ScriptFallback::Closest, "x^{2Q}"), "x\u{b2}\u{a7f4}");
+    /// ```
+    pub fn script_fallback(mut self, fallback: ScriptFallback) -> Self {
+        self.script_fallback = fallback;
+        self
+    }
+
+    /// Apply combining marks to every character of their argument, like `\overline{AB}`.
+    ///
+    /// The argument may contain symbols and scripts like `\vec{\alpha x_1}`,
+    /// but no braces or other combining marks, and whitespace is left unmarked.
+    /// Otherwise, only an argument which is a single character or symbol is combined,
+    /// like the original library.
+    ///
+    /// This is disabled by default, since the output differs from [`crate::replace`].
+    /// [Parsing](Self::parse_latex) the input always applies marks to the whole argument.
+    /// This affects where the input is split when [streaming](crate::ReplaceWriter#splitting).
+    ///
+    /// ```
+    /// let converter = unicodeit::Converter::builder().combining_groups(true).build();
+    /// assert_eq!(converter.replace(r"\overline{AB}"), "A\u{305}B\u{305}");
+    /// assert_eq!(unicodeit::replace(r"\overline{AB}"), r"\overline{AB}");
+    /// ```
+    pub fn combining_groups(mut self, enabled: bool) -> Self {
+        self.combining_groups = enabled;
+        self
+    }
+
+    /// Convert wide accents like `\widehat{a}` and `\overrightarrow{v}`
+    /// as the combining marks with the same appearance, like `\hat{a}` and `\vec{v}`.
+    ///
+    /// Braces are converted like their nearest equivalent,
+    /// so `\overbrace` and `\underbrace` become lines over and under the characters,
+    /// and `\cancel` strikes through them like `\not`.
+    /// Wide accents are usually applied to several characters,
+    /// so are most useful with [combining groups](Self::combining_groups).
+    ///
+    /// This is disabled by default, since the output differs from [`crate::replace`],
+    /// and has no effect if [combining marks](Self::combining_marks) are disabled.
+    ///
+    /// ```
+    /// let converter = unicodeit::Converter::builder()
+    ///     .wide_accents(true)
+    ///     .combining_groups(true)
+    ///     .build();
+    /// assert_eq!(converter.replace(r"\widehat{xy}"), "x\u{302}y\u{302}");
+    /// assert_eq!(converter.replace(r"\cancel{0}"), "0\u{338}");
+    /// ```
+    pub fn wide_accents(mut self, enabled: bool) -> Self {
+        self.wide_accents = enabled;
+        self
+    }
+
+    /// Normalize the output of combining marks and negations like `\not\in`,
+    /// composing them with their base into precomposed characters like `é` (NFC),
+    /// or decomposing any precomposed base (NFD).
+    ///
+    /// The rest of the output is never normalized,
+    /// since many symbols like `\nexists` would be decomposed.
+    /// By default, the marks are left after their base like the original library.
+    ///
+    /// ```
+    /// use unicodeit::{Converter, Normalization};
+    /// let converter = Converter::builder()
+    ///     .normalization(Normalization::Nfc)
+    ///     .build();
+    /// assert_eq!(converter.replace(r"\acute{e} \ddot{u} \not\in"), "\u{e9} \u{fc} \u{2209}");
+    /// ```
+    #[cfg(feature = "normalization")]
+    pub fn normalization(mut self, normalization: crate::Normalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    /// Convert subscripts and superscripts like `x_1` and `x^2`.
+    pub fn sub_super_scripts(mut self, enabled: bool) -> Self {
+        self.sub_super_scripts = enabled;
+        self
+    }
+
+    /// Add a custom replacement like `\R` for `ℝ`,
+    /// in addition to the built-in symbols.
+    ///
+    /// The replacement takes priority over any built-in symbol with the same command,
+    /// and over any previously added replacement with the same command.
+    /// Like the built-in symbols, longer commands are always replaced first,
+    /// so adding `\R` does not break `\Rightarrow`.
+    /// Custom replacements are included
+    /// even if the built-in [replacements](Self::replacements) are disabled.
+    ///
+    /// ```
+    /// use unicodeit::Converter;
+    /// let converter = Converter::builder()
+    ///     .add_replacement(r"\R", "\u{211d}")
+    ///     .add_replacement(r"\eps", "\u{3b5}")
+    ///     .build();
+    /// assert_eq!(converter.replace(r"x \in \R \Rightarrow \eps"), "x \u{2208} \u{211d} \u{21d2} \u{3b5}");
+    /// ```
+    ///
+    /// # Panics
+    /// If the command is empty or contains whitespace,
+    /// which would break [streaming conversion](crate::ReplaceWriter).
+    pub fn add_replacement(
+        mut self,
+        latex: impl Into<Cow<'static, str>>,
+        unicode: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        let latex = latex.into();
+        assert!(
+            !latex.is_empty() && !latex.contains(char::is_whitespace),
+            "invalid replacement command {latex:?}"
+        );
+        insert_custom(&mut self.custom_replacements, latex, unicode.into());
+        self
+    }
+
+    /// Add a custom combining mark like `\ring`,
+    /// which combines with the character in the following braces.
+    ///
+    /// The command must be a backslash followed by ASCII letters,
+    /// and the value is typically a single combining character.
+    /// The combining mark takes priority over any built-in combining mark with the same command.
+    /// Custom combining marks are applied
+    /// even if the built-in [combining marks](Self::combining_marks) are disabled.
+    ///
+    /// ```
+    /// use unicodeit::Converter;
+    /// let converter = Converter::builder()
+    ///     .add_combining_mark(r"\ring", "\u{30a}")
+    ///     .build();
+    /// assert_eq!(converter.replace(r"\ring{a}"), "a\u{30a}");
+    /// ```
+    ///
+    /// # Panics
+    /// If the command is not a backslash followed by ASCII letters.
+    pub fn add_combining_mark(
+        mut self,
+        latex: impl Into<Cow<'static, str>>,
+        unicode: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        let latex = latex.into();
+        assert!(
+            latex.strip_prefix('\\').is_some_and(
+                |name| !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphabetic())
+            ),
+            "invalid combining mark command {latex:?}"
+        );
+        insert_custom(&mut self.custom_combining_marks, latex, unicode.into());
+        self
+    }
+
+    /// Add a custom subscript or superscript like `^Q`.
+    ///
+    /// The script takes priority over any built-in script with the same command.
+    /// Custom scripts are converted
+    /// even if the built-in [sub/superscripts](Self::sub_super_scripts) are disabled.
+    /// Only characters which can already appear in an [expanded group](Self::script_groups)
+    /// are converted inside groups like `x^{12}`,
+    /// unless a [fallback](Self::script_fallback) is chosen.
+    ///
+    /// ```
+    /// use unicodeit::Converter;
+    /// let converter = Converter::builder()
+    ///     .add_sub_super_script("^Q", "\u{a7f4}")
+    ///     .build();
+    /// assert_eq!(converter.replace("x^Q"), "x\u{a7f4}");
+    /// ```
+    ///
+    /// # Panics
+    /// If the command is not `_` or `^` followed by a single non-whitespace character.
+    pub fn add_sub_super_script(
+        mut self,
+        latex: impl Into<Cow<'static, str>>,
+        unicode: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        let latex = latex.into();
+        let mut chars = latex.chars();
+        assert!(
+            matches!(
+                (chars.next(), chars.next(), chars.next()),
+                (Some('_' | '^'), Some(base), None) if !base.is_whitespace()
+            ),
+            "invalid sub/superscript command {latex:?}"
+        );
+        insert_custom(&mut self.custom_sub_super_scripts, latex, unicode.into());
+        self
+    }
+
+    /// Add every entry of some custom symbol tables,
+    /// usually loaded from a file.
+    ///
+    /// This is equivalent to calling [`Self::add_replacement`], [`Self::add_combining_mark`]
+    /// and [`Self::add_sub_super_script`] for each entry,
+    /// so later entries take priority over earlier ones.
+    ///
+    /// # Panics
+    /// If any of the entries are invalid.
+    /// Tables returned by [`SymbolTables::from_toml`](crate::SymbolTables::from_toml)
+    /// and [`SymbolTables::from_json`](crate::SymbolTables::from_json) are always valid.
+    #[cfg(feature = "serde")]
+    pub fn add_tables(mut self, tables: crate::SymbolTables) -> Self {
+        for (latex, unicode) in tables.replacements {
+            self = self.add_replacement(latex, unicode);
+        }
+        for (latex, unicode) in tables.combining_marks {
+            self = self.add_combining_mark(latex, unicode);
+        }
+        for (latex, unicode) in tables.sub_super_scripts {
+            self = self.add_sub_super_script(latex, unicode);
+        }
+        self
+    }
+
+    /// Build the converter, filtering the symbol tables according to the configuration.
+    pub fn build(self) -> Converter {
+        fn table_if(
+            enabled: bool,
+            table: &'static [(&'static str, &'static str)],
+            mut filter: impl FnMut(&str) -> bool,
+        ) -> SymbolTable {
+            if enabled {
+                table
+                    .iter()
+                    .filter(|&&(latex, _)| filter(latex))
+                    .map(|&(latex, unicode)| (Cow::Borrowed(latex), Cow::Borrowed(unicode)))
+                    .collect()
+            } else {
+                Vec::new()
+            }
+        }
+        let replacements = merge_custom(
+            table_if(self.replacements, crate::data::REPLACEMENTS, |latex| {
+                self.math_alphabets || !is_math_alphabet(latex)
+            }),
+            self.custom_replacements,
+        );
+        let mut combining_marks = merge_custom(
+            table_if(self.combining_marks, crate::data::COMBINING_MARKS, |_| true),
+            self.custom_combining_marks,
+        );
+        if self.wide_accents && self.combining_marks {
+            let aliases = WIDE_ACCENTS
+                .iter()
+                .filter(|&&(latex, _)| {
+                    !combining_marks
+                        .iter()
+                        .any(|(existing, _)| existing == latex)
+                })
+                .filter_map(|&(latex, mark)| {
+                    let (_, unicode) = combining_marks
+                        .iter()
+                        .find(|(existing, _)| existing == mark)?;
+                    Some((Cow::Borrowed(latex), unicode.clone()))
+                })
+                .collect();
+            combining_marks = merge_custom(combining_marks, aliases);
+        }
+        let mut sub_super_scripts = merge_custom(
+            table_if(
+                self.sub_super_scripts,
+                crate::data::SUB_SUPER_SCRIPTS,
+                |_| true,
+            ),
+            self.custom_sub_super_scripts,
+        );
+        if self.script_fallback == ScriptFallback::Closest && self.sub_super_scripts {
+            let alternatives = CLOSEST_SCRIPTS
+                .iter()
+                .filter(|&&(latex, _)| {
+                    !sub_super_scripts
+                        .iter()
+                        .any(|(existing, _)| existing == latex)
+                })
+                .map(|&(latex, unicode)| (Cow::Borrowed(latex), Cow::Borrowed(unicode)))
+                .collect();
+            sub_super_scripts = merge_custom(sub_super_scripts, alternatives);
+        }
+        let entries = |table: &SymbolTable| {
+            table
+                .iter()
+                .map(|(latex, unicode)| (latex.clone(), unicode.clone()))
+                .collect::<Vec<_>>()
+        };
+        let engine = Engine::new(
+            entries(&replacements),
+            entries(&combining_marks),
+            entries(&sub_super_scripts),
+        )
+        .not_rewrite(self.not_rewrite && self.combining_marks)
+        .script_groups(self.script_groups && self.sub_super_scripts)
+        .script_fallback(self.script_fallback)
+        .combining_groups(self.combining_groups)
+        .parse_latex(self.parse_latex);
+        #[cfg(feature = "normalization")]
+        let engine = engine.normalization(self.normalization);
+        Converter {
+            strict: self.strict,
+            macros: self.macros,
+            replacements,
+            combining_marks,
+            sub_super_scripts,
+            max_input_len: self.max_input_len,
+            max_output_len: self.max_output_len,
+            math_delimiters: self.math_delimiters,
+            parse_latex: self.parse_latex,
+            combining_groups: self.combining_groups,
+            script_fallback: self.script_fallback,
+            passes: self.passes,
+            engine,
+            known_commands: OnceLock::new(),
+        }
+    }
+}
+
+/// Add a custom entry to a table, replacing any existing entry with the same key.
+fn insert_custom(table: &mut SymbolTable, latex: Cow<'static, str>, unicode: Cow<'static, str>) {
+    match table.iter_mut().find(|(existing, _)| *existing == latex) {
+        Some(entry) => entry.1 = unicode,
+        None => table.push((latex, unicode)),
+    }
+}
+
+/// Merge custom entries into a built-in table,
+/// restoring the invariant that longer keys come first.
+///
+/// Custom entries override built-in entries with the same key,
+/// and come before built-in entries of the same length.
+fn merge_custom(builtin: SymbolTable, custom: SymbolTable) -> SymbolTable {
+    if custom.is_empty() {
+        return builtin;
+    }
+    let builtin = builtin
+        .into_iter()
+        .filter(|(latex, _)| !custom.iter().any(|(existing, _)| existing == latex))
+        .collect::<Vec<_>>();
+    let mut table = custom;
+    table.extend(builtin);
+    // stable, so ties keep custom entries first and built-in entries in order
+    table.sort_by_key(|(latex, _)| std::cmp::Reverse(latex.chars().count()));
+    table
+}
+
+impl Default for ConverterBuilder {
+    #[inline]
+    fn default() -> Self {
+        ConverterBuilder::new()
+    }
+}
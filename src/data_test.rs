@@ -56,3 +56,28 @@ fn incomplete_combining_mark() {
         assert_eq!(crate::replace_naive("\\breve{"), "\\breve{");
     }
 }
+
+#[test]
+#[cfg(feature = "std")]
+fn order_custom_entries() {
+    let converter = crate::Converter::builder()
+        .add_replacement(r"\R", "\u{211d}")
+        .add_replacement(r"\alphabet", "\u{2135}")
+        .add_replacement(r"\alpha", "a")
+        .add_combining_mark(r"\ring", "\u{30a}")
+        .build();
+    for table in [&converter.replacements, &converter.combining_marks] {
+        let mut expr_length = usize::MAX;
+        for (l, _) in table {
+            let char_length = l.chars().count();
+            assert!(char_length <= expr_length);
+            expr_length = char_length;
+        }
+    }
+    let alpha = converter
+        .replacements
+        .iter()
+        .filter(|(l, _)| l == r"\alpha")
+        .collect::<Vec<_>>();
+    assert_eq!(alpha, [&(r"\alpha".into(), "a".into())]);
+}
@@ -0,0 +1,199 @@
+//! Finding math between delimiters like `$...$` in prose.
+
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// The delimiters around math in prose, like `$...$`,
+/// used by [`ConverterBuilder::math_delimiters`](crate::ConverterBuilder::math_delimiters).
+///
+/// Math ends at the first closing delimiter,
+/// skipping over control symbols like `\$` and `\\`.
+/// Like in LaTeX, math never contains a blank line,
+/// so an opening delimiter without a closing delimiter in the same paragraph
+/// is left unchanged.
+///
+/// Outside of math, a backslash escapes an opening delimiter like `\$`,
+/// which is replaced by the delimiter itself.
+/// A backslash also escapes another backslash, so `\\(` is not an opening delimiter.
+///
+/// ```
+/// use unicodeit::{Converter, MathDelimiters};
+/// let converter = Converter::builder()
+///     .math_delimiters(MathDelimiters::new())
+///     .build();
+/// assert_eq!(
+///     converter.replace(r"snake_case costs \$5, but $x_1 \in \mathbb{R}$ does not"),
+///     "snake_case costs $5, but x\u{2081} \u{2208} \u{211d} does not"
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct MathDelimiters {
+    /// Pairs of opening and closing delimiters, with longer opening delimiters first.
+    pairs: Vec<(Cow<'static, str>, Cow<'static, str>)>,
+}
+
+impl MathDelimiters {
+    /// The standard delimiters `$...$`, `$$...$$`, `\(...\)` and `\[...\]`.
+    pub fn new() -> Self {
+        MathDelimiters::empty()
+            .add("$$", "$$")
+            .add("$", "$")
+            .add(r"\(", r"\)")
+            .add(r"\[", r"\]")
+    }
+
+    /// No delimiters, so they can be chosen with [`MathDelimiters::add`].
+    ///
+    /// ```
+    /// use unicodeit::{Converter, MathDelimiters};
+    /// let converter = Converter::builder()
+    ///     .math_delimiters(MathDelimiters::empty().add(r"\(", r"\)"))
+    ///     .build();
+    /// assert_eq!(converter.replace(r"$5 for \(\alpha\)"), "$5 for \u{3b1}");
+    /// ```
+    pub fn empty() -> Self {
+        MathDelimiters { pairs: Vec::new() }
+    }
+
+    /// Add a pair of opening and closing delimiters.
+    ///
+    /// Where several opening delimiters match, the longest is used.
+    ///
+    /// # Panics
+    /// If either delimiter is empty.
+    pub fn add(
+        mut self,
+        open: impl Into<Cow<'static, str>>,
+        close: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        let (open, close) = (open.into(), close.into());
+        assert!(
+            !open.is_empty() && !close.is_empty(),
+            "invalid math delimiters {open:?} and {close:?}"
+        );
+        let index = self
+            .pairs
+            .partition_point(|(existing, _)| existing.len() >= open.len());
+        self.pairs.insert(index, (open, close));
+        self
+    }
+
+    /// Determine if the text cannot contain any math or escaped delimiters.
+    pub(crate) fn is_prose(&self, text: &str) -> bool {
+        !self.pairs.iter().any(|(open, _)| {
+            let first = open.as_bytes()[0];
+            memchr::memchr(first, text.as_bytes()).is_some()
+        })
+    }
+
+    /// Split the text into prose and math.
+    pub(crate) fn split(&self, text: &str) -> Vec<Piece> {
+        let mut pieces = Vec::new();
+        // for each pair, the end of the paragraph in which the closing delimiter is missing
+        let mut missing_until = vec![0; self.pairs.len()];
+        let mut prose_start = 0;
+        let mut index = 0;
+        'search: while let Some(c) = text[index..].chars().next() {
+            let rest = &text[index..];
+            if let Some(escaped) = rest.strip_prefix('\\')
+                && let Some(next) = escaped.chars().next()
+            {
+                let escapes_delimiter = self
+                    .pairs
+                    .iter()
+                    .any(|(open, _)| !open.starts_with('\\') && open.starts_with(next));
+                if escapes_delimiter {
+                    pieces.push(Piece::Prose(prose_start..index));
+                    let end = index + 1 + next.len_utf8();
+                    pieces.push(Piece::Escape(index..end));
+                    prose_start = end;
+                    index = end;
+                    continue;
+                } else if next == '\\' {
+                    index += 2;
+                    continue;
+                }
+            }
+            for (pair, (open, close)) in self.pairs.iter().enumerate() {
+                if index < missing_until[pair] || !rest.starts_with(&**open) {
+                    continue;
+                }
+                let content_start = index + open.len();
+                match find_close(text, content_start, close) {
+                    Ok(close_start) => {
+                        let end = close_start + close.len();
+                        pieces.push(Piece::Prose(prose_start..index));
+                        pieces.push(Piece::Delimiter(index..content_start));
+                        pieces.push(Piece::Math(content_start..close_start));
+                        pieces.push(Piece::Delimiter(close_start..end));
+                        prose_start = end;
+                        index = end;
+                        continue 'search;
+                    }
+                    Err(paragraph_end) => missing_until[pair] = paragraph_end,
+                }
+            }
+            index += c.len_utf8();
+        }
+        pieces.push(Piece::Prose(prose_start..text.len()));
+        pieces.retain(|piece| !matches!(piece, Piece::Prose(range) if range.is_empty()));
+        pieces
+    }
+}
+
+impl Default for MathDelimiters {
+    #[inline]
+    fn default() -> Self {
+        MathDelimiters::new()
+    }
+}
+
+/// A piece of text split by [`MathDelimiters::split`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Piece {
+    /// Prose, which is left unchanged.
+    Prose(Range<usize>),
+    /// An escaped delimiter like `\$`, which is replaced by the character after the backslash.
+    Escape(Range<usize>),
+    /// A delimiter, which is removed.
+    Delimiter(Range<usize>),
+    /// Math between delimiters, which is converted.
+    Math(Range<usize>),
+    /// Text which is converted like a whole input, including any math delimiters.
+    #[cfg(feature = "markdown")]
+    Text(Range<usize>),
+}
+
+/// Find the closing delimiter for math starting at the index.
+///
+/// If there is none before the end of the paragraph,
+/// this fails with the end of the paragraph.
+fn find_close(text: &str, start: usize, close: &str) -> Result<usize, usize> {
+    // if only whitespace has been seen since the last newline
+    let mut line_blank = false;
+    let mut index = start;
+    while let Some(c) = text[index..].chars().next() {
+        if index > start && text[index..].starts_with(close) {
+            return Ok(index);
+        }
+        match c {
+            '\\' => {
+                // skip control symbols like `\$`, but not a line break
+                line_blank = false;
+                index += 1;
+                if let Some(next) = text[index..].chars().next()
+                    && next != '\n'
+                {
+                    index += next.len_utf8();
+                }
+                continue;
+            }
+            '\n' if line_blank => return Err(index),
+            '\n' => line_blank = true,
+            ' ' | '\t' | '\r' => {}
+            _ => line_blank = false,
+        }
+        index += c.len_utf8();
+    }
+    Err(text.len())
+}
@@ -0,0 +1,706 @@
+//! Diagnostics for LaTeX that could not be converted.
+
+use crate::converter::Conversion;
+use crate::macros::{DEFINITION_COMMANDS, Definitions};
+use crate::{ConversionLimit, Converter, MacroLimit, OffsetMap, ScriptKind};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display, Formatter};
+use std::ops::Range;
+
+/// The maximum number of suggestions for an unknown command.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// A problem found while converting LaTeX.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The byte range in the LaTeX input where the problem was found.
+    pub span: Range<usize>,
+    /// How severe the problem is.
+    pub severity: Severity,
+    /// The type of problem.
+    pub kind: DiagnosticKind,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at {}..{}: {}",
+            self.severity, self.span.start, self.span.end, self.kind
+        )
+    }
+}
+
+/// The severity of a [`Diagnostic`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The input was left unconverted, but is otherwise well-formed.
+    Warning,
+    /// The input is malformed.
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// The type of a [`Diagnostic`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DiagnosticKind {
+    /// A control sequence which has no Unicode equivalent.
+    UnknownCommand {
+        /// The command, including the leading backslash and any braced argument.
+        name: String,
+        /// Similar known commands, with the closest first.
+        suggestions: Vec<String>,
+    },
+    /// A combining mark like `\hat` without a braced argument.
+    DanglingCombiningMark {
+        /// The command, including the leading backslash.
+        name: String,
+    },
+    /// A subscript or superscript containing characters
+    /// which have no Unicode equivalent.
+    UnsupportedScript {
+        /// The kind of script.
+        kind: ScriptKind,
+        /// The characters that could not be converted.
+        unsupported: Vec<char>,
+    },
+    /// An opening brace without a closing brace, or vice versa.
+    UnbalancedBrace {
+        /// The unmatched brace character.
+        brace: char,
+    },
+    /// A [macro](crate::Macros) which was left unexpanded,
+    /// because expanding it exceeded a limit.
+    MacroLimitExceeded {
+        /// The macro, including the leading backslash.
+        name: String,
+        /// The limit that was exceeded.
+        limit: MacroLimit,
+    },
+    /// The conversion stopped early,
+    /// because the input or output exceeded a [length limit](crate::ConverterBuilder::max_input_len).
+    ///
+    /// The span covers the input which was not converted.
+    LimitExceeded {
+        /// The limit that was exceeded.
+        limit: ConversionLimit,
+        /// The maximum length in bytes.
+        max_len: usize,
+    },
+}
+
+impl DiagnosticKind {
+    /// The severity of this type of problem.
+    pub fn severity(&self) -> Severity {
+        match self {
+            DiagnosticKind::UnknownCommand { .. } | DiagnosticKind::UnsupportedScript { .. } => {
+                Severity::Warning
+            }
+            DiagnosticKind::DanglingCombiningMark { .. }
+            | DiagnosticKind::UnbalancedBrace { .. }
+            | DiagnosticKind::MacroLimitExceeded { .. }
+            | DiagnosticKind::LimitExceeded { .. } => Severity::Error,
+        }
+    }
+}
+
+impl Display for DiagnosticKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DiagnosticKind::UnknownCommand { name, suggestions } => {
+                write!(f, "unknown command `{name}`")?;
+                for (index, suggestion) in suggestions.iter().enumerate() {
+                    let prefix = match index {
+                        0 => "; did you mean",
+                        _ if index + 1 == suggestions.len() => " or",
+                        _ => ",",
+                    };
+                    write!(f, "{prefix} `{suggestion}`")?;
+                }
+                if !suggestions.is_empty() {
+                    f.write_str("?")?;
+                }
+                Ok(())
+            }
+            DiagnosticKind::DanglingCombiningMark { name } => {
+                write!(f, "combining mark `{name}` is missing its argument")
+            }
+            DiagnosticKind::UnsupportedScript { kind, unsupported } => {
+                write!(f, "no Unicode {kind} for ")?;
+                for (index, c) in unsupported.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "`{c}`")?;
+                }
+                Ok(())
+            }
+            DiagnosticKind::UnbalancedBrace { brace } => write!(f, "unmatched `{brace}`"),
+            DiagnosticKind::MacroLimitExceeded { name, limit } => {
+                write!(f, "expanding `{name}` exceeded the {limit} limit")
+            }
+            DiagnosticKind::LimitExceeded { limit, max_len } => {
+                write!(f, "exceeded the {limit} limit of {max_len} bytes")
+            }
+        }
+    }
+}
+
+/// The error returned by [`Converter::try_replace`]
+/// when some of the input could not be converted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConversionError {
+    output: String,
+    failures: Vec<Diagnostic>,
+}
+
+impl ConversionError {
+    /// Every problem that caused the conversion to fail, sorted by span.
+    ///
+    /// This is never empty.
+    #[inline]
+    pub fn failures(&self) -> &[Diagnostic] {
+        &self.failures
+    }
+
+    /// The byte range in the input of the first failure.
+    #[inline]
+    pub fn span(&self) -> Range<usize> {
+        self.failures[0].span.clone()
+    }
+
+    /// The partially converted output,
+    /// exactly as returned by [`Converter::replace`].
+    #[inline]
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    /// Take the partially converted output.
+    #[inline]
+    pub fn into_output(self) -> String {
+        self.output
+    }
+}
+
+impl Display for ConversionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.failures.as_slice() {
+            [failure] => write!(f, "failed to convert LaTeX: {failure}"),
+            failures => {
+                write!(f, "failed to convert LaTeX ({} problems)", failures.len())?;
+                for failure in failures {
+                    write!(f, "\n  {failure}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+/// Fail if any of the diagnostics are severe enough to fail the conversion.
+pub(crate) fn check_failures(
+    output: String,
+    diagnostics: Vec<Diagnostic>,
+    strict: bool,
+) -> Result<String, ConversionError> {
+    let failures = diagnostics
+        .into_iter()
+        .filter(|diagnostic| strict || diagnostic.severity >= Severity::Error)
+        .collect::<Vec<_>>();
+    if failures.is_empty() {
+        Ok(output)
+    } else {
+        Err(ConversionError { output, failures })
+    }
+}
+
+/// Get the control word at the start of the text, like `\alpha`.
+fn control_word(text: &str) -> Option<&str> {
+    let rest = text.strip_prefix('\\')?;
+    let len = rest
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(rest.len());
+    (len > 0).then(|| &text[..len + 1])
+}
+
+/// The edit distance between two strings, counting characters,
+/// or `None` if it is more than `max`.
+///
+/// The rows are scratch space, which must be one longer than `a`.
+fn edit_distance(
+    a: &[char],
+    b: &str,
+    max: usize,
+    [previous, current]: &mut [Vec<usize>; 2],
+) -> Option<usize> {
+    for (j, distance) in previous.iter_mut().enumerate() {
+        *distance = j;
+    }
+    for (i, b_char) in b.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, &a_char) in a.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(previous, current);
+        // the distance never decreases from one row to the next
+        if previous.iter().all(|&distance| distance > max) {
+            return None;
+        }
+    }
+    Some(previous[a.len()]).filter(|&distance| distance <= max)
+}
+
+/// The commands and scripts known to a [`Converter`],
+/// which are built once the first time it checks a conversion.
+#[derive(Clone, Debug)]
+pub(crate) struct KnownCommands {
+    /// The control words known to the converter, like `\alpha` and `\mathbb`,
+    /// not including macros defined in the text.
+    words: HashSet<String>,
+    /// The control words of the combining marks, like `\hat`.
+    combining_marks: HashSet<String>,
+    /// The characters with a subscript or superscript.
+    scripts: HashSet<(ScriptKind, char)>,
+    /// The commands which can be suggested, sorted by their length in characters.
+    suggestable: Vec<(usize, String)>,
+}
+
+impl KnownCommands {
+    fn new(converter: &Converter) -> Self {
+        let commands = converter
+            .replacements
+            .iter()
+            .chain(&converter.combining_marks)
+            .map(|(latex, _)| &**latex);
+        let mut words = commands
+            .clone()
+            .filter_map(control_word)
+            .chain([r"\not"])
+            .map(String::from)
+            .collect::<HashSet<String>>();
+        if converter.macros.is_some() {
+            words.extend(DEFINITION_COMMANDS.map(String::from));
+        }
+        let combining_marks = converter
+            .combining_marks
+            .iter()
+            .map(|(mark, _)| mark.to_string())
+            .collect();
+        let scripts = converter
+            .sub_super_scripts
+            .iter()
+            .filter_map(|(latex, _)| {
+                let mut chars = latex.chars();
+                let kind = ScriptKind::from_control_char(chars.next()?)?;
+                let c = chars.next()?;
+                chars.next().is_none().then_some((kind, c))
+            })
+            .collect();
+        let mut suggestable = commands
+            .filter(|latex| latex.starts_with('\\'))
+            .map(|latex| (latex.chars().count(), latex.to_string()))
+            .collect::<Vec<_>>();
+        suggestable.sort_unstable();
+        KnownCommands {
+            words,
+            combining_marks,
+            scripts,
+            suggestable,
+        }
+    }
+
+    /// Find the known commands closest to an unknown one.
+    fn suggestions(&self, name: &str) -> Vec<String> {
+        let max_distance = (name.len() / 3).clamp(1, 3);
+        let suggestable = &self.suggestable;
+        // the distance is at least the difference in length,
+        // so only commands of a similar length are compared
+        let name = name.chars().collect::<Vec<char>>();
+        let len = name.len();
+        let start = suggestable.partition_point(|&(other, _)| other + max_distance < len);
+        let end = suggestable.partition_point(|&(other, _)| other <= len + max_distance);
+        let mut rows = [vec![0; len + 1], vec![0; len + 1]];
+        let mut candidates = suggestable[start..end]
+            .iter()
+            .filter_map(|(_, latex)| {
+                Some((edit_distance(&name, latex, max_distance, &mut rows)?, latex))
+            })
+            .collect::<Vec<_>>();
+        candidates.sort();
+        candidates.dedup_by_key(|&mut (_, latex)| latex);
+        candidates
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, latex)| latex.clone())
+            .collect()
+    }
+}
+
+/// The positions of a character in the text, found in a single pass,
+/// so finding the next one never scans the rest of the text again.
+struct Positions(Vec<usize>);
+
+impl Positions {
+    fn new(text: &str, c: char) -> Self {
+        Positions(text.match_indices(c).map(|(index, _)| index).collect())
+    }
+
+    /// Find the first position at or after `start`.
+    fn next(&self, start: usize) -> Option<usize> {
+        let index = self.0.partition_point(|&position| position < start);
+        self.0.get(index).copied()
+    }
+}
+
+/// Finds problems in the input and output of a conversion.
+struct Checker<'a> {
+    converter: &'a Converter,
+    input: &'a str,
+    output: &'a str,
+    map: &'a OffsetMap,
+    known: &'a KnownCommands,
+    /// The names of any macros, including those defined in the text.
+    macro_names: HashSet<&'a str>,
+    /// The suggestions for each unknown command, which is often repeated.
+    suggestions: HashMap<&'a str, Vec<String>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Checker<'a> {
+    fn push(&mut self, span: Range<usize>, kind: DiagnosticKind) {
+        self.diagnostics.push(Diagnostic {
+            span,
+            severity: kind.severity(),
+            kind,
+        });
+    }
+
+    fn is_known(&self, word: &str) -> bool {
+        self.known.words.contains(word) || self.macro_names.contains(word)
+    }
+
+    /// The suggestions for an unknown command, which are only found once for each name.
+    fn suggest(&mut self, name: &'a str) -> Vec<String> {
+        let known = self.known;
+        self.suggestions
+            .entry(name)
+            .or_insert_with(|| known.suggestions(name))
+            .clone()
+    }
+
+    /// Find control words in the input which are not known to the converter.
+    fn check_unknown_words(&mut self) {
+        let input = self.input;
+        let mut search_start = 0;
+        while let Some(found) = input[search_start..].find('\\') {
+            let index = search_start + found;
+            let rest = &input[index..];
+            let Some(word) = control_word(rest) else {
+                // skip control symbols, so `\\` is not treated as two backslashes
+                search_start = index + 1 + rest[1..].chars().next().map_or(0, char::len_utf8);
+                continue;
+            };
+            search_start = index + word.len();
+            if !self.is_known(word) {
+                let suggestions = self.suggest(word);
+                self.push(
+                    index..index + word.len(),
+                    DiagnosticKind::UnknownCommand {
+                        name: word.to_string(),
+                        suggestions,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Determine if the byte at the specified output offset
+    /// was copied unchanged from the input.
+    fn is_unchanged_output(&self, offset: usize) -> bool {
+        let segments = self.map.segments();
+        let index = segments.partition_point(|s| s.output.end <= offset);
+        segments.get(index).is_some_and(|s| s.unchanged)
+    }
+
+    /// Find known commands which were left in the output unconverted.
+    fn check_leftover_commands(&mut self) {
+        let output = self.output;
+        let closing_braces = Positions::new(output, '}');
+        let mut search_start = 0;
+        while let Some(found) = output[search_start..].find('\\') {
+            let index = search_start + found;
+            search_start = index + 1;
+            if !self.is_unchanged_output(index) {
+                continue;
+            }
+            let rest = &output[index..];
+            let (name, end) = match control_word(rest) {
+                Some(word) if !self.is_known(word) => {
+                    // already reported from the input
+                    continue;
+                }
+                Some(word) if self.macro_names.contains(word) => {
+                    // missing arguments, or already reported as exceeding a limit
+                    continue;
+                }
+                Some(word) if self.known.combining_marks.contains(word) => {
+                    self.push(
+                        self.map.input_range(index..index + word.len()),
+                        DiagnosticKind::DanglingCombiningMark {
+                            name: word.to_string(),
+                        },
+                    );
+                    continue;
+                }
+                Some(word) if rest[word.len()..].starts_with('{') => {
+                    match closing_braces.next(index + word.len()) {
+                        Some(close) => {
+                            let end = close + 1 - index;
+                            (&rest[..end], end)
+                        }
+                        None => (word, word.len()),
+                    }
+                }
+                Some(word) => (word, word.len()),
+                None => match rest[1..].chars().next() {
+                    Some('\\') => {
+                        // line break
+                        search_start += 1;
+                        continue;
+                    }
+                    Some(c) if !c.is_whitespace() => (&rest[..1 + c.len_utf8()], 1 + c.len_utf8()),
+                    _ => continue,
+                },
+            };
+            let suggestions = self.suggest(name);
+            self.push(
+                self.map.input_range(index..index + end),
+                DiagnosticKind::UnknownCommand {
+                    name: name.to_string(),
+                    suggestions,
+                },
+            );
+            search_start = index + end;
+        }
+    }
+
+    /// Find subscripts and superscripts which were left in the output unconverted.
+    fn check_leftover_scripts(&mut self) {
+        if self.converter.sub_super_scripts.is_empty() {
+            // scripts are intentionally left unconverted
+            return;
+        }
+        let (input, output) = (self.input, self.output);
+        let closing_braces = Positions::new(output, '}');
+        let closing_parens = Positions::new(output, ')');
+        // unsupported characters in expanded groups, keyed by the span of the group
+        let mut expanded_groups: Vec<(Range<usize>, ScriptKind, Vec<char>)> = Vec::new();
+        // the end of the last unconverted group, whose content has already been checked
+        let mut group_end = 0;
+        for (index, control) in output.match_indices(['^', '_']) {
+            if index < group_end {
+                continue;
+            }
+            let kind = ScriptKind::from_control_char(control.chars().next().unwrap()).unwrap();
+            let input_span = self.map.input_range(index..index + 1);
+            if !input[input_span.clone()].starts_with(control) || output[..index].ends_with('\\') {
+                // produced by a replacement like `\hat{}`, or escaped like `\_`
+                continue;
+            }
+            let rest = &output[index + 1..];
+            let unchanged = self.is_unchanged_output(index);
+            // a group converted by the fallback, like `x^(Q)` or `x²^{Q}`
+            let fallback = !unchanged
+                && self.converter.script_fallback.converts_groups()
+                && rest.starts_with(['{', '(']);
+            if !unchanged && !fallback {
+                // part of an expanded group like `x_{1,2}`
+                let Some(c) = rest.chars().next() else {
+                    continue;
+                };
+                match expanded_groups.last_mut() {
+                    Some((span, _, unsupported)) if *span == input_span => {
+                        if !unsupported.contains(&c) {
+                            unsupported.push(c);
+                        }
+                    }
+                    _ => expanded_groups.push((input_span, kind, vec![c])),
+                }
+                continue;
+            }
+            let (open, closing) = if fallback && rest.starts_with('(') {
+                ('(', &closing_parens)
+            } else {
+                ('{', &closing_braces)
+            };
+            let (content, end, is_group) = match rest.strip_prefix(open) {
+                Some(_) => match closing.next(index + 2) {
+                    Some(close) => (&output[index + 2..close], close + 1, true),
+                    // reported as an unbalanced brace
+                    None => continue,
+                },
+                None => match rest.chars().next() {
+                    Some(c) => (&rest[..c.len_utf8()], index + 1 + c.len_utf8(), false),
+                    None => continue,
+                },
+            };
+            let mut unsupported = Vec::new();
+            for c in content.chars() {
+                let supported = self.known.scripts.contains(&(kind, c))
+                    && (!is_group || fallback || kind.is_group_char(c));
+                if !supported && !unsupported.contains(&c) {
+                    unsupported.push(c);
+                }
+            }
+            if is_group {
+                group_end = end;
+            }
+            if !unsupported.is_empty() {
+                self.push(
+                    self.map.input_range(index..end),
+                    DiagnosticKind::UnsupportedScript { kind, unsupported },
+                );
+            }
+        }
+        for (span, kind, unsupported) in expanded_groups {
+            self.push(
+                span,
+                DiagnosticKind::UnsupportedScript { kind, unsupported },
+            );
+        }
+    }
+
+    /// Find unmatched braces in the input.
+    fn check_braces(&mut self) {
+        let mut open_braces = Vec::new();
+        let mut bytes = self.input.bytes().enumerate();
+        while let Some((index, byte)) = bytes.next() {
+            match byte {
+                b'\\' => {
+                    // skip escaped characters like `\{`
+                    bytes.next();
+                }
+                b'{' => open_braces.push(index),
+                b'}' if open_braces.pop().is_none() => {
+                    self.push(
+                        index..index + 1,
+                        DiagnosticKind::UnbalancedBrace { brace: '}' },
+                    );
+                }
+                _ => {}
+            }
+        }
+        for index in open_braces {
+            self.push(
+                index..index + 1,
+                DiagnosticKind::UnbalancedBrace { brace: '{' },
+            );
+        }
+    }
+}
+
+/// Find the problems with a conversion,
+/// given the macros defined in the input.
+pub(crate) fn check(
+    converter: &Converter,
+    input: &str,
+    conversion: &Conversion,
+    local: &Definitions,
+) -> Vec<Diagnostic> {
+    let map = conversion.map.as_ref().expect("missing offset map");
+    if let Some(limit) = conversion.limit_exceeded {
+        // the rest of the input was dropped, so nothing else is meaningful
+        let (max_len, start) = match limit {
+            ConversionLimit::InputLength => (converter.max_input_len, 0),
+            ConversionLimit::OutputLength => (
+                converter.max_output_len,
+                map.segments()
+                    .last()
+                    .map_or(0, |segment| segment.input.start),
+            ),
+        };
+        let kind = DiagnosticKind::LimitExceeded {
+            limit,
+            max_len: max_len.unwrap_or_default(),
+        };
+        return vec![Diagnostic {
+            span: start..input.len(),
+            severity: kind.severity(),
+            kind,
+        }];
+    }
+    if converter.math_delimiters.is_some() {
+        // only math is converted, so prose is never checked
+        let mut diagnostics = Vec::new();
+        for (range, math) in &conversion.math {
+            diagnostics.extend(
+                check_conversion(converter, &input[range.clone()], math, local)
+                    .into_iter()
+                    .map(|diagnostic| Diagnostic {
+                        span: range.start + diagnostic.span.start
+                            ..range.start + diagnostic.span.end,
+                        ..diagnostic
+                    }),
+            );
+        }
+        return diagnostics;
+    }
+    check_conversion(converter, input, conversion, local)
+}
+
+/// Check a conversion of text which is entirely math.
+fn check_conversion(
+    converter: &Converter,
+    input: &str,
+    conversion: &Conversion,
+    local: &Definitions,
+) -> Vec<Diagnostic> {
+    let map = conversion.map.as_ref().expect("missing offset map");
+    let mut macro_names = HashSet::new();
+    if let Some(macros) = &converter.macros {
+        macro_names.extend(macros.names());
+        macro_names.extend(local.keys().map(String::as_str));
+    }
+    let mut checker = Checker {
+        converter,
+        input,
+        output: &conversion.output,
+        map,
+        known: converter
+            .known_commands
+            .get_or_init(|| KnownCommands::new(converter)),
+        macro_names,
+        suggestions: HashMap::new(),
+        diagnostics: Vec::new(),
+    };
+    for failure in &conversion.macro_failures {
+        checker.push(
+            failure.span.clone(),
+            DiagnosticKind::MacroLimitExceeded {
+                name: failure.name.clone(),
+                limit: failure.limit,
+            },
+        );
+    }
+    checker.check_unknown_words();
+    checker.check_leftover_commands();
+    checker.check_leftover_scripts();
+    checker.check_braces();
+    checker
+        .diagnostics
+        .sort_by_key(|diagnostic| (diagnostic.span.start, diagnostic.span.end));
+    checker.diagnostics
+}
@@ -0,0 +1,259 @@
+//! Compare the optimized implementation against the naive port of the Python code.
+//!
+//! The inputs are generated from the data tables,
+//! using a small deterministic random number generator
+//! so failures are reproducible.
+//!
+//! The two implementations only differ where the original produces broken output,
+//! as listed in the documentation of [`crate::replace`].
+//! Every input where they differ must contain one of those constructs,
+//! found from the text and the data tables alone,
+//! and the output before the first of them must be the same.
+//!
+//! Can't be an integration test, because `crate::data` is private.
+
+#[cfg(not(test))]
+compile_error!("only for testing");
+
+use crate::data::{COMBINING_MARKS, REPLACEMENTS, SUB_SUPER_SCRIPTS};
+use crate::naive_replace::replace as naive;
+use crate::optimized_replace::replace as optimized;
+use crate::scripts::ScriptKind;
+
+/// The number of inputs checked by each test.
+const ITERATIONS: usize = 5_000;
+
+/// A xorshift random number generator.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len())]
+    }
+}
+
+const PLAIN: &[&str] = &[
+    "a", "x", "Z", "1", "2", " ", "+", "-", "(", ")", "{", "}", "é",
+];
+
+/// Arguments of combining marks, including the closing brace if there is one.
+const MARK_ARGUMENTS: &[&str] = &[
+    "a}",
+    "b}",
+    "x}",
+    "1}",
+    "α}",
+    "é}",
+    "\\alpha}",
+    "}",
+    "ab}",
+    "a",
+    "\\hat{a}}",
+    "\\hat{",
+];
+
+/// The command of a combining mark, like `\hat`.
+fn mark_command(key: &'static str) -> &'static str {
+    key.strip_suffix("{}").unwrap_or(key)
+}
+
+/// Generate a single piece of input.
+fn atom(rng: &mut Rng) -> String {
+    match rng.below(8) {
+        0 | 1 => rng.pick(REPLACEMENTS).0.to_owned(),
+        2 => {
+            let mark = mark_command(rng.pick(COMBINING_MARKS).0);
+            let argument = rng.pick(MARK_ARGUMENTS);
+            format!("{mark}{{{argument}")
+        }
+        3 => rng.pick(SUB_SUPER_SCRIPTS).0.to_owned(),
+        4 => {
+            let (prefix, kind, spellings) = rng.pick(&[
+                (
+                    '^',
+                    ScriptKind::Superscript,
+                    &["^", "\\sphat", "\\hat{}"][..],
+                ),
+                ('_', ScriptKind::Subscript, &["_", "\\_"][..]),
+            ]);
+            let chars = SUB_SUPER_SCRIPTS
+                .iter()
+                .filter_map(|&(key, _)| {
+                    let mut chars = key.strip_prefix(prefix)?.chars();
+                    let c = chars.next()?;
+                    (chars.next().is_none() && kind.is_group_char(c)).then_some(c)
+                })
+                .collect::<Vec<char>>();
+            let len = 1 + rng.below(4);
+            let content = (0..len).map(|_| rng.pick(&chars)).collect::<String>();
+            // the original also reads the output of escapes like `\{` as braces
+            let open = rng.pick(&["{", "{", "\\{", "\\lbrace"]);
+            let close = rng.pick(&["}", "}", "\\}", "\\rbrace", ""]);
+            format!("{}{open}{content}{close}", rng.pick(spellings))
+        }
+        5 => "\\not".to_owned(),
+        _ => rng.pick(PLAIN).to_owned(),
+    }
+}
+
+/// Generate an input made of several atoms, without any separators.
+fn input(rng: &mut Rng) -> String {
+    (0..1 + rng.below(6)).map(|_| atom(rng)).collect()
+}
+
+/// Find the longest replacement key at the start of the text.
+fn longest_key(text: &str) -> Option<(&'static str, &'static str)> {
+    REPLACEMENTS
+        .iter()
+        .chain(SUB_SUPER_SCRIPTS)
+        .copied()
+        .filter(|(key, _)| text.starts_with(key))
+        .max_by_key(|(key, _)| key.len())
+}
+
+/// One of the documented differences from the original.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Difference {
+    /// `\not` followed by more than a single symbol.
+    Negation,
+    /// A combining mark without a single character or symbol closed by `}`.
+    MarkArgument,
+    /// A combining mark applied to another one.
+    NestedMarks,
+}
+
+/// Find the first `\not` which the original negates differently.
+///
+/// The original matches `\not(\[A-z]+)`, and `A-z` includes `\`, `^`, `_`, `[`, `]` and backticks,
+/// so it also swallows the following text, and rewrites it to `\slash{...}`,
+/// which only works if the match is a single symbol.
+fn negation(text: &str) -> Option<usize> {
+    let mut from = 0;
+    while let Some(index) = text[from..].find("\\not\\").map(|index| from + index) {
+        let command = &text[index + 4..];
+        let len = 1 + command[1..]
+            .bytes()
+            .take_while(|b| (b'A'..=b'z').contains(b))
+            .count();
+        let is_symbol = command[1..len].bytes().all(|b| b.is_ascii_alphabetic())
+            && longest_key(command).is_some_and(|(key, value)| {
+                key.len() == len && value != "\\" && value.chars().count() == 1
+            });
+        let is_mark = COMBINING_MARKS
+            .iter()
+            .any(|&(key, _)| key == &command[..len]);
+        if !is_symbol || is_mark {
+            return Some(index);
+        }
+        from = index + 4 + len;
+    }
+    None
+}
+
+/// Find the first combining mark like `\hat{` whose argument is not a single character or symbol
+/// closed by `}`, since the original applies the mark to the first character after the brace,
+/// and drops the following one, whatever they are.
+fn mark_argument(text: &str) -> Option<(usize, Difference)> {
+    text.char_indices().find_map(|(index, _)| {
+        let rest = &text[index..];
+        let (mark, _) = COMBINING_MARKS
+            .iter()
+            .find(|(key, _)| rest.starts_with(key) && rest[key.len()..].starts_with('{'))?;
+        let argument = &rest[mark.len() + 1..];
+        if argument.starts_with('}')
+            && longest_key(rest).is_some_and(|(key, _)| key.len() > mark.len() + 1)
+        {
+            // an empty argument is a replacement like `\hat{}`
+            return None;
+        }
+        if COMBINING_MARKS
+            .iter()
+            .any(|(key, _)| argument.starts_with(key) && argument[key.len()..].starts_with('{'))
+        {
+            return Some((index, Difference::NestedMarks));
+        }
+        let single = match longest_key(argument) {
+            Some((key, value)) => {
+                value.chars().count() == 1 && argument[key.len()..].starts_with('}')
+            }
+            None => {
+                let mut chars = argument.chars();
+                chars.next().is_some_and(|c| !matches!(c, '{' | '}' | '\\'))
+                    && chars.as_str().starts_with('}')
+            }
+        };
+        (!single).then_some((index, Difference::MarkArgument))
+    })
+}
+
+fn check(seed: u64) {
+    let mut rng = Rng(seed);
+    let mut found = Vec::new();
+    for _ in 0..ITERATIONS {
+        let text = input(&mut rng);
+        if optimized(&text) == naive(&text) {
+            continue;
+        }
+        let negation = negation(&text).map(|index| (index, Difference::Negation));
+        let Some((index, difference)) = negation.into_iter().chain(mark_argument(&text)).min()
+        else {
+            panic!("undocumented difference for {text:?}");
+        };
+        // everything before the difference is converted the same way
+        let before = &text[..index];
+        assert_eq!(optimized(before), naive(before), "input: {text:?}");
+        found.push(difference);
+    }
+    // make sure the generated inputs cover every difference
+    for difference in [
+        Difference::Negation,
+        Difference::MarkArgument,
+        Difference::NestedMarks,
+    ] {
+        assert!(found.contains(&difference), "{difference:?}");
+    }
+}
+
+#[test]
+fn differential() {
+    check(0x2545_f491_4f6c_dd1d);
+}
+
+#[test]
+fn differential_alternate_seed() {
+    check(0x9e37_79b9_7f4a_7c15);
+}
+
+#[test]
+fn documented_differences() {
+    for (text, expected, original) in [
+        (
+            r"x \not\in\mathbb{R}",
+            "x \u{2208}\u{338}\u{211d}",
+            "x \u{2208}\u{338}mathbb}{R}",
+        ),
+        (
+            r"\not\subset_1",
+            "\u{2282}\u{338}\u{2081}",
+            "\u{2282}\u{338}}1",
+        ),
+        (r"\hat{ab}", r"\hat{ab}", "a\u{302}}"),
+        (r"\vec{1", r"\vec{1", "1\u{20d7}"),
+        (r"\bar{\hat{a}}", "a\u{302}\u{305}", "\\\u{305}hat{a}}"),
+        (r"\\mathrm{o}", r"\o", "\u{f8}"),
+    ] {
+        assert_eq!(optimized(text), expected, "input: {text:?}");
+        assert_eq!(naive(text), original, "input: {text:?}");
+    }
+}
@@ -2,21 +2,60 @@
 //! Converts latex to Unicode characters.
 //!
 //! Port of [unicodeit.net](https://www.unicodeit.net) to rust.
+//!
+//! ## `no_std` support
+//! Disabling the default `std` feature makes the crate depend only on `core` and `alloc`.
+//! The free functions like [`replace`] are still available,
+//! but the configurable `Converter` requires `std`.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use core::fmt;
 
+#[cfg(feature = "std")]
+mod converter;
 pub(crate) mod data;
 #[cfg(test)]
 mod data_test;
-#[cfg(any(feature = "naive-impl", not(feature = "prefer-optimized-impl")))]
+#[cfg(feature = "std")]
+mod delimiters;
+#[cfg(feature = "std")]
+mod diagnostics;
+#[cfg(all(test, feature = "naive-impl"))]
+mod differential_test;
+#[cfg(feature = "std")]
+mod macros;
+#[cfg(feature = "std")]
+mod mapping;
+#[cfg(feature = "markdown")]
+mod markdown;
+#[cfg(feature = "naive-impl")]
 mod naive_replace;
-#[cfg(any(feature = "optimized-impl", feature = "prefer-optimized-impl"))]
+#[cfg(feature = "normalization")]
+mod normalization;
 mod optimized_replace;
+#[cfg(feature = "std")]
+mod passes;
+#[cfg(feature = "std")]
+mod reverse;
+#[cfg(all(test, feature = "std"))]
+mod reverse_test;
+mod scripts;
+#[cfg(feature = "std")]
+mod stream;
+mod syntax;
+#[cfg(feature = "serde")]
+mod tables;
 
 /// Describe the version of the data used in the crate.
 ///
 /// The format of this string is implementation-specific,
 /// and may change at any time.
 pub fn version_info() -> String {
-    format!(
+    alloc::format!(
         "unicodeit.rs{}-data{}",
         env!("CARGO_PKG_VERSION"),
         data::UNICODEIT_VERSION
@@ -25,19 +64,172 @@ pub fn version_info() -> String {
 
 /// Replace the LaTeX characters with Unicode equivalents wherever possible.
 ///
-/// This function is a port of the [unicodeit](https://www.unicodeit.net) library to rust,
-/// which tries to exactly mimic the behavior of the original library.
+/// This function is a port of the [unicodeit](https://www.unicodeit.net) library to rust.
+/// It produces the same output as the original library,
+/// except where the original produces broken output:
+///
+/// - `\not` only negates the symbol immediately following it,
+///   so `\not\in\mathbb{R}` becomes `∉ℝ`.
+///   The original also negates any following letters, commands, `^`, `_`, `[` and `]`,
+///   and produces `∉mathbb}{R}`.
+/// - A combining mark is only applied to a single character or symbol closed by `}`,
+///   and is left unchanged otherwise, so `\hat{ab}` and `\vec{1` are unchanged.
+///   The original applies the mark to the first character after the brace and drops the next one,
+///   producing `â}` and `1⃗`.
+/// - Nested combining marks like `\bar{\hat{a}}` are applied from the inside out,
+///   instead of in the order of the original tables.
+/// - Every command is converted at most once, using the longest matching command.
+///   The original replaces each command in turn, reading the output again,
+///   so `\\mathrm{o}` becomes `\o` instead of `ø`.
+///
+/// This never panics, even if the input is malformed,
+/// and always takes time linear in the length of the input.
+#[cfg_attr(
+    feature = "std",
+    doc = "\n\nThis is equivalent to calling [`Converter::replace`] on the default converter."
+)]
 #[inline]
 pub fn replace(text: &str) -> String {
-    cfg_if::cfg_if! {
-        if #[cfg(feature = "prefer-optimized-impl")] {
-            naive_replace::replace(text)
-        } else {
-            naive_replace::replace(text)
-        }
-    }
+    optimized_replace::replace(text)
+}
+
+/// Replace the LaTeX characters with Unicode equivalents,
+/// borrowing the input if it cannot contain anything to convert.
+#[cfg_attr(
+    feature = "std",
+    doc = "\n\nThis is equivalent to calling [`Converter::replace_cow`] on the default converter."
+)]
+#[inline]
+pub fn replace_cow(text: &str) -> Cow<'_, str> {
+    optimized_replace::default_engine().replace_cow(text)
+}
+
+/// Append the converted text to `out`,
+/// without any other allocation.
+///
+/// The only exception is a combining mark whose argument is followed by characters
+/// which extend it, like the `\u{301}` in `\hat{e\u{301}}`.
+#[cfg_attr(
+    feature = "std",
+    doc = "\n\nThis is equivalent to calling [`Converter::replace_to_string`] on the default converter."
+)]
+#[inline]
+pub fn replace_to_string(text: &str, out: &mut String) {
+    optimized_replace::default_engine().replace_to_string(text, out);
+}
+
+/// Write the converted text to `out`,
+/// without any allocation.
+///
+/// The only exception is a combining mark whose argument is followed by characters
+/// which extend it, like the `\u{301}` in `\hat{e\u{301}}`.
+#[cfg_attr(
+    feature = "std",
+    doc = "\n\nThis is equivalent to calling [`Converter::replace_into`] on the default converter."
+)]
+#[inline]
+pub fn replace_into<W: fmt::Write + ?Sized>(text: &str, out: &mut W) -> fmt::Result {
+    optimized_replace::default_engine().replace_into(text, out)
+}
+
+/// Split the text into [`Token`]s, describing which parts are converted and how.
+///
+/// The tokens are found exactly like [`replace`] converts the text,
+/// so concatenating their [converted text](Token::unicode) gives the same output.
+#[cfg_attr(
+    feature = "std",
+    doc = "\n\nThis is equivalent to calling [`Converter::tokens`] on the default converter."
+)]
+///
+/// ```
+/// use unicodeit::Token;
+/// let tokens: Vec<Token> = unicodeit::tokens(r"a \alpha^2 \unknown").collect();
+/// assert_eq!(tokens, [
+///     Token::Literal("a "),
+///     Token::Symbol { latex: r"\alpha", unicode: "\u{3b1}" },
+///     Token::Script { latex: "^2", kind: unicodeit::ScriptKind::Superscript, chars: "\u{b2}".into() },
+///     Token::Literal(" "),
+///     Token::Unknown(r"\unknown"),
+/// ]);
+/// ```
+#[inline]
+pub fn tokens(text: &str) -> Tokens<'_> {
+    optimized_replace::default_engine().tokens(text)
+}
+
+/// Replace the LaTeX characters with Unicode equivalents,
+/// also returning a map between offsets in the input and the output.
+///
+/// This is equivalent to calling [`Converter::replace_with_mapping`] on the default converter.
+#[cfg(feature = "std")]
+#[inline]
+pub fn replace_with_mapping(text: &str) -> (String, OffsetMap) {
+    converter::DEFAULT_CONVERTER.replace_with_mapping(text)
+}
+
+/// Replace the LaTeX characters with Unicode equivalents,
+/// also returning diagnostics for any input which could not be converted.
+///
+/// This is equivalent to calling [`Converter::replace_with_diagnostics`] on the default converter.
+#[cfg(feature = "std")]
+#[inline]
+pub fn replace_with_diagnostics(text: &str) -> (String, Vec<Diagnostic>) {
+    converter::DEFAULT_CONVERTER.replace_with_diagnostics(text)
 }
 
+/// Replace the LaTeX characters with Unicode equivalents,
+/// failing if any of the input could not be converted.
+///
+/// This is equivalent to calling [`Converter::try_replace`] on a [strict](ConverterBuilder::strict)
+/// version of the default converter.
+///
+/// ```
+/// let error = unicodeit::try_replace(r"\alpha + \brevee{a}").unwrap_err();
+/// assert_eq!(error.span(), 9..16);
+/// assert_eq!(error.output(), unicodeit::replace(r"\alpha + \brevee{a}"));
+/// ```
+#[cfg(feature = "std")]
+#[inline]
+pub fn try_replace(text: &str) -> Result<String, ConversionError> {
+    converter::STRICT_CONVERTER.try_replace(text)
+}
+
+/// Replace the LaTeX characters with Unicode equivalents in a Markdown document,
+/// leaving code, HTML and URLs unchanged.
+///
+/// This is equivalent to calling [`Converter::replace_markdown`] on the default converter.
+#[cfg(feature = "markdown")]
+#[inline]
+pub fn replace_markdown(text: &str) -> String {
+    converter::DEFAULT_CONVERTER.replace_markdown(text)
+}
+
+#[cfg(feature = "std")]
+pub use converter::{ConversionLimit, Converter, ConverterBuilder};
+#[cfg(feature = "std")]
+pub use delimiters::MathDelimiters;
+#[cfg(feature = "std")]
+pub use diagnostics::{ConversionError, Diagnostic, DiagnosticKind, Severity};
+#[cfg(feature = "std")]
+pub use macros::{MacroError, MacroLimit, Macros};
+#[cfg(feature = "std")]
+pub use mapping::{OffsetMap, Segment};
+#[cfg(feature = "normalization")]
+pub use normalization::Normalization;
+pub use optimized_replace::tokens::{Token, Tokens};
+#[cfg(feature = "std")]
+pub use passes::{BuiltinPass, Pass, Passes};
+#[cfg(feature = "std")]
+pub use reverse::to_latex;
+pub use scripts::{ScriptFallback, ScriptKind};
+#[cfg(feature = "futures-io")]
+pub use stream::{AsyncReplaceReader, AsyncReplaceWriter};
+#[cfg(feature = "std")]
+pub use stream::{ReplaceReader, ReplaceWriter};
+pub use syntax::{Node, NodeKind, parse};
+#[cfg(feature = "serde")]
+pub use tables::{InvalidEntry, SymbolTables, TableError, TableKind};
+
 #[cfg(feature = "naive-impl")]
 pub use naive_replace::replace as replace_naive;
 
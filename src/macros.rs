@@ -0,0 +1,566 @@
+//! Expansion of macros defined with `\newcommand` and `\def`.
+//!
+//! This runs before the rest of the conversion,
+//! so the expanded text is converted like any other input.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::ops::Range;
+
+/// The maximum number of parameters a macro can have, like in TeX.
+const MAX_PARAMS: usize = 9;
+
+/// A macro defined by `\newcommand`, `\DeclareMathOperator` or `\def`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Macro {
+    /// The number of parameters, at most [`MAX_PARAMS`].
+    params: usize,
+    /// The default value of the first parameter, if it is optional.
+    default: Option<String>,
+    /// The replacement text, with parameters like `#1`.
+    body: String,
+}
+
+/// The commands which define macros.
+pub(crate) const DEFINITION_COMMANDS: [&str; 4] = [
+    r"\newcommand",
+    r"\renewcommand",
+    r"\DeclareMathOperator",
+    r"\def",
+];
+
+/// Macros defined while expanding, keyed by name including the backslash.
+pub(crate) type Definitions = HashMap<String, Macro>;
+
+/// Macro definitions to expand before converting,
+/// enabled using [`ConverterBuilder::macros`](crate::ConverterBuilder::macros).
+///
+/// Definitions can be given in a separate [preamble](Self::preamble),
+/// or appear in the text itself.
+/// The supported forms of definition are:
+///
+/// - `\newcommand{\name}[2]{body}` and `\renewcommand`,
+///   including an optional first argument like `\newcommand{\name}[2][default]{body}`
+/// - `\DeclareMathOperator{\name}{text}`
+/// - `\def\name#1#2{body}`, with undelimited parameters only
+///
+/// Definitions in the text are removed from the output.
+/// Expansion is limited by both [depth](Self::max_depth) and [length](Self::max_len),
+/// to guard against runaway recursion like `\def\x{\x\x}`.
+///
+/// ```
+/// use unicodeit::{Converter, Macros};
+/// let macros = Macros::new().preamble(r"\newcommand{\norm}[1]{\Vert #1 \Vert}");
+/// let converter = Converter::builder().macros(macros).build();
+/// assert_eq!(converter.replace(r"\norm{\alpha}"), "\u{2016} \u{3b1} \u{2016}");
+/// ```
+#[derive(Clone, Debug)]
+pub struct Macros {
+    definitions: Definitions,
+    max_depth: usize,
+    max_len: usize,
+}
+
+impl Macros {
+    /// The default limit on the depth of nested expansions.
+    pub const DEFAULT_MAX_DEPTH: usize = 64;
+    /// The default limit on the total length of expanded text, in bytes.
+    pub const DEFAULT_MAX_LEN: usize = 1 << 20;
+
+    /// Create an empty set of macros, with the default limits.
+    pub fn new() -> Self {
+        Macros {
+            definitions: HashMap::new(),
+            max_depth: Self::DEFAULT_MAX_DEPTH,
+            max_len: Self::DEFAULT_MAX_LEN,
+        }
+    }
+
+    /// Add the macros defined in a preamble.
+    ///
+    /// Everything other than the definitions is ignored,
+    /// and later definitions replace earlier ones.
+    /// Macros used in the preamble are not expanded,
+    /// so definitions can refer to macros defined later.
+    pub fn preamble(mut self, preamble: &str) -> Self {
+        let matches = Matches::new(preamble);
+        let mut pos = 0;
+        while let Some(found) = preamble[pos..].find('\\') {
+            let start = pos + found;
+            match parse_definition(preamble, &matches, start) {
+                Some((name, definition, end)) => {
+                    self.definitions.insert(name, definition);
+                    pos = end;
+                }
+                None => pos = control_sequence(preamble, start).map_or(start + 1, |(_, end)| end),
+            }
+        }
+        self
+    }
+
+    /// Limit the depth of nested expansions, [64](Self::DEFAULT_MAX_DEPTH) by default.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Limit the total length of expanded text in bytes,
+    /// [1 MiB](Self::DEFAULT_MAX_LEN) by default.
+    pub fn max_len(mut self, len: usize) -> Self {
+        self.max_len = len;
+        self
+    }
+
+    /// Determine if a macro like `\norm` is defined.
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.definitions.contains_key(name)
+    }
+
+    /// The names of the defined macros, in no particular order.
+    pub(crate) fn names(&self) -> impl Iterator<Item = &str> {
+        self.definitions.keys().map(String::as_str)
+    }
+
+    /// Expand the macros in the text, without converting it.
+    ///
+    /// Fails if any expansion exceeds the limits.
+    ///
+    /// ```
+    /// use unicodeit::Macros;
+    /// let macros = Macros::new();
+    /// assert_eq!(macros.expand(r"\def\pair#1#2{(#1, #2)} \pair a{bc}")?, " (a, bc)");
+    /// assert!(macros.expand(r"\def\x{\x\x} \x").is_err());
+    /// # Ok::<(), unicodeit::MacroError>(())
+    /// ```
+    pub fn expand(&self, text: &str) -> Result<String, MacroError> {
+        let expansion = self.expand_with(text, &mut Definitions::new());
+        match expansion.failures.into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(expansion.text.into_owned()),
+        }
+    }
+
+    /// Expand the macros in the text,
+    /// adding any definitions in the text to `local`.
+    ///
+    /// Invocations which exceed the limits are left unexpanded.
+    pub(crate) fn expand_with<'t>(&self, text: &'t str, local: &mut Definitions) -> Expansion<'t> {
+        let mut expander = Expander {
+            macros: self,
+            local,
+            expanded_len: 0,
+        };
+        let matches = Matches::new(text);
+        let mut output = String::new();
+        let mut edits = Vec::new();
+        let mut failures = Vec::new();
+        let mut last_index = 0;
+        let mut pos = 0;
+        while let Some(found) = text[pos..].find('\\') {
+            let start = pos + found;
+            let (replacement, end) = match expander.expand_at(text, &matches, start, 0) {
+                Step::Copy(end) => {
+                    pos = end;
+                    continue;
+                }
+                Step::Define(end) => (Cow::Borrowed(""), end),
+                Step::Expand(expanded, end) => (Cow::Owned(expanded), end),
+                Step::Fail(limit, end) => {
+                    failures.push(MacroError {
+                        span: start..end,
+                        name: control_sequence(text, start).unwrap().0.into(),
+                        limit,
+                    });
+                    pos = end;
+                    continue;
+                }
+            };
+            output.push_str(&text[last_index..start]);
+            output.push_str(&replacement);
+            edits.push((start..end, replacement.len()));
+            last_index = end;
+            pos = end;
+        }
+        let text = if edits.is_empty() {
+            Cow::Borrowed(text)
+        } else {
+            output.push_str(&text[last_index..]);
+            Cow::Owned(output)
+        };
+        Expansion {
+            text,
+            edits,
+            failures,
+        }
+    }
+}
+
+impl Default for Macros {
+    #[inline]
+    fn default() -> Self {
+        Macros::new()
+    }
+}
+
+/// The result of [`Macros::expand_with`].
+pub(crate) struct Expansion<'t> {
+    /// The expanded text.
+    pub(crate) text: Cow<'t, str>,
+    /// The ranges of the input which were replaced, and the length of their replacements.
+    pub(crate) edits: Vec<(Range<usize>, usize)>,
+    /// Invocations which were left unexpanded because they exceeded a limit.
+    pub(crate) failures: Vec<MacroError>,
+}
+
+/// The limit exceeded by a [`MacroError`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MacroLimit {
+    /// Too many nested expansions, set by [`Macros::max_depth`].
+    Depth,
+    /// Too much expanded text, set by [`Macros::max_len`].
+    Length,
+}
+
+impl Display for MacroLimit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MacroLimit::Depth => "depth",
+            MacroLimit::Length => "length",
+        })
+    }
+}
+
+/// The error returned by [`Macros::expand`] when an expansion exceeds a limit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MacroError {
+    /// The byte range in the input of the macro invocation, including its arguments.
+    pub span: Range<usize>,
+    /// The name of the macro, including the leading backslash.
+    pub name: String,
+    /// The limit that was exceeded.
+    pub limit: MacroLimit,
+}
+
+impl Display for MacroError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expanding `{}` at {}..{} exceeded the {} limit",
+            self.name, self.span.start, self.span.end, self.limit
+        )
+    }
+}
+
+impl std::error::Error for MacroError {}
+
+/// The result of expanding the control sequence at a position.
+enum Step {
+    /// Not a macro, so copy the text up to the position.
+    Copy(usize),
+    /// A definition, which is removed up to the position.
+    Define(usize),
+    /// A macro invocation up to the position, with its expansion.
+    Expand(String, usize),
+    /// A macro invocation up to the position, which exceeded a limit.
+    Fail(MacroLimit, usize),
+}
+
+struct Expander<'a> {
+    macros: &'a Macros,
+    local: &'a mut Definitions,
+    /// The total length of the macro bodies expanded so far.
+    expanded_len: usize,
+}
+
+impl Expander<'_> {
+    fn lookup(&self, name: &str) -> Option<&Macro> {
+        self.local
+            .get(name)
+            .or_else(|| self.macros.definitions.get(name))
+    }
+
+    /// Expand the control sequence at the start position.
+    fn expand_at(&mut self, text: &str, matches: &Matches, start: usize, depth: usize) -> Step {
+        let Some((name, name_end)) = control_sequence(text, start) else {
+            return Step::Copy(start + 1);
+        };
+        if let Some((name, definition, end)) = parse_definition(text, matches, start) {
+            self.local.insert(name, definition);
+            return Step::Define(end);
+        }
+        let Some(definition) = self.lookup(name).cloned() else {
+            return Step::Copy(name_end);
+        };
+        let Some((arguments, end)) = parse_arguments(text, matches, name_end, &definition) else {
+            // missing arguments
+            return Step::Copy(name_end);
+        };
+        if depth >= self.macros.max_depth {
+            return Step::Fail(MacroLimit::Depth, end);
+        }
+        let body = substitute(&definition.body, &arguments);
+        self.expanded_len += body.len();
+        if self.expanded_len > self.macros.max_len {
+            return Step::Fail(MacroLimit::Length, end);
+        }
+        match self.expand_nested(&body, depth + 1) {
+            Ok(expanded) => Step::Expand(expanded, end),
+            Err(limit) => Step::Fail(limit, end),
+        }
+    }
+
+    /// Expand the body of a macro, failing if any nested expansion exceeds a limit.
+    fn expand_nested(&mut self, text: &str, depth: usize) -> Result<String, MacroLimit> {
+        let matches = Matches::new(text);
+        let mut output = String::with_capacity(text.len());
+        let mut last_index = 0;
+        let mut pos = 0;
+        while let Some(found) = text[pos..].find('\\') {
+            let start = pos + found;
+            let (replacement, end) = match self.expand_at(text, &matches, start, depth) {
+                Step::Copy(end) => {
+                    pos = end;
+                    continue;
+                }
+                Step::Define(end) => (String::new(), end),
+                Step::Expand(expanded, end) => (expanded, end),
+                Step::Fail(limit, _) => return Err(limit),
+            };
+            output.push_str(&text[last_index..start]);
+            output.push_str(&replacement);
+            last_index = end;
+            pos = end;
+        }
+        output.push_str(&text[last_index..]);
+        Ok(output)
+    }
+}
+
+/// Read the control sequence starting at the backslash at `start`,
+/// like `\alpha` or `\{`, returning it and the index after it.
+fn control_sequence(text: &str, start: usize) -> Option<(&str, usize)> {
+    let rest = text[start..].strip_prefix('\\')?;
+    let len = match rest.find(|c: char| !c.is_ascii_alphabetic()) {
+        Some(0) => rest.chars().next()?.len_utf8(),
+        Some(len) => len,
+        None if rest.is_empty() => return None,
+        None => rest.len(),
+    };
+    let end = start + 1 + len;
+    Some((&text[start..end], end))
+}
+
+fn skip_whitespace(text: &str, pos: usize) -> usize {
+    text[pos..]
+        .find(|c: char| !c.is_whitespace())
+        .map_or(text.len(), |offset| pos + offset)
+}
+
+/// The closing braces and brackets matching those in a text.
+///
+/// These are found in a single pass over the text,
+/// so reading an argument never scans to the end of the text again,
+/// which would take quadratic time for many unclosed braces.
+struct Matches {
+    /// The index of the `}` closing each `{`.
+    braces: HashMap<usize, usize>,
+    /// The index of the `]` closing each `[`,
+    /// which is the next one outside of any nested braces.
+    brackets: HashMap<usize, usize>,
+}
+
+impl Matches {
+    fn new(text: &str) -> Self {
+        let mut matches = Matches {
+            braces: HashMap::new(),
+            brackets: HashMap::new(),
+        };
+        let mut open_braces = Vec::new();
+        // the unclosed brackets directly within each open brace, and outside of them
+        let mut open_brackets = vec![Vec::new()];
+        let mut bytes = text.bytes().enumerate();
+        while let Some((index, byte)) = bytes.next() {
+            match byte {
+                b'\\' => {
+                    // skip escaped braces like `\{`
+                    bytes.next();
+                }
+                b'{' => {
+                    open_braces.push(index);
+                    open_brackets.push(Vec::new());
+                }
+                b'}' => {
+                    // brackets directly within the group are never closed
+                    match open_braces.pop() {
+                        Some(start) => {
+                            matches.braces.insert(start, index);
+                            open_brackets.pop();
+                        }
+                        None => open_brackets[0].clear(),
+                    }
+                }
+                b'[' => open_brackets.last_mut().unwrap().push(index),
+                b']' => {
+                    for start in open_brackets.last_mut().unwrap().drain(..) {
+                        matches.brackets.insert(start, index);
+                    }
+                }
+                _ => {}
+            }
+        }
+        matches
+    }
+}
+
+/// Read a balanced group starting at the brace at `start`,
+/// returning its contents and the index after the closing brace.
+fn group<'t>(text: &'t str, matches: &Matches, start: usize) -> Option<(&'t str, usize)> {
+    let &end = matches.braces.get(&start)?;
+    Some((&text[start + 1..end], end + 1))
+}
+
+/// Read an optional argument like `[2]` after any whitespace,
+/// returning its contents and the index after the closing bracket.
+fn optional_argument<'t>(text: &'t str, matches: &Matches, pos: usize) -> Option<(&'t str, usize)> {
+    let start = skip_whitespace(text, pos);
+    let &end = matches.brackets.get(&start)?;
+    Some((&text[start + 1..end], end + 1))
+}
+
+/// Read an undelimited argument after any whitespace,
+/// which is either a braced group, a control sequence or a single character.
+fn argument<'t>(text: &'t str, matches: &Matches, pos: usize) -> Option<(&'t str, usize)> {
+    let start = skip_whitespace(text, pos);
+    match text[start..].chars().next()? {
+        '{' => group(text, matches, start),
+        '\\' => control_sequence(text, start),
+        '}' => None,
+        c => Some((&text[start..start + c.len_utf8()], start + c.len_utf8())),
+    }
+}
+
+/// Read the name of a macro being defined, either `\name` or `{\name}`.
+fn macro_name<'t>(text: &'t str, matches: &Matches, pos: usize) -> Option<(&'t str, usize)> {
+    let start = skip_whitespace(text, pos);
+    match text[start..].chars().next()? {
+        '\\' => control_sequence(text, start),
+        '{' => {
+            let (contents, end) = group(text, matches, start)?;
+            let contents = contents.trim();
+            match control_sequence(contents, 0) {
+                Some((name, name_end)) if name_end == contents.len() => Some((name, end)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Parse the definition starting at the backslash at `start`,
+/// returning the name of the macro, its definition, and the index after the definition.
+fn parse_definition(text: &str, matches: &Matches, start: usize) -> Option<(String, Macro, usize)> {
+    let (command, mut pos) = control_sequence(text, start)?;
+    match command {
+        r"\newcommand" | r"\renewcommand" | r"\DeclareMathOperator" => {
+            if text[pos..].starts_with('*') {
+                pos += 1;
+            }
+            let (name, mut pos) = macro_name(text, matches, pos)?;
+            let mut params = 0;
+            let mut default = None;
+            if command != r"\DeclareMathOperator" {
+                if let Some((count, end)) = optional_argument(text, matches, pos) {
+                    params = count.trim().parse::<usize>().ok()?;
+                    if params > MAX_PARAMS {
+                        return None;
+                    }
+                    pos = end;
+                }
+                if let Some((value, end)) = optional_argument(text, matches, pos)
+                    && params > 0
+                {
+                    default = Some(value.to_string());
+                    pos = end;
+                }
+            }
+            let (body, end) = argument(text, matches, pos)?;
+            let definition = Macro {
+                params,
+                default,
+                body: body.to_string(),
+            };
+            Some((name.to_string(), definition, end))
+        }
+        r"\def" => {
+            let (name, mut pos) = macro_name(text, matches, pos)?;
+            let mut params = 0;
+            // only simple parameters like `#1#2` are supported
+            while let Some(rest) = text[pos..].strip_prefix('#') {
+                let digit = rest.chars().next()?.to_digit(10)? as usize;
+                if digit != params + 1 {
+                    return None;
+                }
+                params = digit;
+                pos += 2;
+            }
+            let (body, end) = group(text, matches, pos)?;
+            let definition = Macro {
+                params,
+                default: None,
+                body: body.to_string(),
+            };
+            Some((name.to_string(), definition, end))
+        }
+        _ => None,
+    }
+}
+
+/// Read the arguments to an invocation of a macro, starting after its name.
+fn parse_arguments<'t>(
+    text: &'t str,
+    matches: &Matches,
+    mut pos: usize,
+    definition: &'t Macro,
+) -> Option<(Vec<&'t str>, usize)> {
+    let mut arguments = Vec::with_capacity(definition.params);
+    if let Some(default) = &definition.default {
+        match optional_argument(text, matches, pos) {
+            Some((value, end)) => {
+                arguments.push(value);
+                pos = end;
+            }
+            None => arguments.push(default),
+        }
+    }
+    while arguments.len() < definition.params {
+        let (value, end) = argument(text, matches, pos)?;
+        arguments.push(value);
+        pos = end;
+    }
+    Some((arguments, pos))
+}
+
+/// Substitute the arguments for parameters like `#1` in the body of a macro.
+fn substitute(body: &str, arguments: &[&str]) -> String {
+    let mut result = String::with_capacity(body.len());
+    let mut chars = body.char_indices().peekable();
+    while let Some((_, c)) = chars.next() {
+        if c != '#' {
+            result.push(c);
+            continue;
+        }
+        match chars.peek().map(|&(_, next)| next) {
+            Some('#') => {
+                result.push('#');
+                chars.next();
+            }
+            Some(digit @ '1'..='9') if (digit as usize - '1' as usize) < arguments.len() => {
+                result.push_str(arguments[digit as usize - '1' as usize]);
+                chars.next();
+            }
+            _ => result.push('#'),
+        }
+    }
+    result
+}
@@ -0,0 +1,302 @@
+//! Mapping between byte offsets in the LaTeX input and the Unicode output.
+
+use std::ops::Range;
+
+/// A correspondence between a range of the input and a range of the output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Segment {
+    /// The byte range in the LaTeX input.
+    pub input: Range<usize>,
+    /// The byte range in the Unicode output.
+    pub output: Range<usize>,
+    /// If the input was copied to the output unchanged.
+    ///
+    /// Offsets within an unchanged segment correspond exactly,
+    /// while a changed segment can only be mapped as a whole.
+    pub unchanged: bool,
+}
+
+/// Maps byte offsets in the LaTeX input to byte offsets in the Unicode output,
+/// and vice versa.
+///
+/// The map is made up of contiguous [segments](Segment),
+/// which together cover both the entire input and the entire output.
+/// Adjacent substitutions are kept as separate segments wherever possible.
+///
+/// ```
+/// let (output, map) = unicodeit::replace_with_mapping(r"x = \alpha");
+/// assert_eq!(output, "x = \u{3b1}");
+/// assert_eq!(map.output_range(4..10), 4..6);
+/// assert_eq!(map.input_range(0..1), 0..1);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OffsetMap {
+    segments: Vec<Segment>,
+}
+
+impl OffsetMap {
+    /// Create a map where the input is copied unchanged.
+    pub(crate) fn identity(len: usize) -> Self {
+        let mut builder = OffsetMapBuilder::default();
+        builder.unchanged(len);
+        builder.finish()
+    }
+
+    /// The segments of the map, in order.
+    #[inline]
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// The length of the input covered by this map.
+    #[inline]
+    pub fn input_len(&self) -> usize {
+        self.segments.last().map_or(0, |segment| segment.input.end)
+    }
+
+    /// The length of the output covered by this map.
+    #[inline]
+    pub fn output_len(&self) -> usize {
+        self.segments.last().map_or(0, |segment| segment.output.end)
+    }
+
+    /// Map a range of the input to the corresponding range of the output.
+    ///
+    /// If the range partially overlaps a changed segment,
+    /// the result is extended to include that entire segment.
+    /// An empty range maps to an empty range
+    /// unless it falls inside a changed segment.
+    ///
+    /// Offsets past the end of the input are clamped.
+    pub fn output_range(&self, input: Range<usize>) -> Range<usize> {
+        map_range(&self.segments, input, |s| &s.input, |s| &s.output)
+    }
+
+    /// Map a range of the output back to the corresponding range of the input.
+    ///
+    /// This behaves the same as [`OffsetMap::output_range`], but in reverse.
+    pub fn input_range(&self, output: Range<usize>) -> Range<usize> {
+        map_range(&self.segments, output, |s| &s.output, |s| &s.input)
+    }
+
+    /// Compose this map with a map of a later conversion pass,
+    /// giving a map from the input of this map to the output of the next.
+    pub(crate) fn then(&self, next: &OffsetMap) -> OffsetMap {
+        debug_assert_eq!(self.output_len(), next.input_len());
+        let (first, second) = (&self.segments, &next.segments);
+        let mut builder = OffsetMapBuilder::default();
+        let (mut i, mut j) = (0, 0);
+        // position in the intermediate text
+        let mut pos = 0;
+        // map a position in the intermediate text using the current segments
+        let first_input = |i: usize, pos: usize| match first.get(i) {
+            Some(segment) => segment.input.start + (pos - segment.output.start),
+            None => self.input_len(),
+        };
+        let second_output = |j: usize, pos: usize| match second.get(j) {
+            Some(segment) => segment.output.start + (pos - segment.input.start),
+            None => next.output_len(),
+        };
+        while i < first.len() && j < second.len() {
+            let input_start = first_input(i, pos);
+            let output_start = second_output(j, pos);
+            // include any empty segments that were skipped over
+            builder.changed(
+                input_start - builder.input_pos,
+                output_start - builder.output_pos,
+            );
+            if first[i].unchanged && second[j].unchanged {
+                let end = first[i].output.end.min(second[j].input.end);
+                builder.unchanged(end - pos);
+                pos = end;
+            } else {
+                // extend until both maps are at a boundary
+                let mut end = pos;
+                if !first[i].unchanged {
+                    end = end.max(first[i].output.end);
+                }
+                if !second[j].unchanged {
+                    end = end.max(second[j].input.end);
+                }
+                loop {
+                    while i < first.len() && first[i].output.end <= end {
+                        i += 1;
+                    }
+                    while j < second.len() && second[j].input.end <= end {
+                        j += 1;
+                    }
+                    let mut extended = end;
+                    if let Some(segment) = first.get(i)
+                        && !segment.unchanged
+                        && segment.output.start < end
+                    {
+                        extended = extended.max(segment.output.end);
+                    }
+                    if let Some(segment) = second.get(j)
+                        && !segment.unchanged
+                        && segment.input.start < end
+                    {
+                        extended = extended.max(segment.input.end);
+                    }
+                    if extended == end {
+                        break;
+                    }
+                    end = extended;
+                }
+                builder.changed(
+                    first_input(i, end) - input_start,
+                    second_output(j, end) - output_start,
+                );
+                pos = end;
+            }
+            while i < first.len() && first[i].output.end <= pos {
+                i += 1;
+            }
+            while j < second.len() && second[j].input.end <= pos {
+                j += 1;
+            }
+        }
+        builder.changed(
+            self.input_len() - builder.input_pos,
+            next.output_len() - builder.output_pos,
+        );
+        builder.finish()
+    }
+}
+
+fn map_range(
+    segments: &[Segment],
+    range: Range<usize>,
+    from: impl Fn(&Segment) -> &Range<usize>,
+    to: impl Fn(&Segment) -> &Range<usize>,
+) -> Range<usize> {
+    let Some(last) = segments.last() else {
+        return 0..0;
+    };
+    let map_offset = |offset: usize, is_end: bool| -> usize {
+        let offset = offset.min(from(last).end);
+        // the first segment ending after the offset (or at it, for the end of a range)
+        let index = segments.partition_point(|s| {
+            if is_end {
+                from(s).end < offset
+            } else {
+                from(s).end <= offset
+            }
+        });
+        let Some(segment) = segments.get(index) else {
+            return to(last).end;
+        };
+        let (source, target) = (from(segment), to(segment));
+        if segment.unchanged || offset == source.start {
+            target.start + (offset - source.start).min(target.len())
+        } else if offset == source.end || is_end {
+            target.end
+        } else {
+            target.start
+        }
+    };
+    let start = map_offset(range.start, false);
+    // an empty range inside a changed segment covers the whole segment
+    let end = map_offset(range.end.max(range.start), true);
+    start..end
+}
+
+/// Builds an [`OffsetMap`] for a single pass over the text.
+#[derive(Default)]
+pub(crate) struct OffsetMapBuilder {
+    segments: Vec<Segment>,
+    input_pos: usize,
+    output_pos: usize,
+}
+
+impl OffsetMapBuilder {
+    /// Record input that was copied to the output unchanged.
+    pub(crate) fn unchanged(&mut self, len: usize) {
+        if len == 0 {
+            return;
+        }
+        match self.segments.last_mut() {
+            Some(last) if last.unchanged => {
+                last.input.end += len;
+                last.output.end += len;
+            }
+            _ => self.segments.push(Segment {
+                input: self.input_pos..self.input_pos + len,
+                output: self.output_pos..self.output_pos + len,
+                unchanged: true,
+            }),
+        }
+        self.input_pos += len;
+        self.output_pos += len;
+    }
+
+    /// Record input that was replaced by different output.
+    pub(crate) fn changed(&mut self, input_len: usize, output_len: usize) {
+        if input_len == 0 && output_len == 0 {
+            return;
+        }
+        self.segments.push(Segment {
+            input: self.input_pos..self.input_pos + input_len,
+            output: self.output_pos..self.output_pos + output_len,
+            unchanged: false,
+        });
+        self.input_pos += input_len;
+        self.output_pos += output_len;
+    }
+
+    pub(crate) fn finish(self) -> OffsetMap {
+        OffsetMap {
+            segments: self.segments,
+        }
+    }
+}
+
+/// Tracks the offset map across the passes of a conversion, if requested.
+pub(crate) struct MappingTracker {
+    map: Option<OffsetMap>,
+}
+
+impl MappingTracker {
+    /// Create a tracker, which does nothing unless enabled.
+    pub(crate) fn new(enabled: bool, text: &str) -> Self {
+        MappingTracker {
+            map: enabled.then(|| OffsetMap::identity(text.len())),
+        }
+    }
+
+    /// Record a pass which replaced the specified ranges of the text.
+    ///
+    /// The replacements are given as the range in the old text
+    /// and the length of the new text, and must be sorted and non-overlapping.
+    pub(crate) fn record(
+        &mut self,
+        old_len: usize,
+        replacements: impl IntoIterator<Item = (Range<usize>, usize)>,
+    ) {
+        let Some(ref mut map) = self.map else {
+            return;
+        };
+        let mut builder = OffsetMapBuilder::default();
+        let mut last_index = 0;
+        for (range, new_len) in replacements {
+            builder.unchanged(range.start - last_index);
+            builder.changed(range.len(), new_len);
+            last_index = range.end;
+        }
+        builder.unchanged(old_len - last_index);
+        *map = map.then(&builder.finish());
+    }
+
+    /// Map a range of the current text back to the original text,
+    /// or leave it unchanged if the tracker is disabled.
+    pub(crate) fn input_range(&self, range: Range<usize>) -> Range<usize> {
+        match &self.map {
+            Some(map) => map.input_range(range),
+            None => range,
+        }
+    }
+
+    pub(crate) fn finish(self) -> Option<OffsetMap> {
+        self.map
+    }
+}
@@ -0,0 +1,174 @@
+//! Finding the text and math in Markdown, using [`pulldown_cmark`].
+
+use crate::delimiters::Piece;
+use pulldown_cmark::{Event, LinkType, Options, Parser, Tag, TagEnd};
+use std::ops::Range;
+
+/// Split a Markdown document into the text and math to convert,
+/// and everything else, which is left unchanged.
+///
+/// Code, HTML, autolinks, the labels of reference links like `[a_1]`, bare URLs like `https://example.com/a_1` and front matter are never converted,
+/// and neither are characters escaped with a backslash or written as entities like `&amp;`.
+/// Math like `$x_1$` and `$$\alpha$$` is converted without its delimiters.
+pub(crate) fn split(text: &str) -> Vec<Piece> {
+    let options = Options::ENABLE_MATH
+        | Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS;
+    let mut pieces = Pieces {
+        text,
+        pieces: Vec::new(),
+        end: 0,
+        run: None,
+    };
+    // how many enclosing elements contain text which must not be converted
+    let mut verbatim_depth = 0usize;
+    // the end of the last text, so an escaped backslash is not mistaken for an escape
+    let mut text_end = 0;
+    for (event, mut range) in Parser::new_ext(text, options).into_offset_iter() {
+        match event {
+            Event::Start(
+                Tag::CodeBlock(_)
+                | Tag::MetadataBlock(_)
+                | Tag::HtmlBlock
+                | Tag::Link {
+                    // the text of a reference link without a separate label is its label
+                    link_type:
+                        LinkType::Autolink
+                        | LinkType::Email
+                        | LinkType::Shortcut
+                        | LinkType::ShortcutUnknown
+                        | LinkType::Collapsed
+                        | LinkType::CollapsedUnknown,
+                    ..
+                },
+            ) => verbatim_depth += 1,
+            Event::End(TagEnd::CodeBlock | TagEnd::MetadataBlock(_) | TagEnd::HtmlBlock) => {
+                verbatim_depth -= 1;
+            }
+            Event::End(TagEnd::Link) if verbatim_depth > 0 => verbatim_depth -= 1,
+            Event::Text(content) => {
+                let escaped = is_escaped(text, range.start) && text_end != range.start;
+                text_end = range.end;
+                if verbatim_depth > 0 || *content != text[range.clone()] {
+                    // entities like `&amp;` differ from their text
+                    continue;
+                }
+                if escaped {
+                    // the escaped character is kept as it is
+                    range.start += 1;
+                }
+                pieces.extend_run(range);
+            }
+            Event::SoftBreak if verbatim_depth == 0 => pieces.extend_run(range),
+            Event::InlineMath(content) | Event::DisplayMath(content) => {
+                let delimiter_len = (range.len() - content.len()) / 2;
+                let content_range = range.start + delimiter_len..range.end - delimiter_len;
+                if text.get(content_range.clone()) == Some(&*content) {
+                    pieces.push_math(range, content_range);
+                }
+            }
+            _ => {}
+        }
+    }
+    pieces.finish(text.len())
+}
+
+/// The pieces of a Markdown document split so far.
+struct Pieces<'a> {
+    /// The whole document.
+    text: &'a str,
+    pieces: Vec<Piece>,
+    /// The end of the last piece.
+    end: usize,
+    /// The current run of contiguous text, which has not been added yet.
+    run: Option<Range<usize>>,
+}
+
+impl Pieces<'_> {
+    /// Add text, extending the current run if it is contiguous.
+    fn extend_run(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        match &mut self.run {
+            Some(run) if run.end == range.start => run.end = range.end,
+            _ => {
+                self.end_run();
+                self.run = Some(range);
+            }
+        }
+    }
+
+    /// Add the current run of text, if any, leaving any URLs in it unchanged.
+    fn end_run(&mut self) {
+        let Some(mut run) = self.run.take() else {
+            return;
+        };
+        while let Some(url) = find_url(&self.text[run.clone()]) {
+            let url = run.start + url.start..run.start + url.end;
+            self.pieces.push(Piece::Prose(self.end..run.start));
+            self.pieces.push(Piece::Text(run.start..url.start));
+            self.end = url.start;
+            run.start = url.end;
+        }
+        self.pieces.push(Piece::Prose(self.end..run.start));
+        self.end = run.end;
+        self.pieces.push(Piece::Text(run));
+    }
+
+    /// Add math and its delimiters.
+    fn push_math(&mut self, range: Range<usize>, content: Range<usize>) {
+        self.end_run();
+        self.pieces.push(Piece::Prose(self.end..range.start));
+        self.pieces
+            .push(Piece::Delimiter(range.start..content.start));
+        self.pieces.push(Piece::Math(content.clone()));
+        self.pieces.push(Piece::Delimiter(content.end..range.end));
+        self.end = range.end;
+    }
+
+    /// Add the rest of the document.
+    fn finish(mut self, len: usize) -> Vec<Piece> {
+        self.end_run();
+        self.pieces.push(Piece::Prose(self.end..len));
+        self.pieces.retain(
+            |piece| !matches!(piece, Piece::Prose(range) | Piece::Text(range) if range.is_empty()),
+        );
+        self.pieces
+    }
+}
+
+/// Determine if the character at the index is escaped by a backslash.
+///
+/// Only ASCII punctuation can be escaped in Markdown.
+fn is_escaped(text: &str, index: usize) -> bool {
+    let bytes = text.as_bytes();
+    index > 0 && bytes[index - 1] == b'\\' && bytes.get(index).is_some_and(u8::is_ascii_punctuation)
+}
+
+/// Find the first URL like `https://example.com/a_1` in the text.
+///
+/// A URL starts with a scheme followed by `://`, and ends before the next whitespace or `<`.
+fn find_url(text: &str) -> Option<Range<usize>> {
+    let bytes = text.as_bytes();
+    let mut from = 0;
+    while let Some(index) = text[from..].find("://").map(|index| from + index) {
+        let scheme_start = bytes[..index]
+            .iter()
+            .rposition(|&b| !(b.is_ascii_alphanumeric() || matches!(b, b'+' | b'-' | b'.')))
+            .map_or(0, |i| i + 1);
+        // a scheme must start with a letter
+        let scheme_start = (scheme_start..index).find(|&i| bytes[i].is_ascii_alphabetic());
+        if let Some(start) = scheme_start {
+            let end = bytes[index..]
+                .iter()
+                .position(|&b| b.is_ascii_whitespace() || b == b'<')
+                .map_or(text.len(), |len| index + len);
+            return Some(start..end);
+        }
+        from = index + 3;
+    }
+    None
+}
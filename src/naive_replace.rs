@@ -11,6 +11,8 @@ use std::sync::LazyLock;
 ///
 /// The behavior of this function should exactly match the behavior
 /// of the original library, but requires the `regex` crate to opperate.
+/// It differs from [`crate::replace`] where the original produces broken output,
+/// as listed in its documentation.
 /// In addition to the cost of using `regex`,
 /// each invocation requires several thousand reallocations of the input string.
 pub fn replace(text: &str) -> String {
@@ -19,7 +21,7 @@ pub fn replace(text: &str) -> String {
     let mut text = {
         static REGEX: LazyLock<Regex> =
             LazyLock::new(|| Regex::new(r##"\\not(\\[A-z]+)"##).unwrap());
-        REGEX.replace_all(text, r#"\slash{$1}""#).into_owned()
+        REGEX.replace_all(text, r"\slash{$1}").into_owned()
     };
     // escape combining marks with a space after the backslash
     for &(key, _val) in crate::data::COMBINING_MARKS {
@@ -43,31 +45,17 @@ pub fn replace(text: &str) -> String {
         replace_char: char,
     ) -> String {
         assert!(matches!(replace_char, '^' | '_'));
-        let mut offset = 0isize;
-        let mut text = orig_text.to_string();
-        for s in find_regex.find_iter(orig_text) {
-            let mut count = 0usize;
-            let target_text = s.as_str();
-            let new_string = sub_regex.replace_all(
-                &target_text[2..target_text.len() - 1],
-                |c: &regex::Captures| {
-                    count += 1;
-                    format!("{replace_char}{}", &c[0])
-                },
-            );
-            // f = f[:s.start() + offset] + newstring + f[s.end() + offset:]
-            let mut buffer = String::with_capacity(text.len() + new_string.len());
-            {
-                let offset = usize::try_from(offset).unwrap();
-                buffer.push_str(&text[..s.start() + offset]);
-                buffer.push_str(&new_string);
-                buffer.push_str(&text[s.end() + offset..]);
-            }
-            text = buffer;
-            let count = isize::try_from(count).unwrap();
-            offset += (count * 2) - (count + 3);
-        }
-        text
+        find_regex
+            .replace_all(orig_text, |s: &regex::Captures| {
+                let target_text = &s[0];
+                sub_regex
+                    .replace_all(
+                        &target_text[2..target_text.len() - 1],
+                        |c: &regex::Captures| format!("{replace_char}{}", &c[0]),
+                    )
+                    .into_owned()
+            })
+            .into_owned()
     }
     // expand groups of subscripts: \_{01234}
     {
@@ -111,31 +99,33 @@ pub fn replace(text: &str) -> String {
     // process combining marks first
     for &(key, val) in crate::data::COMBINING_MARKS {
         let escaped_latex = format!("\\ {}{{", &key[1..]);
-        while let Some(find_index) = text.find(&escaped_latex) {
-            if text.len() <= find_index + escaped_latex.len() {
+        if !text.contains(&escaped_latex) {
+            continue;
+        }
+        // Python searches from the start of the text after each replacement,
+        // but a replacement can never create an earlier match,
+        // so a single pass gives the same result in linear time.
+        let mut combined = String::with_capacity(text.len());
+        let mut rest = text.as_str();
+        while let Some(find_index) = rest.find(&escaped_latex) {
+            combined.push_str(&rest[..find_index]);
+            // like python, the combined character and the following one
+            // (normally the closing brace) are counted in code points
+            let mut argument = rest[find_index + escaped_latex.len()..].chars();
+            let Some(combined_char) = argument.next() else {
                 // incomplete: unescape and continue
-                text.truncate(find_index);
-                text.push_str(key);
-                text.push('{');
-                continue;
-            }
-            let combined_char = text[find_index + escaped_latex.len()..]
-                .chars()
-                .next()
-                .unwrap();
-
-            let char_offset = combined_char.len_utf8() + 1;
-            let remainder = if text.len() >= find_index + escaped_latex.len() + char_offset {
-                text[find_index + escaped_latex.len() + char_offset..].to_string()
-            } else {
-                String::new()
+                combined.push_str(key);
+                combined.push('{');
+                rest = "";
+                break;
             };
-
-            text.truncate(find_index);
-            text.push(combined_char);
-            text.push_str(val);
-            text.push_str(&remainder);
+            argument.next();
+            combined.push(combined_char);
+            combined.push_str(val);
+            rest = argument.as_str();
         }
+        combined.push_str(rest);
+        text = combined;
     }
 
     text
@@ -0,0 +1,32 @@
+//! Normalizing the output of combining marks, like `\acute{e}`.
+
+use unicode_normalization::UnicodeNormalization;
+
+/// The Unicode normalization form of text with combining marks applied,
+/// used by [`ConverterBuilder::normalization`](crate::ConverterBuilder::normalization).
+///
+/// Only the output of combining marks and negations like `\not\in` is normalized,
+/// so symbols like `\Angstrom` or `\nexists` are never composed or decomposed.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Normalization {
+    /// Leave the marks after their base, like the original library.
+    #[default]
+    None,
+    /// Compose the marks with their base where a precomposed character exists (NFC),
+    /// so `\acute{e}` becomes `é` and `\not\in` becomes `∉`.
+    Nfc,
+    /// Decompose any precomposed base into separate marks (NFD),
+    /// so `\hat{é}` becomes `e` followed by `\u{301}\u{302}`.
+    Nfd,
+}
+
+impl Normalization {
+    /// Normalize text with combining marks applied.
+    pub(crate) fn apply(self, text: &str) -> String {
+        match self {
+            Normalization::None => text.into(),
+            Normalization::Nfc => text.nfc().collect(),
+            Normalization::Nfd => text.nfd().collect(),
+        }
+    }
+}
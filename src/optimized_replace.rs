@@ -1,288 +1,1048 @@
-//! The core crate logic.
-
-use aho_corasick::{AhoCorasick, MatchKind};
-use std::sync::OnceLock;
-
-fn replace_with<'a, M, R>(
-    original_text: &'a str,
-    potential_matches: impl Iterator<Item = M>,
-    mut initial_match_index: impl FnMut(&M) -> usize,
-    mut func: impl FnMut(&M, &'a str) -> Option<(usize, R)>,
-    mut do_replace: impl FnMut(R, &mut String),
-) -> String {
-    let mut buffer = String::new();
-    let mut last_index = 0usize; // last index of source text handled
-    for potential_match in potential_matches {
-        let match_index = initial_match_index(&potential_match);
-        if match_index < last_index {
-            continue;
-        }
-        let remaining_text = &original_text[match_index..];
-        match func(&potential_match, remaining_text) {
-            Some((matched_length, replacement)) => {
-                buffer.push_str(&original_text[last_index..match_index]);
-                do_replace(replacement, &mut buffer);
-                last_index = match_index + matched_length;
-            }
-            None => {
-                // just skip this match
-            }
-        }
-    }
-    buffer.push_str(&original_text[last_index..]);
-    buffer
-}
+//! A single-pass implementation of [`crate::replace`].
+//!
+//! Unlike the original library, which rewrites the whole text once per phase,
+//! this scans the input once, writing the output as it goes.
+//! Each position is only examined a bounded number of times,
+//! so conversion always takes linear time.
+
+#[cfg(feature = "normalization")]
+use crate::Normalization;
+use crate::scripts::{ScriptFallback, ScriptKind};
+use aho_corasick::{AhoCorasick, Anchored, Input, MatchKind, StartKind};
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use core::ops::Range;
+use unicode_segmentation::{GraphemeCursor, GraphemeIncomplete, UnicodeSegmentation};
+
+pub(crate) mod tokens;
+mod tree;
 
 /// An optimized implementation of the [`crate::replace`] function
 /// that avoids frequent reallocation and regular expressions.
 ///
-/// Currently, this implementation may be inconsistent with the python implementation
-/// in some cases. For this reason, this is not the default implementation of the replace function.
+/// This is the default implementation of the replace function.
 pub fn replace(text: &str) -> String {
-    // Catch cases like \not\subset and \not\in and convert them to
-    // use the combining character slash as in \slash{\subset}
-    let mut text: String = {
-        // original code: re.sub(r'\\not(\\[A-z]+)', r'\\slash{\1}', f)
-        const SEARCH_STR: &str = r#"\not\"#;
-        replace_with(
-            text,
-            memchr::memmem::find_iter(text.as_bytes(), SEARCH_STR),
-            |&index| index,
-            |&_not_index, remaining_text| {
-                debug_assert!(remaining_text.starts_with(SEARCH_STR));
-                let possible_command_text = &remaining_text[SEARCH_STR.len()..];
-                let command_len = possible_command_text
-                    .find(|c: char| !c.is_ascii_alphabetic())
-                    .unwrap_or(possible_command_text.len());
-                if command_len > 0 {
-                    Some((
-                        command_len + SEARCH_STR.len(),
-                        &possible_command_text[..command_len],
-                    ))
-                } else {
-                    None
-                }
-            },
-            |target_command, buffer| {
-                buffer.push_str("\\slash{");
-                buffer.push_str(target_command);
-                buffer.push('}');
-            },
-        )
-    };
-
-    // escape combining marks with a space after the backslash
-    text = {
-        static COMBINING_MARKS_INITIAL_SEARCH: OnceLock<AhoCorasick> = OnceLock::new();
-        replace_with(
-            &text,
-            COMBINING_MARKS_INITIAL_SEARCH
-                .get_or_init(|| {
-                    AhoCorasick::builder()
-                        .match_kind(MatchKind::LeftmostFirst)
-                        .build(
-                            crate::data::COMBINING_MARKS
-                                .iter()
-                                .map(|(mark, _)| format!("{mark}{{")),
-                        )
-                        .unwrap()
-                })
-                .find_iter(&text),
-            |m| m.start(),
-            |m, _remaining_text| Some((m.len(), m.pattern().as_usize())),
-            |mark_index, buffer| {
-                buffer.push_str("\\ ");
-                buffer.push_str(&crate::data::COMBINING_MARKS[mark_index].0[1..]);
-                buffer.push('{');
-            },
-        )
-    };
-
-    // replace
-    text = {
-        static REPLACEMENTS_SEARCH: OnceLock<AhoCorasick> = OnceLock::new();
-
-        replace_with(
-            &text,
-            REPLACEMENTS_SEARCH
-                .get_or_init(|| {
-                    AhoCorasick::builder()
-                        .match_kind(MatchKind::LeftmostFirst)
-                        .build(crate::data::REPLACEMENTS.iter().map(|(key, _)| key))
-                        .unwrap()
-                })
-                .find_iter(&text),
-            |m| m.start(),
-            |m, _| Some((m.len(), m.pattern().as_usize())),
-            |replacement_index, buffer| {
-                buffer.push_str(crate::data::REPLACEMENTS[replacement_index].1);
-            },
-        )
-    };
-
-    // this is done in a second pass after primary replacements, unlike in python port
-    // original comment: `check whether it was escaped for combining marks but has empty braces`
-    text = {
-        static REPLACEMENTS_WITH_BRACKET: OnceLock<AhoCorasick> = OnceLock::new();
-        replace_with(
-            &text,
-            REPLACEMENTS_WITH_BRACKET
-                .get_or_init(|| {
-                    AhoCorasick::builder()
-                        .match_kind(MatchKind::LeftmostFirst)
-                        .build(
-                            crate::data::REPLACEMENTS_WITH_BRACKET_SUFFIX
-                                .iter()
-                                .map(|&(key, _)| format!("\\ {}", &key[1..])),
-                        )
-                        .unwrap()
+    default_engine().replace(text)
+}
+
+/// The engine using the built-in tables.
+#[cfg(feature = "std")]
+#[inline]
+pub(crate) fn default_engine() -> &'static Engine {
+    &crate::converter::DEFAULT_CONVERTER.engine
+}
+
+/// The engine using the built-in tables.
+#[cfg(not(feature = "std"))]
+pub(crate) fn default_engine() -> &'static Engine {
+    use alloc::boxed::Box;
+    use once_cell::race::OnceBox;
+
+    static DEFAULT_ENGINE: OnceBox<Engine> = OnceBox::new();
+    DEFAULT_ENGINE.get_or_init(|| {
+        fn entries(
+            table: &'static [(&'static str, &'static str)],
+        ) -> impl Iterator<Item = (&'static str, Cow<'static, str>)> {
+            table
+                .iter()
+                .map(|&(latex, unicode)| (latex, Cow::Borrowed(unicode)))
+        }
+        Box::new(Engine::new(
+            entries(crate::data::REPLACEMENTS),
+            entries(crate::data::COMBINING_MARKS),
+            entries(crate::data::SUB_SUPER_SCRIPTS),
+        ))
+    })
+}
+
+/// A table of keys, which are matched at a specific position in the text.
+#[derive(Clone, Debug)]
+struct Matcher {
+    automaton: AhoCorasick,
+    values: Vec<Cow<'static, str>>,
+}
+
+impl Matcher {
+    fn new<K: AsRef<str>>(entries: Vec<(K, Cow<'static, str>)>) -> Self {
+        let (keys, values): (Vec<K>, Vec<_>) = entries.into_iter().unzip();
+        let automaton = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .start_kind(StartKind::Anchored)
+            .build(keys.iter().map(K::as_ref))
+            .unwrap();
+        Matcher { automaton, values }
+    }
+
+    /// Find the longest key starting at the index,
+    /// returning the index of the entry and the end of the match.
+    ///
+    /// If several keys are equal, the first one is used.
+    #[inline]
+    fn find(&self, text: &str, index: usize) -> Option<(usize, usize)> {
+        let input = Input::new(text).range(index..).anchored(Anchored::Yes);
+        let found = self.automaton.find(input)?;
+        Some((found.pattern().as_usize(), found.end()))
+    }
+}
+
+/// A unit of text which can be part of a script, like `2` or `\beta`.
+#[derive(Copy, Clone)]
+enum Unit {
+    Replacement(usize),
+    Char(char),
+}
+
+/// A piece of text which is converted as a whole.
+enum Token {
+    /// A replacement like `\alpha`.
+    Replacement(usize),
+    /// A command negated by `\not`, like `\not\in`.
+    Not(usize),
+    /// A single script like `x^2`,
+    /// replacing the first character of the unit.
+    Script { script: usize, unit: Unit },
+    /// A group of scripts like `x^{12}`, expanded as if each unit was a separate script.
+    Group {
+        kind: ScriptKind,
+        content: Range<usize>,
+    },
+    /// A group of scripts like `x^{2Q}` with a character which has no script,
+    /// converted using the [fallback](ScriptFallback):
+    /// the units of the prefix are expanded, and the rest is left in braces or parentheses.
+    Fallback {
+        kind: ScriptKind,
+        prefix: Range<usize>,
+        rest: Range<usize>,
+    },
+    /// A character which is left unchanged.
+    Char(char),
+    /// The argument of a combining mark like `ab` in `\hat{ab}` or `e\u{301}` in `\hat{e\u{301}}`,
+    /// converted as a sequence of tokens with the marks applied to every grapheme cluster.
+    Argument { content: Range<usize> },
+}
+
+/// The conversion of text starting with a combining mark like `\hat{`.
+enum Combining {
+    /// The combining marks are left unchanged,
+    /// and conversion continues after them.
+    Unchanged(usize),
+    /// The innermost combining marks are applied to their argument.
+    ///
+    /// Any outer marks which are not closed
+    /// directly after the argument are left unchanged.
+    Combined {
+        /// The start of the combined marks.
+        start: usize,
+        /// The end of the combined marks, and the start of the argument.
+        argument: usize,
+        token: Token,
+        /// The end of the argument, and the start of the closing braces.
+        token_end: usize,
+        /// The end of the closing braces.
+        end: usize,
+    },
+}
+
+/// The result of [`Engine::step`].
+enum Step {
+    /// The text is left unchanged up to the index.
+    Skip(usize),
+    /// A token, with the openers and closing braces of any combining marks around it.
+    Token {
+        token: Token,
+        start: usize,
+        openers: Range<usize>,
+        closers: Range<usize>,
+    },
+    /// There is no more text.
+    End,
+}
+
+/// Counts the length of the output written so far.
+struct Output<'a, W: ?Sized> {
+    out: &'a mut W,
+    len: usize,
+    /// The maximum length of the output.
+    limit: usize,
+}
+
+impl<'a, W: Write + ?Sized> Output<'a, W> {
+    fn new(out: &'a mut W) -> Self {
+        Output {
+            out,
+            len: 0,
+            limit: usize::MAX,
+        }
+    }
+
+    /// Determine if more output of the length fits within the limit.
+    #[inline]
+    fn fits(&self, len: usize) -> bool {
+        len <= self.limit - self.len
+    }
+
+    /// Copy the unchanged text, or as much of it as fits within the limit,
+    /// returning where it was cut off.
+    fn copy(&mut self, text: &str, range: Range<usize>) -> Result<Option<usize>, fmt::Error> {
+        if self.fits(range.len()) {
+            self.write_str(&text[range])?;
+            return Ok(None);
+        }
+        let mut end = range.start + (self.limit - self.len);
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.write_str(&text[range.start..end])?;
+        Ok(Some(end))
+    }
+}
+
+impl<W: Write + ?Sized> Write for Output<'_, W> {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.len += s.len();
+        self.out.write_str(s)
+    }
+}
+
+/// Measures the length of the output, without writing it.
+struct Length(usize);
+
+impl Write for Length {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.len();
+        Ok(())
+    }
+}
+
+/// The longest part of the output kept by a [`Window`].
+const WINDOW_LEN: usize = 32;
+
+/// Keeps the part of the output within a range of offsets, without writing the rest,
+/// so the end of a long output can be read without allocating.
+struct Window {
+    range: Range<usize>,
+    /// The length of the output written so far.
+    len: usize,
+    bytes: [u8; WINDOW_LEN],
+}
+
+impl Window {
+    /// Keep the output up to the end, as far back as fits.
+    fn ending_at(end: usize) -> Self {
+        Window {
+            range: end.saturating_sub(WINDOW_LEN)..end,
+            len: 0,
+            bytes: [0; WINDOW_LEN],
+        }
+    }
+
+    /// The kept output and its offset, after any character cut off at the start.
+    fn chunk(&self) -> (usize, &str) {
+        let bytes = &self.bytes[..self.range.len()];
+        let cut_off = bytes.iter().take_while(|&&b| b & 0xc0 == 0x80).count();
+        let chunk = core::str::from_utf8(&bytes[cut_off..]).expect("kept whole characters");
+        (self.range.start + cut_off, chunk)
+    }
+}
+
+impl Write for Window {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let start = self.len;
+        self.len += s.len();
+        let from = start.max(self.range.start);
+        let to = self.len.min(self.range.end);
+        if from < to {
+            self.bytes[from - self.range.start..to - self.range.start]
+                .copy_from_slice(&s.as_bytes()[from - start..to - start]);
+        }
+        Ok(())
+    }
+}
+
+/// The single-pass conversion engine.
+#[derive(Clone, Debug)]
+pub(crate) struct Engine {
+    replacements: Matcher,
+    /// Combining marks, with keys like `\hat{`.
+    combining_marks: Matcher,
+    /// The control character, the base character, and the script.
+    scripts: Vec<(char, char, Cow<'static, str>)>,
+    /// The index of the `\slash` mark, used to rewrite `\not`.
+    slash: Option<usize>,
+    /// Expand groups of scripts like `x^{12}`.
+    script_groups: bool,
+    /// How to convert groups of scripts which cannot be expanded.
+    script_fallback: ScriptFallback,
+    /// Apply combining marks to every character of their argument, like `\hat{ab}`.
+    combining_groups: bool,
+    /// Convert by walking the syntax tree of the input.
+    parse_latex: bool,
+    /// The normalization of the output of combining marks.
+    #[cfg(feature = "normalization")]
+    normalization: Normalization,
+    /// The bytes which can start a token.
+    triggers: [bool; 256],
+}
+
+impl Engine {
+    /// Create an engine with the specified tables.
+    ///
+    /// Entries earlier in a table take priority over later ones with the same key.
+    pub(crate) fn new<K: AsRef<str>>(
+        replacements: impl IntoIterator<Item = (K, Cow<'static, str>)>,
+        combining_marks: impl IntoIterator<Item = (K, Cow<'static, str>)>,
+        sub_super_scripts: impl IntoIterator<Item = (K, Cow<'static, str>)>,
+    ) -> Self {
+        let mut triggers = [false; 256];
+        for byte in [b'\\', b'^', b'_'] {
+            triggers[byte as usize] = true;
+        }
+        let replacements: Vec<_> = replacements
+            .into_iter()
+            .filter(|(latex, _)| !latex.as_ref().is_empty())
+            .inspect(|(latex, _)| triggers[latex.as_ref().as_bytes()[0] as usize] = true)
+            .collect();
+        let combining_marks: Vec<_> = combining_marks
+            .into_iter()
+            .filter(|(latex, _)| {
+                latex.as_ref().strip_prefix('\\').is_some_and(|name| {
+                    !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphabetic())
                 })
-                .find_iter(&text),
-            |m| m.start(),
-            |m, _remaining_text| Some((m.len(), m.pattern().as_usize())),
-            |bracket_replacement_index, buffer| {
-                buffer.push_str(
-                    crate::data::REPLACEMENTS_WITH_BRACKET_SUFFIX[bracket_replacement_index].1,
-                );
-            },
-        )
-    };
+            })
+            .map(|(latex, mark)| (format!("{}{{", latex.as_ref()), mark))
+            .collect();
+        let slash = combining_marks
+            .iter()
+            .position(|(latex, _)| latex == r"\slash{");
+        let mut scripts: Vec<_> = sub_super_scripts
+            .into_iter()
+            .filter_map(|(latex, script)| {
+                let mut chars = latex.as_ref().chars();
+                match (chars.next(), chars.next(), chars.next()) {
+                    (Some(control @ ('_' | '^')), Some(base), None) => {
+                        Some((control, base, script))
+                    }
+                    _ => None,
+                }
+            })
+            .collect();
+        // stable, so the first entry with each key is kept
+        scripts.sort_by_key(|&(control, base, _)| (control, base));
+        scripts.dedup_by_key(|&mut (control, base, _)| (control, base));
+        Engine {
+            replacements: Matcher::new(replacements),
+            combining_marks: Matcher::new(combining_marks),
+            scripts,
+            slash,
+            script_groups: true,
+            script_fallback: ScriptFallback::Unchanged,
+            combining_groups: false,
+            parse_latex: false,
+            #[cfg(feature = "normalization")]
+            normalization: Normalization::None,
+            triggers,
+        }
+    }
+
+    /// Rewrite `\not\in` as `\slash{\in}`, if there is a `\slash` combining mark.
+    ///
+    /// Enabled by default.
+    #[cfg(feature = "std")]
+    pub(crate) fn not_rewrite(mut self, enabled: bool) -> Self {
+        if !enabled {
+            self.slash = None;
+        }
+        self
+    }
+
+    /// Expand groups of scripts like `x^{12}`.
+    ///
+    /// Enabled by default.
+    #[cfg(feature = "std")]
+    pub(crate) fn script_groups(mut self, enabled: bool) -> Self {
+        self.script_groups = enabled;
+        self
+    }
+
+    /// Convert groups of scripts which cannot be expanded, like `x^{Q}`.
+    ///
+    /// These are left unchanged by default.
+    #[cfg(feature = "std")]
+    pub(crate) fn script_fallback(mut self, fallback: ScriptFallback) -> Self {
+        self.script_fallback = fallback;
+        self
+    }
+
+    /// Apply combining marks to every character of their argument, like `\hat{ab}`,
+    /// instead of only to an argument which is a single token.
+    ///
+    /// Disabled by default.
+    #[cfg(feature = "std")]
+    pub(crate) fn combining_groups(mut self, enabled: bool) -> Self {
+        self.combining_groups = enabled;
+        self
+    }
+
+    /// Convert by walking the syntax tree of the input,
+    /// instead of matching each command in the text.
+    ///
+    /// Disabled by default.
+    #[cfg(feature = "std")]
+    pub(crate) fn parse_latex(mut self, enabled: bool) -> Self {
+        self.parse_latex = enabled;
+        self
+    }
+
+    /// Normalize the output of combining marks.
+    ///
+    /// The marks are left after their base by default.
+    #[cfg(feature = "normalization")]
+    pub(crate) fn normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    /// Normalize text with combining marks applied, if enabled.
+    fn normalize<'a>(&self, combined: &'a str) -> Cow<'a, str> {
+        #[cfg(feature = "normalization")]
+        if self.normalization != Normalization::None {
+            return Cow::Owned(self.normalization.apply(combined));
+        }
+        Cow::Borrowed(combined)
+    }
+
+    /// Determine if the text has nothing which could be converted.
+    #[inline]
+    pub(crate) fn is_unchanged(&self, text: &str) -> bool {
+        !text.bytes().any(|b| self.triggers[b as usize])
+    }
+
+    /// Convert the text.
+    pub(crate) fn replace(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        self.replace_to_string(text, &mut out);
+        out
+    }
+
+    /// Convert the text, borrowing it if nothing could be converted.
+    pub(crate) fn replace_cow<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if self.is_unchanged(text) {
+            Cow::Borrowed(text)
+        } else {
+            Cow::Owned(self.replace(text))
+        }
+    }
+
+    /// Convert the text, appending it to the string.
+    pub(crate) fn replace_to_string(&self, text: &str, out: &mut String) {
+        self.replace_into(text, out)
+            .expect("writing to a string cannot fail");
+    }
+
+    /// Convert the text, writing it to the output.
+    pub(crate) fn replace_into<W: Write + ?Sized>(&self, text: &str, out: &mut W) -> fmt::Result {
+        self.convert(text, &mut Output::new(out), None)?;
+        Ok(())
+    }
+
+    /// Convert the text, appending it to the string,
+    /// and recording each changed range of the text with the length of its output.
+    ///
+    /// If the output would be longer than `max_len`,
+    /// the conversion stops and the rest of the text is dropped,
+    /// returning where the text was cut off.
+    #[cfg(feature = "std")]
+    pub(crate) fn replace_with_edits(
+        &self,
+        text: &str,
+        out: &mut String,
+        edits: &mut Vec<(Range<usize>, usize)>,
+        max_len: Option<usize>,
+    ) -> Option<usize> {
+        let mut output = Output::new(out);
+        if let Some(max_len) = max_len {
+            output.limit = max_len;
+        }
+        self.convert(text, &mut output, Some(edits))
+            .expect("writing to a string cannot fail")
+    }
 
-    fn do_sub_super_group_expansion(
+    /// Convert the text, returning where it was cut off
+    /// if the output reached its limit.
+    fn convert<W: Write + ?Sized>(
+        &self,
         text: &str,
-        group_start: &str,
-        is_target_char: impl Fn(char) -> bool,
-    ) -> String {
-        assert_eq!(group_start.len(), 2);
-        assert!(group_start.ends_with("{"));
-        let control_char = group_start.as_bytes()[0] as char;
-        assert!(matches!(control_char, '^' | '_'));
-
-        // original code takes f_{34a5} -> f_3_4_a_5
-        replace_with(
-            text,
-            memchr::memmem::find_iter(text.as_bytes(), group_start),
-            |&index| index,
-            |&_start_index, remaining_text| {
-                let end_index = memchr::memchr(b'}', remaining_text.as_bytes())?;
-                let target_text = &remaining_text[2..end_index];
-                if target_text.chars().all(&is_target_char) {
-                    Some((target_text.len() + 3, target_text))
-                } else {
-                    None
+        out: &mut Output<'_, W>,
+        mut edits: Option<&mut Vec<(Range<usize>, usize)>>,
+    ) -> Result<Option<usize>, fmt::Error> {
+        if self.parse_latex {
+            return self.convert_tree(text, out, edits);
+        }
+        let bytes = text.as_bytes();
+        // the end of the text which has already been written
+        let mut written = 0;
+        let mut index = 0;
+        let mut cut_off = None;
+        while index < bytes.len() {
+            // keys never start with a continuation byte,
+            // so this is always the start of a character
+            if !self.triggers[bytes[index] as usize] {
+                index += 1;
+                continue;
+            }
+            let (token, start, openers, closers) = match self.step(text, index) {
+                Step::Skip(end) => {
+                    index = end;
+                    continue;
                 }
-            },
-            |target_text, buffer| {
-                for c in target_text.chars() {
-                    buffer.push(control_char);
-                    buffer.push(c);
+                Step::Token {
+                    token,
+                    start,
+                    openers,
+                    closers,
+                } => (token, start, openers, closers),
+                Step::End => break,
+            };
+            cut_off = out.copy(text, written..start)?;
+            if cut_off.is_some() {
+                break;
+            }
+            if out.limit != usize::MAX {
+                let mut length = Length(0);
+                self.write_combined(text, &token, openers.clone(), closers.len(), &mut length)?;
+                if !out.fits(length.0) {
+                    cut_off = Some(start);
+                    break;
                 }
+            }
+            let output_start = out.len;
+            self.write_combined(text, &token, openers, closers.len(), out)?;
+            if let Some(edits) = edits.as_deref_mut() {
+                edits.push((start..closers.end, out.len - output_start));
+            }
+            written = closers.end;
+            index = closers.end;
+        }
+        if cut_off.is_none() {
+            cut_off = out.copy(text, written..text.len())?;
+        }
+        if let Some(cut_off) = cut_off
+            && let Some(edits) = edits
+        {
+            edits.push((cut_off..text.len(), 0));
+        }
+        Ok(cut_off)
+    }
+
+    /// Find the next token, starting at a character which can start one.
+    fn step(&self, text: &str, index: usize) -> Step {
+        if text.as_bytes()[index] == b'\\'
+            && let Some(combining) = self.combining(text, index)
+        {
+            return match combining {
+                Combining::Unchanged(end) => Step::Skip(end),
+                Combining::Combined {
+                    start,
+                    argument,
+                    token,
+                    token_end,
+                    end,
+                } => Step::Token {
+                    token,
+                    start,
+                    openers: start..argument,
+                    closers: token_end..end,
+                },
+            };
+        }
+        match self.token(text, index) {
+            Some((Token::Char(c), _)) => Step::Skip(index + c.len_utf8()),
+            Some((token, end)) => Step::Token {
+                token,
+                start: index,
+                openers: index..index,
+                closers: end..end,
             },
-        )
-    }
-
-    // expand groups of subscripts: \_{01234}
-    text = do_sub_super_group_expansion(&text, "_{", |c: char| {
-        matches!(
-            c,
-            '0'..='9' | '\u{03B2}' | '\u{03B3}' | '\u{03C1}' | '\u{03C6}' | '\u{03C7}' | '\u{2212}'
-        ) || (c.is_ascii() && memchr::memchr(c as u8, b"+-=()<>-aeoxjhklmnpstiruv").is_some())
-    });
-
-    // expand groups of superscripts: \^{01234}
-    text = do_sub_super_group_expansion(&text, "^{", |c: char| {
-        matches!(
-            c,
-            '0'..='9'
-                | '\u{03B2}'
-                | '\u{03B3}'
-                | '\u{03B4}'
-                | '\u{03C6}'
-                | '\u{03C7}'
-                | '\u{222B}'
-                | '\u{2212}'
-        ) || (c.is_ascii()
-            && memchr::memchr(
-                c as u8,
-                b"+-=()<>ABDEGHIJKLMNOPRTUWabcdefghijklmnoprstuvwxyz",
-            )
-            .is_some())
-    });
-
-    // now replace subsuperscripts
-    {
-        static SUBSUPERSCRIPT_SEARCH: OnceLock<AhoCorasick> = OnceLock::new();
-
-        text = replace_with(
-            &text,
-            SUBSUPERSCRIPT_SEARCH
-                .get_or_init(|| {
-                    AhoCorasick::builder()
-                        .match_kind(MatchKind::LeftmostFirst)
-                        .build(crate::data::SUB_SUPER_SCRIPTS.iter().map(|(key, _)| key))
-                        .unwrap()
-                })
-                .find_iter(&text),
-            |m| m.start(),
-            |m, _| Some((m.len(), m.pattern().as_usize())),
-            |replacement_index, buffer| {
-                buffer.push_str(crate::data::SUB_SUPER_SCRIPTS[replacement_index].1);
-            },
-        )
-    }
-
-    // process combining marks first
-    // differs from the other operations as we process all replacements of a single type
-    // before moving on to the next one
-    {
-        assert_eq!(
-            crate::data::COMBINING_MARKS.len(),
-            crate::data::COMBINING_MARKS_ESCAPED_LATEX.len()
-        );
-        static COMBINING_MARKS_ESCAPED_LATEX_SEARCH: OnceLock<AhoCorasick> = OnceLock::new();
-        let escaped_latex_search = COMBINING_MARKS_ESCAPED_LATEX_SEARCH.get_or_init(|| {
-            AhoCorasick::builder()
-                .match_kind(MatchKind::LeftmostFirst)
-                .build(crate::data::COMBINING_MARKS_ESCAPED_LATEX.iter().copied())
-                .unwrap()
-        });
-        let mut replace_buffer = String::with_capacity(16);
-        while let Some(initial_find) = escaped_latex_search.find(&text) {
-            let (original_command, result_text) =
-                crate::data::COMBINING_MARKS[initial_find.pattern().as_usize()];
-            let escaped_latex =
-                crate::data::COMBINING_MARKS_ESCAPED_LATEX[initial_find.pattern().as_usize()];
-            let finder = memchr::memmem::Finder::new(escaped_latex);
-            while let Some(found_index) = finder.find(text.as_bytes()) {
-                if text.len() <= found_index + escaped_latex.len() {
-                    // incomplete: unescape and continue
-                    text.truncate(found_index);
-                    text.push_str(original_command);
-                    text.push('{');
-                    continue;
+            None => Step::End,
+        }
+    }
+
+    /// Find the combining mark like `\hat{` at the index,
+    /// returning the index of the mark and the end of the brace.
+    ///
+    /// A combining mark with empty braces like `\breve{}` is a replacement instead.
+    fn opener(&self, text: &str, index: usize) -> Option<(usize, usize)> {
+        let (mark, end) = self.combining_marks.find(text, index)?;
+        match self.replacements.find(text, index) {
+            Some((_, replacement_end))
+                if replacement_end == end + 1 && text[end..].starts_with('}') =>
+            {
+                None
+            }
+            _ => Some((mark, end)),
+        }
+    }
+
+    /// Convert a sequence of combining marks like `\hat{\dot{a}}`.
+    fn combining(&self, text: &str, index: usize) -> Option<Combining> {
+        let (_, mut argument) = self.opener(text, index)?;
+        let mut openers = 1;
+        while let Some((_, end)) = self.opener(text, argument) {
+            argument = end;
+            openers += 1;
+        }
+        let token = match text[argument..].chars().next() {
+            Some('{' | '}') | None => None,
+            Some(_) => self.token(text, argument),
+        };
+        let Some((mut token, mut token_end)) = token else {
+            return Some(Combining::Unchanged(argument));
+        };
+        if !text[token_end..].starts_with('}') {
+            // marks already applied to the argument in the input stay next to it
+            let extension = self.cluster_extension(text, &token, &text[token_end..]);
+            if extension > 0 {
+                token_end += extension;
+                token = Token::Argument {
+                    content: argument..token_end,
+                };
+            }
+        }
+        if self.combining_groups
+            && !text[token_end..].starts_with('}')
+            && let Some(content_end) = self.argument_end(text, token_end)
+        {
+            token = Token::Argument {
+                content: argument..content_end,
+            };
+            token_end = content_end;
+        }
+        let closers = text[token_end..]
+            .bytes()
+            .take(openers)
+            .take_while(|&b| b == b'}')
+            .count();
+        if closers == 0 {
+            return Some(Combining::Unchanged(argument));
+        }
+        // skip the openers which are not closed
+        let mut start = index;
+        for _ in closers..openers {
+            start = self.opener(text, start).map_or(argument, |(_, end)| end);
+        }
+        Some(Combining::Combined {
+            start,
+            argument,
+            token,
+            token_end,
+            end: token_end + closers,
+        })
+    }
+
+    /// Find the closing brace of the argument of a combining mark,
+    /// which is a sequence of tokens not containing any braces or other combining marks.
+    fn argument_end(&self, text: &str, mut index: usize) -> Option<usize> {
+        loop {
+            match text[index..].chars().next()? {
+                '}' => return Some(index),
+                '{' => return None,
+                '\\' if self.opener(text, index).is_some() => return None,
+                _ => index = self.token(text, index)?.1,
+            }
+        }
+    }
+
+    /// Find the token starting at the index,
+    /// which must not be a combining mark.
+    fn token(&self, text: &str, index: usize) -> Option<(Token, usize)> {
+        let c = text[index..].chars().next()?;
+        if let Some((kind, start)) = self.control(text, index)
+            && let Some(script) = self.script(text, start, kind)
+        {
+            return Some(script);
+        }
+        if c == '\\'
+            && let Some(not) = self.not(text, index)
+        {
+            return Some(not);
+        }
+        match self.replacements.find(text, index) {
+            Some((replacement, end)) => Some((Token::Replacement(replacement), end)),
+            None => Some((Token::Char(c), index + c.len_utf8())),
+        }
+    }
+
+    /// Find the unit starting at the index,
+    /// unless it is a combining mark.
+    fn unit(&self, text: &str, index: usize) -> Option<(Unit, usize)> {
+        let c = text[index..].chars().next()?;
+        if c == '\\' && self.opener(text, index).is_some() {
+            return None;
+        }
+        match self.replacements.find(text, index) {
+            Some((replacement, end)) => Some((Unit::Replacement(replacement), end)),
+            None => Some((Unit::Char(c), index + c.len_utf8())),
+        }
+    }
+
+    /// The converted text of the unit.
+    fn unit_str<'a>(&'a self, unit: Unit, buffer: &'a mut [u8; 4]) -> &'a str {
+        match unit {
+            Unit::Replacement(replacement) => &self.replacements.values[replacement],
+            Unit::Char(c) => c.encode_utf8(buffer),
+        }
+    }
+
+    /// Find the script for the control character and the base character.
+    fn find_script(&self, control: char, base: char) -> Option<usize> {
+        self.scripts
+            .binary_search_by_key(&(control, base), |&(control, base, _)| (control, base))
+            .ok()
+    }
+
+    /// Find the unit at the index if it converts to exactly the expected text,
+    /// returning the end of the unit.
+    ///
+    /// Like the original, this allows replacements like `\{` to act as
+    /// the text they produce.
+    fn unit_matches(&self, text: &str, index: usize, expected: char) -> Option<usize> {
+        let (unit, end) = self.unit(text, index)?;
+        let mut buffer = [0; 4];
+        let mut chars = self.unit_str(unit, &mut buffer).chars();
+        (chars.next() == Some(expected) && chars.next().is_none()).then_some(end)
+    }
+
+    /// Find the control character of a script at the index,
+    /// returning the kind of script and the start of its argument.
+    fn control(&self, text: &str, index: usize) -> Option<(ScriptKind, usize)> {
+        [ScriptKind::Superscript, ScriptKind::Subscript]
+            .into_iter()
+            .find_map(|kind| {
+                let end = self.unit_matches(text, index, kind.control_char())?;
+                Some((kind, end))
+            })
+    }
+
+    /// Find the script whose argument starts at the index, like `^2` or `^{12}`.
+    fn script(&self, text: &str, start: usize, kind: ScriptKind) -> Option<(Token, usize)> {
+        if self.script_groups
+            && let Some(content_start) = self.unit_matches(text, start, '{')
+        {
+            if let Some((content_end, end)) = self.group_end(text, content_start, kind) {
+                let content = content_start..content_end;
+                return Some((Token::Group { kind, content }, end));
+            }
+            if let Some(fallback) = self.fallback(text, content_start, kind) {
+                return Some(fallback);
+            }
+        }
+        let (unit, end) = self.unit(text, start)?;
+        let base = self.unit_str(unit, &mut [0; 4]).chars().next()?;
+        let script = self.find_script(kind.control_char(), base)?;
+        Some((Token::Script { script, unit }, end))
+    }
+
+    /// Find the closing brace of a group of scripts,
+    /// if the group can be expanded,
+    /// returning the start and end of the brace.
+    fn group_end(&self, text: &str, start: usize, kind: ScriptKind) -> Option<(usize, usize)> {
+        let mut index = start;
+        loop {
+            if let Some(end) = self.unit_matches(text, index, '}') {
+                return (index > start).then_some((index, end));
+            }
+            let (unit, end) = self.unit(text, index)?;
+            if !self
+                .unit_str(unit, &mut [0; 4])
+                .chars()
+                .all(|c| self.is_group_char(kind, c))
+            {
+                return None;
+            }
+            index = end;
+        }
+    }
+
+    /// Determine if the character can appear in an expanded group of scripts.
+    ///
+    /// With a fallback, so can any character with a script, like a custom `^Q`.
+    fn is_group_char(&self, kind: ScriptKind, c: char) -> bool {
+        kind.is_group_char(c)
+            || (self.script_fallback != ScriptFallback::Unchanged
+                && self.find_script(kind.control_char(), c).is_some())
+    }
+
+    /// Find a group of scripts starting at the index which cannot be expanded, like `^{2Q}`,
+    /// if it can be converted using the fallback.
+    ///
+    /// The group must not contain braces, other scripts or combining marks.
+    fn fallback(&self, text: &str, start: usize, kind: ScriptKind) -> Option<(Token, usize)> {
+        if !self.script_fallback.converts_groups() {
+            return None;
+        }
+        let mut prefix_end = None;
+        let mut index = start;
+        loop {
+            if let Some(end) = self.unit_matches(text, index, '}') {
+                // an empty group is left unchanged
+                let prefix_end = prefix_end?;
+                let prefix_end = match self.script_fallback {
+                    ScriptFallback::Prefix if prefix_end > start => prefix_end,
+                    ScriptFallback::Prefix => return None,
+                    _ => start,
+                };
+                let prefix = start..prefix_end;
+                let rest = prefix_end..index;
+                return Some((Token::Fallback { kind, prefix, rest }, end));
+            }
+            let (unit, end) = self.unit(text, index)?;
+            let mut buffer = [0; 4];
+            let converted = self.unit_str(unit, &mut buffer);
+            if converted.contains(['{', '^', '_']) {
+                return None;
+            }
+            if prefix_end.is_none() && !converted.chars().all(|c| self.is_group_char(kind, c)) {
+                prefix_end = Some(index);
+            }
+            index = end;
+        }
+    }
+
+    /// Find a negated command like `\not\in`.
+    fn not(&self, text: &str, index: usize) -> Option<(Token, usize)> {
+        self.slash?;
+        let command = index + r"\not".len();
+        let name = text[index..].strip_prefix(r"\not\")?;
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic())
+            || self.opener(text, command).is_some()
+        {
+            return None;
+        }
+        let (replacement, end) = self.replacements.find(text, command)?;
+        Some((Token::Not(replacement), end))
+    }
+
+    /// Write the converted token,
+    /// followed by the marks of the innermost `count` combining openers in the range,
+    /// normalizing the output of the marks if enabled.
+    fn write_combined<W: Write + ?Sized>(
+        &self,
+        text: &str,
+        token: &Token,
+        openers: Range<usize>,
+        count: usize,
+        out: &mut W,
+    ) -> fmt::Result {
+        #[cfg(feature = "normalization")]
+        if self.normalization != Normalization::None
+            && (count > 0 || matches!(token, Token::Not(_)))
+        {
+            let mut combined = String::new();
+            self.write_marked(text, token, openers, count, &mut combined)?;
+            return out.write_str(&self.normalization.apply(&combined));
+        }
+        self.write_marked(text, token, openers, count, out)
+    }
+
+    /// Write the converted token,
+    /// followed by the marks of the innermost `count` combining openers in the range.
+    fn write_marked<W: Write + ?Sized>(
+        &self,
+        text: &str,
+        token: &Token,
+        openers: Range<usize>,
+        count: usize,
+        out: &mut W,
+    ) -> fmt::Result {
+        if let Token::Argument { .. } = token {
+            // the only allocation, since the clusters are only known once converted
+            let mut argument = String::new();
+            self.write_token(text, token, &mut argument)?;
+            for cluster in argument.graphemes(true) {
+                out.write_str(cluster)?;
+                if !cluster.starts_with(char::is_whitespace) {
+                    self.write_marks(text, openers.clone(), count, out)?;
+                }
+            }
+            return Ok(());
+        }
+        self.write_token(text, token, out)?;
+        self.write_marks(text, openers, count, out)
+    }
+
+    /// Find how much of the rest of the input continues the last grapheme cluster
+    /// of the converted token, like the `\u{301}` in `e\u{301}`.
+    ///
+    /// The token is converted again for each part of the output needed as context,
+    /// which is rarely more than its last character.
+    fn cluster_extension(&self, text: &str, token: &Token, rest: &str) -> usize {
+        let window = |end| {
+            let mut window = Window::ending_at(end);
+            self.write_token(text, token, &mut window)
+                .expect("writing to a window cannot fail");
+            window
+        };
+        let start = window(0).len;
+        if start == 0 || rest.is_empty() {
+            return 0;
+        }
+        let mut cursor = GraphemeCursor::new(start, start + rest.len(), true);
+        let provide_context = |cursor: &mut GraphemeCursor, end| {
+            let window = window(end);
+            let (chunk_start, chunk) = window.chunk();
+            cursor.provide_context(chunk, chunk_start);
+        };
+        loop {
+            match cursor.is_boundary(rest, start) {
+                Ok(true) => return 0,
+                Ok(false) => break,
+                Err(GraphemeIncomplete::PreContext(end)) if end <= start => {
+                    provide_context(&mut cursor, end);
+                }
+                Err(_) => return 0,
+            }
+        }
+        loop {
+            match cursor.next_boundary(rest, start) {
+                Ok(end) => return end.map_or(rest.len(), |end| end - start),
+                Err(GraphemeIncomplete::PreContext(end)) if end <= start => {
+                    provide_context(&mut cursor, end);
                 }
-                match text[found_index + escaped_latex.len()..].chars().next() {
+                Err(_) => return rest.len(),
+            }
+        }
+    }
+
+    /// Write the marks of the innermost `count` combining openers in the range.
+    fn write_marks<W: Write + ?Sized>(
+        &self,
+        text: &str,
+        openers: Range<usize>,
+        count: usize,
+        out: &mut W,
+    ) -> fmt::Result {
+        // the innermost mark is applied first
+        let mut opener_end = openers.end;
+        for _ in 0..count {
+            let opener_start = text[..opener_end].rfind('\\').unwrap_or(openers.start);
+            if let Some((mark, _)) = self.combining_marks.find(text, opener_start) {
+                out.write_str(&self.combining_marks.values[mark])?;
+            }
+            opener_end = opener_start;
+        }
+        Ok(())
+    }
+
+    /// Write the converted token.
+    fn write_token<W: Write + ?Sized>(
+        &self,
+        text: &str,
+        token: &Token,
+        out: &mut W,
+    ) -> fmt::Result {
+        match *token {
+            Token::Replacement(replacement) => {
+                out.write_str(&self.replacements.values[replacement])
+            }
+            Token::Not(replacement) => {
+                out.write_str(&self.replacements.values[replacement])?;
+                match self.slash {
+                    Some(slash) => out.write_str(&self.combining_marks.values[slash]),
+                    None => Ok(()),
+                }
+            }
+            Token::Script { script, unit } => {
+                out.write_str(&self.scripts[script].2)?;
+                let mut buffer = [0; 4];
+                let mut rest = self.unit_str(unit, &mut buffer).chars();
+                rest.next();
+                out.write_str(rest.as_str())
+            }
+            Token::Group { kind, ref content } => {
+                self.write_group(text, kind, content.clone(), out)
+            }
+            Token::Fallback {
+                kind,
+                ref prefix,
+                ref rest,
+            } => {
+                self.write_group(text, kind, prefix.clone(), out)?;
+                let (open, close) = match self.script_fallback {
+                    ScriptFallback::Parenthesized => ('(', ')'),
+                    _ => ('{', '}'),
+                };
+                out.write_char(kind.control_char())?;
+                out.write_char(open)?;
+                let mut index = rest.start;
+                while index < rest.end {
+                    let Some((unit, end)) = self.unit(text, index) else {
+                        break;
+                    };
+                    out.write_str(self.unit_str(unit, &mut [0; 4]))?;
+                    index = end;
+                }
+                out.write_char(close)
+            }
+            Token::Char(c) => out.write_char(c),
+            Token::Argument { ref content } => {
+                let mut index = content.start;
+                while index < content.end {
+                    let Some((token, end)) = self.token(text, index) else {
+                        break;
+                    };
+                    self.write_token(text, &token, out)?;
+                    index = end;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Write a group of scripts as if each unit was a separate script,
+    /// leaving characters without a script after the control character.
+    fn write_group<W: Write + ?Sized>(
+        &self,
+        text: &str,
+        kind: ScriptKind,
+        content: Range<usize>,
+        out: &mut W,
+    ) -> fmt::Result {
+        let control = kind.control_char();
+        let mut index = content.start;
+        while index < content.end {
+            let Some((unit, end)) = self.unit(text, index) else {
+                break;
+            };
+            for base in self.unit_str(unit, &mut [0; 4]).chars() {
+                match self.find_script(control, base) {
+                    Some(script) => out.write_str(&self.scripts[script].2)?,
                     None => {
-                        // incomplete: unescape and continue
-                        text.truncate(found_index);
-                        text.push_str(original_command);
-                        text.push('{');
-                        continue;
-                    }
-                    Some(combined_char) => {
-                        let char_offset = combined_char.len_utf8() + 1;
-                        let resume_index =
-                            (found_index + escaped_latex.len() + char_offset).min(text.len());
-                        replace_buffer.clear();
-                        replace_buffer.push(combined_char);
-                        assert!(replace_buffer.len() + result_text.len() < 16);
-                        replace_buffer.push_str(result_text);
-                        text.replace_range(found_index..resume_index, &replace_buffer);
+                        out.write_char(control)?;
+                        out.write_char(base)?;
                     }
                 }
             }
+            index = end;
         }
+        Ok(())
     }
+}
 
-    text
+/// Write the text, applying the combining marks to every extended grapheme cluster
+/// after any marks already applied to it.
+///
+/// Whitespace is left unmarked.
+fn combine<W: Write + ?Sized>(text: &str, marks: &str, out: &mut W) -> fmt::Result {
+    for cluster in text.graphemes(true) {
+        out.write_str(cluster)?;
+        if !cluster.starts_with(char::is_whitespace) {
+            out.write_str(marks)?;
+        }
+    }
+    Ok(())
 }
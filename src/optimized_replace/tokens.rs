@@ -0,0 +1,281 @@
+//! Splitting text into the tokens converted by the single-pass engine.
+
+use super::{Engine, Step, Token as EngineToken, combine};
+use crate::scripts::ScriptKind;
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::String;
+use core::iter::FusedIterator;
+use core::ops::Range;
+
+/// A piece of text and its conversion, returned by [`tokens`](crate::tokens).
+///
+/// Concatenating the [converted text](Token::unicode) of every token
+/// gives the same text as [`crate::replace`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Token<'a> {
+    /// Text which is left unchanged, like `a + ` in `a + \alpha`.
+    Literal(&'a str),
+    /// A symbol like `\alpha` and its Unicode equivalent.
+    Symbol {
+        /// The LaTeX for the symbol, like `\alpha`.
+        latex: &'a str,
+        /// The Unicode equivalent, like `α`.
+        unicode: &'a str,
+    },
+    /// A subscript or superscript like `^2` or `_{12}`, not including its base.
+    Script {
+        /// The LaTeX for the script, like `^2`.
+        latex: &'a str,
+        /// Whether this is a subscript or a superscript.
+        kind: ScriptKind,
+        /// The Unicode script characters, like `²`.
+        chars: Cow<'a, str>,
+    },
+    /// A group of scripts like `^{Q}` with a character which has no script,
+    /// converted using the [fallback](crate::ConverterBuilder::script_fallback).
+    ScriptFallback {
+        /// The LaTeX for the group, like `^{Q}`.
+        latex: &'a str,
+        /// Whether this is a subscript or a superscript.
+        kind: ScriptKind,
+        /// The converted group, like `^(Q)`.
+        unicode: Cow<'a, str>,
+    },
+    /// A combining mark applied to its argument like `\hat{a}`,
+    /// or a negated command like `\not\in`.
+    Combining {
+        /// The LaTeX for the mark and its argument, like `\hat{a}`.
+        latex: &'a str,
+        /// The converted argument, like `a`.
+        base: Cow<'a, str>,
+        /// The Unicode combining marks following the argument, like `\u{302}`.
+        mark: Cow<'a, str>,
+    },
+    /// A combining mark applied to every grapheme cluster of its argument like `\hat{ab}`,
+    /// with [combining groups](crate::ConverterBuilder::combining_groups) enabled.
+    CombiningGroup {
+        /// The LaTeX for the mark and its argument, like `\hat{ab}`.
+        latex: &'a str,
+        /// The converted argument, like `ab`.
+        base: Cow<'a, str>,
+        /// The Unicode combining marks applied to each character, like `\u{302}`.
+        mark: Cow<'a, str>,
+        /// The argument with the marks applied, like `a\u{302}b\u{302}`.
+        combined: Cow<'a, str>,
+    },
+    /// Text which looks like LaTeX but cannot be converted, like `\unknown` or a `^` without a script.
+    ///
+    /// This is left unchanged.
+    Unknown(&'a str),
+}
+
+impl<'a> Token<'a> {
+    /// The text of the input covered by this token.
+    pub fn latex(&self) -> &'a str {
+        match *self {
+            Token::Literal(text) | Token::Unknown(text) => text,
+            Token::Symbol { latex, .. }
+            | Token::Script { latex, .. }
+            | Token::ScriptFallback { latex, .. }
+            | Token::Combining { latex, .. }
+            | Token::CombiningGroup { latex, .. } => latex,
+        }
+    }
+
+    /// The converted text of this token.
+    ///
+    /// ```
+    /// let converted: String = unicodeit::tokens(r"x^2 + \hat{a}")
+    ///     .map(|token| token.unicode())
+    ///     .collect();
+    /// assert_eq!(converted, unicodeit::replace(r"x^2 + \hat{a}"));
+    /// ```
+    pub fn unicode(&self) -> Cow<'a, str> {
+        match self {
+            Token::Literal(text) | Token::Unknown(text) => Cow::Borrowed(text),
+            Token::Symbol { unicode, .. } => Cow::Borrowed(unicode),
+            Token::Script { chars, .. } => chars.clone(),
+            Token::ScriptFallback { unicode, .. } => unicode.clone(),
+            Token::Combining { base, mark, .. } => Cow::Owned(format!("{base}{mark}")),
+            Token::CombiningGroup { combined, .. } => combined.clone(),
+        }
+    }
+}
+
+/// An iterator over the [`Token`]s of a text, returned by [`tokens`](crate::tokens).
+#[derive(Clone, Debug)]
+pub struct Tokens<'a> {
+    engine: &'a Engine,
+    text: &'a str,
+    /// The end of the text which has already been returned.
+    written: usize,
+    /// The position of the search for the next token.
+    index: usize,
+    /// A token found after a literal, which is returned next.
+    pending: Option<Token<'a>>,
+}
+
+impl Engine {
+    /// Split the text into tokens.
+    pub(crate) fn tokens<'a>(&'a self, text: &'a str) -> Tokens<'a> {
+        Tokens {
+            engine: self,
+            text,
+            written: 0,
+            index: 0,
+            pending: None,
+        }
+    }
+}
+
+impl<'a> Tokens<'a> {
+    /// Find the end of text which cannot be converted, starting at the index,
+    /// if it looks like LaTeX.
+    fn unknown(&self, index: usize, end: usize) -> Option<usize> {
+        match self.text.as_bytes()[index] {
+            // extend a single backslash over the name of an unknown command
+            b'\\' if end == index + 1 => Some(
+                end + self.text.as_bytes()[end..]
+                    .iter()
+                    .take_while(|&&b| b.is_ascii_alphabetic() && !self.engine.triggers[b as usize])
+                    .count(),
+            ),
+            b'\\' | b'^' | b'_' => Some(end),
+            _ => None,
+        }
+    }
+
+    /// Describe a token found by the engine.
+    fn token(
+        &self,
+        token: EngineToken,
+        latex: &'a str,
+        openers: Range<usize>,
+        marks: usize,
+    ) -> Token<'a> {
+        let engine = self.engine;
+        let base = match token {
+            EngineToken::Replacement(replacement) | EngineToken::Not(replacement) => {
+                Cow::Borrowed(&*engine.replacements.values[replacement])
+            }
+            _ => {
+                let mut base = String::new();
+                engine
+                    .write_token(self.text, &token, &mut base)
+                    .expect("writing to a string cannot fail");
+                Cow::Owned(base)
+            }
+        };
+        if let EngineToken::Argument { .. } = token {
+            let mut mark = String::new();
+            engine
+                .write_marks(self.text, openers, marks, &mut mark)
+                .expect("writing to a string cannot fail");
+            let mut combined = String::new();
+            combine(&base, &mark, &mut combined).expect("writing to a string cannot fail");
+            // a single grapheme cluster like `e\u{301}` is combined as a whole
+            if combined.strip_prefix(&*base) == Some(&*mark) {
+                let mark = Cow::Owned(mark);
+                return Token::Combining { latex, base, mark };
+            }
+            return Token::CombiningGroup {
+                latex,
+                base,
+                mark: Cow::Owned(mark),
+                combined: Cow::Owned(combined),
+            };
+        }
+        if marks == 0 && !matches!(token, EngineToken::Not(_)) {
+            return match token {
+                EngineToken::Replacement(replacement) => Token::Symbol {
+                    latex,
+                    unicode: &engine.replacements.values[replacement],
+                },
+                EngineToken::Script { script, .. } => Token::Script {
+                    latex,
+                    kind: ScriptKind::from_control_char(engine.scripts[script].0)
+                        .expect("scripts start with a control character"),
+                    chars: base,
+                },
+                EngineToken::Group { kind, .. } => Token::Script {
+                    latex,
+                    kind,
+                    chars: base,
+                },
+                EngineToken::Fallback { kind, .. } => Token::ScriptFallback {
+                    latex,
+                    kind,
+                    unicode: base,
+                },
+                EngineToken::Not(_) | EngineToken::Char(_) | EngineToken::Argument { .. } => {
+                    unreachable!()
+                }
+            };
+        }
+        let mut combined = String::new();
+        engine
+            .write_marked(self.text, &token, openers, marks, &mut combined)
+            .expect("writing to a string cannot fail");
+        let mark = Cow::Owned(combined[base.len()..].into());
+        Token::Combining { latex, base, mark }
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        if let Some(token) = self.pending.take() {
+            return Some(token);
+        }
+        let text = self.text;
+        let bytes = text.as_bytes();
+        while self.index < bytes.len() {
+            if !self.engine.triggers[bytes[self.index] as usize] {
+                self.index += 1;
+                continue;
+            }
+            let (start, end, token) = match self.engine.step(text, self.index) {
+                Step::Skip(end) => match self.unknown(self.index, end) {
+                    Some(end) => (self.index, end, Token::Unknown(&text[self.index..end])),
+                    None => {
+                        self.index = end;
+                        continue;
+                    }
+                },
+                Step::Token { start, .. } if start > self.index => {
+                    // combining marks which are not closed are left unchanged,
+                    // and the rest is found again from the first mark which is closed
+                    (self.index, start, Token::Unknown(&text[self.index..start]))
+                }
+                Step::Token {
+                    token,
+                    start,
+                    openers,
+                    closers,
+                } => {
+                    let latex = &text[start..closers.end];
+                    let token = self.token(token, latex, openers, closers.len());
+                    (start, closers.end, token)
+                }
+                Step::End => break,
+            };
+            let literal = &text[self.written..start];
+            self.written = end;
+            self.index = end;
+            if literal.is_empty() {
+                return Some(token);
+            }
+            self.pending = Some(token);
+            return Some(Token::Literal(literal));
+        }
+        self.index = bytes.len();
+        let literal = &text[self.written..];
+        self.written = bytes.len();
+        (!literal.is_empty()).then_some(Token::Literal(literal))
+    }
+}
+
+impl FusedIterator for Tokens<'_> {}
@@ -0,0 +1,346 @@
+//! Conversion by walking the [syntax tree](crate::parse) of the input,
+//! instead of matching each command in the text.
+//!
+//! Replacements must cover whole nodes, so `\alphax` is left unchanged,
+//! and the arguments of combining marks and scripts are matched by their braces,
+//! so `\hat{ab}` puts a hat on both characters.
+
+use super::{Engine, Output, combine};
+use crate::scripts::{ScriptFallback, ScriptKind};
+use crate::syntax::{Node, NodeKind, parse};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use core::ops::Range;
+use core::slice;
+use unicode_segmentation::UnicodeSegmentation;
+
+impl Engine {
+    /// Convert the text by walking its syntax tree,
+    /// returning where it was cut off if the output reached its limit.
+    pub(super) fn convert_tree<W: Write + ?Sized>(
+        &self,
+        text: &str,
+        out: &mut Output<'_, W>,
+        edits: Option<&mut Vec<(Range<usize>, usize)>>,
+    ) -> Result<Option<usize>, fmt::Error> {
+        let nodes = parse(text);
+        let mut walker = Walker {
+            engine: self,
+            text,
+            out,
+            edits,
+            cut_off: None,
+        };
+        walker.nodes(&nodes)?;
+        if let Some(cut_off) = walker.cut_off
+            && let Some(edits) = walker.edits
+        {
+            edits.push((cut_off..text.len(), 0));
+        }
+        Ok(walker.cut_off)
+    }
+
+    /// Convert a sequence of sibling nodes to a string,
+    /// like the argument of a combining mark or script.
+    fn convert_nodes(&self, text: &str, nodes: &[Node]) -> String {
+        let mut converted = String::new();
+        let mut walker = Walker {
+            engine: self,
+            text,
+            out: &mut Output::new(&mut converted),
+            edits: None,
+            cut_off: None,
+        };
+        walker
+            .nodes(nodes)
+            .expect("writing to a string cannot fail");
+        converted
+    }
+
+    /// Convert every character to a script of the kind,
+    /// unless one of them has no script.
+    fn scripts(&self, kind: ScriptKind, text: &str) -> Option<String> {
+        if text.is_empty() {
+            return None;
+        }
+        text.chars()
+            .map(|base| {
+                let script = self.find_script(kind.control_char(), base)?;
+                Some(&*self.scripts[script].2)
+            })
+            .collect()
+    }
+}
+
+/// Writes the converted nodes, keeping track of where the output was cut off.
+struct Walker<'a, 'o, W: ?Sized> {
+    engine: &'a Engine,
+    text: &'a str,
+    out: &'a mut Output<'o, W>,
+    edits: Option<&'a mut Vec<(Range<usize>, usize)>>,
+    /// Where the input was cut off because the output reached its limit.
+    cut_off: Option<usize>,
+}
+
+impl<W: Write + ?Sized> Walker<'_, '_, W> {
+    /// Copy the unchanged text, or as much of it as fits.
+    fn copy(&mut self, range: Range<usize>) -> fmt::Result {
+        if self.cut_off.is_none() {
+            self.cut_off = self.out.copy(self.text, range)?;
+        }
+        Ok(())
+    }
+
+    /// Write the conversion of the range, unless it does not fit.
+    fn write(&mut self, range: Range<usize>, converted: &str) -> fmt::Result {
+        if self.cut_off.is_some() {
+            return Ok(());
+        }
+        if converted == &self.text[range.clone()] {
+            return self.copy(range);
+        }
+        if !self.out.fits(converted.len()) {
+            self.cut_off = Some(range.start);
+            return Ok(());
+        }
+        self.out.write_str(converted)?;
+        if let Some(edits) = self.edits.as_deref_mut() {
+            edits.push((range, converted.len()));
+        }
+        Ok(())
+    }
+
+    /// Convert a sequence of sibling nodes.
+    fn nodes(&mut self, nodes: &[Node]) -> fmt::Result {
+        let Some(first) = nodes.first() else {
+            return Ok(());
+        };
+        let mut index = 0;
+        let mut position = first.span.start;
+        while index < nodes.len() && self.cut_off.is_none() {
+            position = self.step(nodes, index, position)?;
+            while index < nodes.len() && nodes[index].span.end <= position {
+                index += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Convert the text starting at the position within the node at the index,
+    /// returning the end of the converted text.
+    fn step(&mut self, nodes: &[Node], index: usize, position: usize) -> Result<usize, fmt::Error> {
+        let node = &nodes[index];
+        let replacement = self.replacement(nodes, index, position);
+        if node.kind == NodeKind::Command && replacement.is_none_or(|(_, end)| end <= node.span.end)
+        {
+            if let Some(end) = self.combining(nodes, index)? {
+                return Ok(end);
+            }
+            if let Some(end) = self.not(nodes, index)? {
+                return Ok(end);
+            }
+        }
+        if let Some((replacement, end)) = replacement {
+            let engine = self.engine;
+            self.write(position..end, &engine.replacements.values[replacement])?;
+            return Ok(end);
+        }
+        let Range { start, end } = node.span;
+        match &node.kind {
+            NodeKind::Text => {
+                // copy up to the next character which could start a replacement
+                let triggers = &self.engine.triggers;
+                let next = self.text.as_bytes()[position + 1..end]
+                    .iter()
+                    .position(|&b| triggers[b as usize])
+                    .map_or(end, |offset| position + 1 + offset);
+                self.copy(position..next)?;
+                return Ok(next);
+            }
+            NodeKind::Group { children, closed } => {
+                self.copy(start..start + 1)?;
+                self.nodes(children)?;
+                if *closed {
+                    self.copy(end - 1..end)?;
+                }
+            }
+            NodeKind::Script { kind, argument } => self.script(node, *kind, argument.as_deref())?,
+            _ => self.copy(start..end)?,
+        }
+        Ok(end)
+    }
+
+    /// Find the longest replacement starting at the position,
+    /// returning the index of the replacement and its end.
+    ///
+    /// The replacement must end at the end of a node or within text,
+    /// so `\alpha` is not found at the start of `\alphax`.
+    fn replacement(&self, nodes: &[Node], index: usize, position: usize) -> Option<(usize, usize)> {
+        let engine = self.engine;
+        if !engine.triggers[self.text.as_bytes()[position] as usize] {
+            return None;
+        }
+        let (replacement, end) = engine.replacements.find(self.text, position)?;
+        let last = nodes[index..]
+            .iter()
+            .take_while(|node| node.span.start < end)
+            .last()?;
+        let aligned = last.span.end == end || (last.kind == NodeKind::Text && end < last.span.end);
+        aligned.then_some((replacement, end))
+    }
+
+    /// Convert a combining mark like `\hat` at the index with its argument,
+    /// returning the end of the argument.
+    ///
+    /// The argument is the following group, command, or the next character of text,
+    /// so `\hat{ab}`, `\hat\alpha` and `\hat a` are all combined.
+    fn combining(&mut self, nodes: &[Node], index: usize) -> Result<Option<usize>, fmt::Error> {
+        let engine = self.engine;
+        let command = &nodes[index];
+        let mut opener = String::from(&self.text[command.span.clone()]);
+        opener.push('{');
+        let Some((mark, opener_end)) = engine.combining_marks.find(&opener, 0) else {
+            return Ok(None);
+        };
+        let Some(next) = nodes.get(index + 1).filter(|_| opener_end == opener.len()) else {
+            return Ok(None);
+        };
+        let (argument, end) = match &next.kind {
+            NodeKind::Group {
+                children,
+                closed: true,
+            } if has_unparsed(children) => {
+                // content which is nested too deeply is left unchanged, along with its marks
+                self.copy(command.span.start..next.span.end)?;
+                return Ok(Some(next.span.end));
+            }
+            NodeKind::Group {
+                children,
+                closed: true,
+            } => (engine.convert_nodes(self.text, children), next.span.end),
+            NodeKind::Command | NodeKind::Symbol => (
+                engine.convert_nodes(self.text, slice::from_ref(next)),
+                next.span.end,
+            ),
+            NodeKind::Text => {
+                // like LaTeX, spaces after the command are skipped
+                let rest = self.text[next.span.clone()].trim_start();
+                let Some(cluster) = rest.graphemes(true).next() else {
+                    return Ok(None);
+                };
+                (
+                    String::from(cluster),
+                    next.span.end - rest.len() + cluster.len(),
+                )
+            }
+            _ => return Ok(None),
+        };
+        if argument.is_empty() {
+            return Ok(None);
+        }
+        let mut combined = String::new();
+        combine(
+            &argument,
+            &engine.combining_marks.values[mark],
+            &mut combined,
+        )?;
+        self.write(command.span.start..end, &engine.normalize(&combined))?;
+        Ok(Some(end))
+    }
+
+    /// Convert a negated command like `\not\in` at the index,
+    /// returning the end of the negated command.
+    fn not(&mut self, nodes: &[Node], index: usize) -> Result<Option<usize>, fmt::Error> {
+        let engine = self.engine;
+        let command = &nodes[index];
+        let (Some(slash), Some(next)) = (engine.slash, nodes.get(index + 1)) else {
+            return Ok(None);
+        };
+        if &self.text[command.span.clone()] != r"\not"
+            || !matches!(next.kind, NodeKind::Command | NodeKind::Symbol)
+        {
+            return Ok(None);
+        }
+        let Some((replacement, end)) = self
+            .replacement(nodes, index + 1, next.span.start)
+            .filter(|&(_, end)| end == next.span.end)
+        else {
+            return Ok(None);
+        };
+        let mut negated = String::from(&*engine.replacements.values[replacement]);
+        negated.push_str(&engine.combining_marks.values[slash]);
+        self.write(command.span.start..end, &engine.normalize(&negated))?;
+        Ok(Some(end))
+    }
+
+    /// Convert a script like `^2` or `_{n+1}`.
+    ///
+    /// If any character of the argument has no script,
+    /// the argument is converted but the script is left as it is,
+    /// unless a group can be converted using the [fallback](ScriptFallback).
+    fn script(&mut self, node: &Node, kind: ScriptKind, argument: Option<&Node>) -> fmt::Result {
+        let engine = self.engine;
+        let Some(argument) = argument else {
+            return self.copy(node.span.clone());
+        };
+        let (content, group) = match &argument.kind {
+            NodeKind::Group {
+                children,
+                closed: true,
+            } if has_unparsed(children) => return self.copy(node.span.clone()),
+            NodeKind::Group {
+                children,
+                closed: true,
+            } if engine.script_groups => (&children[..], true),
+            NodeKind::Group { .. } => {
+                self.copy(node.span.start..argument.span.start)?;
+                return self.nodes(slice::from_ref(argument));
+            }
+            _ => (slice::from_ref(argument), false),
+        };
+        let converted = engine.convert_nodes(self.text, content);
+        if let Some(scripts) = engine.scripts(kind, &converted) {
+            return self.write(node.span.clone(), &scripts);
+        }
+        let control = kind.control_char();
+        let mut written = String::new();
+        let mut rest = &*converted;
+        if group && engine.script_fallback == ScriptFallback::Prefix {
+            // convert the characters before the first one without a script
+            let prefix_end = converted
+                .find(|c| engine.find_script(control, c).is_none())
+                .unwrap_or(converted.len());
+            if let Some(prefix) = engine.scripts(kind, &converted[..prefix_end]) {
+                written = prefix;
+                rest = &converted[prefix_end..];
+            }
+        }
+        written.push(control);
+        if group {
+            let (open, close) = match engine.script_fallback {
+                ScriptFallback::Parenthesized if !converted.is_empty() => ('(', ')'),
+                _ => ('{', '}'),
+            };
+            written.push(open);
+            written.push_str(rest);
+            written.push(close);
+        } else {
+            written.push_str(&converted);
+        }
+        self.write(node.span.clone(), &written)
+    }
+}
+
+/// Determine if any of the nodes contains a group nested too deeply to be parsed.
+fn has_unparsed(nodes: &[Node]) -> bool {
+    nodes.iter().any(|node| match &node.kind {
+        NodeKind::Unparsed => true,
+        NodeKind::Group { children, .. } => has_unparsed(children),
+        NodeKind::Script {
+            argument: Some(argument),
+            ..
+        } => has_unparsed(slice::from_ref(argument)),
+        _ => false,
+    })
+}
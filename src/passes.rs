@@ -0,0 +1,237 @@
+//! The sequence of passes run on each piece of math, including custom passes.
+
+use std::borrow::Cow;
+use std::fmt::{self, Debug, Formatter};
+use std::ops::Range;
+use std::panic::RefUnwindSafe;
+use std::sync::Arc;
+
+/// A custom step of the conversion, which rewrites the text of each piece of math,
+/// used by [`Passes`].
+///
+/// Depending on where the pass is [added](Passes::add_before),
+/// it sees LaTeX before it is converted or the Unicode output after,
+/// so it can strip commands like `\displaystyle` or post-process the output.
+///
+/// Any function from `&str` to `String` is a pass.
+/// Passes must be [`RefUnwindSafe`], so a [`Converter`](crate::Converter) stays unwind safe.
+///
+/// ```
+/// use std::borrow::Cow;
+/// use unicodeit::{BuiltinPass, Converter, Pass, Passes};
+///
+/// struct StripDisplayStyle;
+///
+/// impl Pass for StripDisplayStyle {
+///     fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+///         if text.contains(r"\displaystyle") {
+///             Cow::Owned(text.replace(r"\displaystyle ", ""))
+///         } else {
+///             Cow::Borrowed(text)
+///         }
+///     }
+/// }
+///
+/// let converter = Converter::builder()
+///     .passes(Passes::new().add_before(BuiltinPass::Symbols, StripDisplayStyle))
+///     .build();
+/// assert_eq!(converter.replace(r"\displaystyle \sum_i"), "\u{2211}\u{1d62}");
+/// ```
+pub trait Pass: Send + Sync + RefUnwindSafe {
+    /// Rewrite the text, borrowing it if it is unchanged.
+    ///
+    /// In the [offset map](crate::OffsetMap), everything from the first byte
+    /// which was changed to the last is treated as a single replacement.
+    fn apply<'a>(&self, text: &'a str) -> Cow<'a, str>;
+}
+
+impl<F: Fn(&str) -> String + Send + Sync + RefUnwindSafe> Pass for F {
+    #[inline]
+    fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        Cow::Owned(self(text))
+    }
+}
+
+/// One of the passes built into a [`Converter`](crate::Converter),
+/// which custom passes can be added before or after.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum BuiltinPass {
+    /// Expanding [macros](crate::ConverterBuilder::macros), if enabled.
+    Macros,
+    /// Converting symbols, combining marks and scripts,
+    /// using the phases enabled on the [builder](crate::ConverterBuilder).
+    Symbols,
+}
+
+/// A pass in the list, which is either built-in or custom.
+#[derive(Clone)]
+pub(crate) enum PassKind {
+    Builtin(BuiltinPass),
+    Custom(Arc<dyn Pass>),
+}
+
+impl Debug for PassKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PassKind::Builtin(builtin) => builtin.fmt(f),
+            PassKind::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+/// The passes run on each piece of math, in order,
+/// used by [`ConverterBuilder::passes`](crate::ConverterBuilder::passes).
+///
+/// Custom passes can be added before or after any of the [built-in passes](BuiltinPass),
+/// which can also be reordered or left out.
+/// The limit on the [output length](crate::ConverterBuilder::max_output_len) still applies
+/// to the output of any custom passes run after the built-in passes.
+///
+/// Custom passes affect where the input is split when [streaming](crate::ReplaceWriter#splitting),
+/// and each part is passed separately.
+///
+/// ```
+/// use unicodeit::{BuiltinPass, Converter, Passes};
+/// let passes = Passes::new()
+///     .add_before(BuiltinPass::Symbols, |text: &str| text.replace(r"\mathrm{d}", "d"))
+///     .add_after(BuiltinPass::Symbols, |text: &str| text.replace('\u{2212}', "-"));
+/// let converter = Converter::builder().passes(passes).build();
+/// assert_eq!(converter.replace(r"\int f(x) \mathrm{d}x - 1"), "\u{222b} f(x) dx - 1");
+/// ```
+#[derive(Clone, Debug)]
+pub struct Passes {
+    pub(crate) passes: Vec<PassKind>,
+}
+
+impl Passes {
+    /// The built-in passes in their default order,
+    /// [macros](BuiltinPass::Macros) then [symbols](BuiltinPass::Symbols).
+    pub fn new() -> Self {
+        Passes::empty()
+            .builtin(BuiltinPass::Macros)
+            .builtin(BuiltinPass::Symbols)
+    }
+
+    /// No passes, so the built-in passes can be chosen and ordered with [`Passes::builtin`].
+    ///
+    /// ```
+    /// use unicodeit::{Converter, Passes};
+    /// let converter = Converter::builder()
+    ///     .passes(Passes::empty().custom(|text: &str| text.to_uppercase()))
+    ///     .build();
+    /// assert_eq!(converter.replace(r"\alpha"), r"\ALPHA");
+    /// ```
+    pub fn empty() -> Self {
+        Passes { passes: Vec::new() }
+    }
+
+    /// Add a built-in pass at the end of the list.
+    ///
+    /// # Panics
+    /// If the pass is already in the list.
+    pub fn builtin(mut self, pass: BuiltinPass) -> Self {
+        assert!(
+            self.position(pass).is_none(),
+            "built-in pass {pass:?} added twice"
+        );
+        self.passes.push(PassKind::Builtin(pass));
+        self
+    }
+
+    /// Add a custom pass at the end of the list.
+    pub fn custom(mut self, pass: impl Pass + 'static) -> Self {
+        self.passes.push(PassKind::Custom(Arc::new(pass)));
+        self
+    }
+
+    /// Add a custom pass immediately before a built-in pass.
+    ///
+    /// # Panics
+    /// If the built-in pass is not in the list.
+    pub fn add_before(self, builtin: BuiltinPass, pass: impl Pass + 'static) -> Self {
+        let index = self.expect_position(builtin);
+        self.insert(index, pass)
+    }
+
+    /// Add a custom pass immediately after a built-in pass,
+    /// and after any custom passes already added after it.
+    ///
+    /// # Panics
+    /// If the built-in pass is not in the list.
+    pub fn add_after(self, builtin: BuiltinPass, pass: impl Pass + 'static) -> Self {
+        let index = self.expect_position(builtin) + 1;
+        let custom = self.passes[index..]
+            .iter()
+            .take_while(|pass| matches!(pass, PassKind::Custom(_)))
+            .count();
+        self.insert(index + custom, pass)
+    }
+
+    fn insert(mut self, index: usize, pass: impl Pass + 'static) -> Self {
+        self.passes.insert(index, PassKind::Custom(Arc::new(pass)));
+        self
+    }
+
+    fn position(&self, builtin: BuiltinPass) -> Option<usize> {
+        self.passes
+            .iter()
+            .position(|pass| matches!(pass, PassKind::Builtin(existing) if *existing == builtin))
+    }
+
+    fn expect_position(&self, builtin: BuiltinPass) -> usize {
+        self.position(builtin)
+            .unwrap_or_else(|| panic!("built-in pass {builtin:?} is not in the list"))
+    }
+
+    /// Determine if these are the built-in passes in their default order.
+    pub(crate) fn is_default(&self) -> bool {
+        matches!(
+            self.passes[..],
+            [
+                PassKind::Builtin(BuiltinPass::Macros),
+                PassKind::Builtin(BuiltinPass::Symbols)
+            ]
+        )
+    }
+
+    /// Determine if there are any custom passes.
+    pub(crate) fn has_custom(&self) -> bool {
+        self.passes
+            .iter()
+            .any(|pass| matches!(pass, PassKind::Custom(_)))
+    }
+}
+
+impl Default for Passes {
+    #[inline]
+    fn default() -> Self {
+        Passes::new()
+    }
+}
+
+/// Find the part of the old text which was changed to give the new text,
+/// returning its range and the length of the new part.
+///
+/// The range always falls on character boundaries in both texts.
+pub(crate) fn changed_range(old: &str, new: &str) -> (Range<usize>, usize) {
+    let mut prefix = old
+        .bytes()
+        .zip(new.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(prefix) || !new.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+    let mut suffix = old
+        .bytes()
+        .rev()
+        .zip(new.bytes().rev())
+        .take(old.len().min(new.len()) - prefix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(old.len() - suffix) || !new.is_char_boundary(new.len() - suffix) {
+        suffix -= 1;
+    }
+    (prefix..old.len() - suffix, new.len() - suffix - prefix)
+}
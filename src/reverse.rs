@@ -0,0 +1,193 @@
+//! The reverse conversion, from Unicode back to LaTeX.
+//!
+//! This inverts the tables in [`crate::data`],
+//! so that running [`crate::replace`] over the output
+//! reproduces the original Unicode text,
+//! unless it already contains LaTeX like `\alpha` or `x^2`.
+
+use crate::ScriptKind;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+/// Maps a single character back to the LaTeX command that produces it.
+struct ReverseTable {
+    /// Inverse of [`crate::data::REPLACEMENTS`].
+    replacements: HashMap<char, &'static str>,
+    /// Inverse of [`crate::data::SUB_SUPER_SCRIPTS`],
+    /// giving the control character (`^` or `_`) and the base character.
+    scripts: HashMap<char, (char, char)>,
+    /// Inverse of [`crate::data::COMBINING_MARKS`].
+    combining_marks: HashMap<char, &'static str>,
+}
+
+/// Decide if `candidate` is a better canonical command than `existing`.
+///
+/// Shorter commands are preferred, falling back to table order for ties.
+/// This picks `\bar` over `\overline`, and `\mathbfit{...}` over the equally long `\mathbold{...}`.
+fn is_preferred(candidate: &str, existing: &str) -> bool {
+    candidate.chars().count() < existing.chars().count()
+}
+
+/// Extract the single character from a table value.
+fn single_char(value: &str) -> Option<char> {
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+fn insert_preferred(map: &mut HashMap<char, &'static str>, c: char, latex: &'static str) {
+    map.entry(c)
+        .and_modify(|existing| {
+            if is_preferred(latex, existing) {
+                *existing = latex;
+            }
+        })
+        .or_insert(latex);
+}
+
+static REVERSE_TABLE: LazyLock<ReverseTable> = LazyLock::new(|| {
+    let mut replacements = HashMap::new();
+    for &(latex, unicode) in crate::data::REPLACEMENTS {
+        match single_char(unicode) {
+            // `\not` would negate the following command, so a lone U+338 is kept as is
+            _ if latex == "\\not" => {}
+            // ASCII text is left alone, there is no need to escape it
+            Some(c) if !c.is_ascii() => insert_preferred(&mut replacements, c, latex),
+            _ => {}
+        }
+    }
+    let mut scripts = HashMap::new();
+    for &(latex, unicode) in crate::data::SUB_SUPER_SCRIPTS {
+        let (Some(c), Some((control, base))) = (single_char(unicode), split_script(latex)) else {
+            continue;
+        };
+        if !c.is_ascii() {
+            // all script entries have the same length, so first entry wins
+            scripts.entry(c).or_insert((control, base));
+        }
+    }
+    let mut combining_marks = HashMap::new();
+    for &(latex, unicode) in crate::data::COMBINING_MARKS {
+        if let Some(c) = single_char(unicode) {
+            insert_preferred(&mut combining_marks, c, latex);
+        }
+    }
+    ReverseTable {
+        replacements,
+        scripts,
+        combining_marks,
+    }
+});
+
+/// Split a script entry like `_1` into its control character and base character.
+fn split_script(latex: &str) -> Option<(char, char)> {
+    let mut chars = latex.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some(control @ ('^' | '_')), Some(base), None) => Some((control, base)),
+        _ => None,
+    }
+}
+
+/// Accumulates LaTeX output,
+/// separating control words from following letters.
+///
+/// A space would be kept by [`crate::replace`],
+/// so the letter is written as `\mathrm{b}` instead,
+/// which converts back to the plain letter.
+struct LatexWriter {
+    buffer: String,
+    /// If the buffer currently ends with a control word like `\alpha`.
+    pending_control_word: bool,
+}
+
+impl LatexWriter {
+    fn push_str(&mut self, text: &str) {
+        match text.chars().next() {
+            Some(letter) if self.pending_control_word && letter.is_ascii_alphabetic() => {
+                // `\alpha\mathrm{b}` instead of `\alphab`
+                self.buffer.push_str("\\mathrm{");
+                self.buffer.push(letter);
+                self.buffer.push('}');
+                self.buffer.push_str(&text[1..]);
+            }
+            _ => self.buffer.push_str(text),
+        }
+        self.pending_control_word = text.starts_with('\\')
+            && text.len() > 1
+            && text.ends_with(|c: char| c.is_ascii_alphabetic());
+    }
+
+    fn push_script_run(&mut self, control: char, run: &[char]) {
+        let mut group = String::new();
+        let flush_group = |group: &mut String, writer: &mut LatexWriter| {
+            match group.chars().count() {
+                0 => {}
+                1 => writer.push_str(&format!("{control}{group}")),
+                _ => writer.push_str(&format!("{control}{{{group}}}")),
+            }
+            group.clear();
+        };
+        let kind = ScriptKind::from_control_char(control).expect("invalid control char");
+        for &base in run {
+            if kind.is_group_char(base) {
+                group.push(base);
+            } else {
+                // could not be expanded from a group, so must stand alone
+                flush_group(&mut group, self);
+                self.push_str(&format!("{control}{base}"));
+            }
+        }
+        flush_group(&mut group, self);
+    }
+}
+
+/// Convert Unicode characters back into LaTeX wherever possible.
+///
+/// This is the inverse of [`crate::replace`].
+/// When multiple commands produce the same character,
+/// the shortest one is used.
+/// Runs of subscripts and superscripts are grouped into `_{...}` and `^{...}`,
+/// and combining marks are turned back into commands like `\hat{a}`.
+///
+/// ASCII characters are never escaped,
+/// except for a letter following a control word, which is written as `\mathrm{...}`,
+/// so that `αb` becomes `\alpha\mathrm{b}` rather than `\alphab`.
+pub fn to_latex(text: &str) -> String {
+    let table = &*REVERSE_TABLE;
+    let mut writer = LatexWriter {
+        buffer: String::with_capacity(text.len()),
+        pending_control_word: false,
+    };
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if let Some(&(control, base)) = table.scripts.get(&c) {
+            let mut run = vec![base];
+            while let Some(&(next_control, next_base)) =
+                chars.peek().and_then(|next| table.scripts.get(next))
+            {
+                if next_control != control {
+                    break;
+                }
+                run.push(next_base);
+                chars.next();
+            }
+            writer.push_script_run(control, &run);
+            continue;
+        }
+        let mut latex = match table.replacements.get(&c) {
+            Some(&command) => command.to_string(),
+            None => c.to_string(),
+        };
+        while let Some(&mark) = chars
+            .peek()
+            .and_then(|next| table.combining_marks.get(next))
+        {
+            latex = format!("{mark}{{{latex}}}");
+            chars.next();
+        }
+        writer.push_str(&latex);
+    }
+    writer.buffer
+}
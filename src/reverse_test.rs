@@ -0,0 +1,83 @@
+//! Round-trip tests for [`crate::to_latex`].
+//!
+//! Can't be an integration test, because `crate::data` is private.
+
+#[cfg(not(test))]
+compile_error!("only for testing");
+
+fn assert_round_trip(unicode: &str) {
+    let latex = crate::to_latex(unicode);
+    assert_eq!(
+        crate::replace(&latex),
+        unicode,
+        "Failed to round-trip `{unicode}` through `{latex}`"
+    );
+}
+
+#[test]
+fn round_trip_replacements() {
+    for &(_, u) in crate::data::REPLACEMENTS {
+        if u.is_ascii() {
+            continue;
+        }
+        assert_round_trip(u);
+    }
+}
+
+#[test]
+fn round_trip_sub_super_scripts() {
+    for &(_, u) in crate::data::SUB_SUPER_SCRIPTS {
+        if u.is_ascii() {
+            continue;
+        }
+        assert_round_trip(&format!("x{u}"));
+    }
+}
+
+#[test]
+fn round_trip_script_runs() {
+    for control in ['^', '_'] {
+        let run = crate::data::SUB_SUPER_SCRIPTS
+            .iter()
+            .filter(|(l, u)| l.starts_with(control) && !u.is_ascii())
+            .map(|&(_, u)| u)
+            .collect::<String>();
+        assert_round_trip(&format!("x{run}"));
+    }
+}
+
+#[test]
+fn round_trip_combining_marks() {
+    for &(_, u) in crate::data::COMBINING_MARKS {
+        assert_round_trip(&format!("a{u}"));
+        assert_round_trip(&format!("\u{3b1}{u}"));
+    }
+}
+
+#[test]
+fn round_trip_sequences() {
+    for unicode in [
+        "\u{3b1}b",
+        "\u{2211}\u{1d62}a\u{1d62}",
+        "x\u{302}y",
+        "\u{302}a",
+        "\u{338}\u{3b1}",
+        "\u{3b1}\u{302}b",
+        "x\u{b2}y",
+    ] {
+        assert_round_trip(unicode);
+    }
+}
+
+#[test]
+fn round_trip_followed_by_letter() {
+    for &(_, u) in crate::data::REPLACEMENTS {
+        if u.is_ascii() {
+            continue;
+        }
+        assert_round_trip(&format!("{u}b"));
+    }
+    for &(_, u) in crate::data::COMBINING_MARKS {
+        assert_round_trip(&format!("{u}a"));
+    }
+}
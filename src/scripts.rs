@@ -0,0 +1,144 @@
+//! Subscripts and superscripts.
+
+use core::fmt::{self, Display, Formatter};
+
+/// Whether a script is a subscript or a superscript.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ScriptKind {
+    /// A subscript like `x_1`.
+    Subscript,
+    /// A superscript like `x^2`.
+    Superscript,
+}
+
+impl ScriptKind {
+    /// The character which starts this kind of script.
+    #[inline]
+    pub fn control_char(self) -> char {
+        match self {
+            ScriptKind::Subscript => '_',
+            ScriptKind::Superscript => '^',
+        }
+    }
+
+    /// Get the kind of script started by the specified character.
+    #[inline]
+    pub fn from_control_char(c: char) -> Option<Self> {
+        match c {
+            '_' => Some(ScriptKind::Subscript),
+            '^' => Some(ScriptKind::Superscript),
+            _ => None,
+        }
+    }
+
+    /// Determine if the character can appear in an expanded group like `x_{12}`.
+    ///
+    /// Mirrors the character classes used by [`crate::replace`].
+    pub(crate) fn is_group_char(self, c: char) -> bool {
+        match self {
+            ScriptKind::Subscript => is_subscript_group_char(c),
+            ScriptKind::Superscript => is_superscript_group_char(c),
+        }
+    }
+}
+
+/// How to convert a group of scripts like `x^{Q}` or `a_{bc}`
+/// which contains a character without a Unicode script,
+/// used by [`ConverterBuilder::script_fallback`](crate::ConverterBuilder::script_fallback).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ScriptFallback {
+    /// Leave the whole group unchanged, like the original library.
+    #[default]
+    Unchanged,
+    /// Convert the characters before the first one without a script,
+    /// and leave the rest in braces, so `x^{2Q}` becomes `x²^{Q}`.
+    Prefix,
+    /// Write the group in parentheses, so `x^{Q}` becomes `x^(Q)` and `a_{bc}` becomes `a_(bc)`.
+    Parenthesized,
+    /// Use the closest alternative for characters without a script,
+    /// like the small capital `𐞲` for `^Y` or the small letter `ₐ` for `_A`,
+    /// leaving the group unchanged if there is none.
+    Closest,
+}
+
+impl ScriptFallback {
+    /// Determine if groups are converted even if a character has no script.
+    pub(crate) fn converts_groups(self) -> bool {
+        matches!(self, ScriptFallback::Prefix | ScriptFallback::Parenthesized)
+    }
+}
+
+impl Display for ScriptKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ScriptKind::Subscript => "subscript",
+            ScriptKind::Superscript => "superscript",
+        })
+    }
+}
+
+/// Determine if the character can appear in an expanded `_{...}` group.
+fn is_subscript_group_char(c: char) -> bool {
+    matches!(
+        c,
+        '0'..='9'
+            | '+'..='='
+            | '('
+            | ')'
+            | '>'
+            | 'a'
+            | 'e'
+            | 'o'
+            | 'x'
+            | 'j'
+            | 'h'
+            | 'k'
+            | 'l'
+            | 'm'
+            | 'n'
+            | 'p'
+            | 's'
+            | 't'
+            | 'i'
+            | 'r'
+            | 'u'
+            | 'v'
+            | '\u{03B2}'
+            | '\u{03B3}'
+            | '\u{03C1}'
+            | '\u{03C6}'
+            | '\u{03C7}'
+            | '\u{2212}'
+    )
+}
+
+/// Determine if the character can appear in an expanded `^{...}` group.
+fn is_superscript_group_char(c: char) -> bool {
+    matches!(
+        c,
+        '0'..='9'
+            | '+'..='='
+            | '('
+            | ')'
+            | '>'
+            | 'A'
+            | 'B'
+            | 'D'
+            | 'E'
+            | 'G'..='P'
+            | 'R'
+            | 'T'
+            | 'U'
+            | 'W'
+            | 'a'..='p'
+            | 'r'..='z'
+            | '\u{3B2}'
+            | '\u{3B3}'
+            | '\u{3B4}'
+            | '\u{3C6}'
+            | '\u{3C7}'
+            | '\u{222B}'
+            | '\u{2212}'
+    )
+}
@@ -0,0 +1,652 @@
+//! Streaming conversion over [`std::io::Read`] and [`std::io::Write`].
+//!
+//! Input is buffered until it can be split at a point
+//! where converting each side separately gives the same result
+//! as converting everything at once with [`Converter::replace`].
+
+use crate::Converter;
+use crate::macros::Definitions;
+use std::io::{self, Read, Write};
+
+/// Buffers streaming input until it can be safely converted.
+///
+/// The input is only split after whitespace,
+/// because no symbol or subscript group can contain whitespace.
+/// The exception is the character right after a combining mark like `\hat{`,
+/// so splitting is also avoided if there is a `{` within the lookbehind window.
+/// This window is long enough to fit the longest replacement inside a script group,
+/// so covers combining marks applied to symbols like `\hat{\alpha}` or `\hat{_{\beta}}`.
+///
+/// Some options only allow splitting at blank lines, as listed in the [`ReplaceWriter`] docs.
+/// Macros defined in the input are remembered for the rest of the stream.
+pub(crate) struct StreamBuffer<'a> {
+    converter: &'a Converter,
+    /// Macros defined earlier in the stream.
+    definitions: Definitions,
+    /// Text that has not yet been converted.
+    pending: String,
+    /// The bytes of an incomplete UTF-8 character at the end of the input.
+    incomplete_char: Vec<u8>,
+    /// Byte index in `pending` up to which split points have been searched.
+    checked: usize,
+    /// Byte index in `pending` of the last safe split point.
+    split: usize,
+    /// The number of bytes before a split point that must not contain a `{`.
+    lookbehind: usize,
+    /// The brace depth at `checked`, only tracked if macros are enabled.
+    depth: usize,
+    /// If the byte before `checked` is an unescaped backslash.
+    escaped: bool,
+}
+
+impl<'a> StreamBuffer<'a> {
+    pub(crate) fn new(converter: &'a Converter) -> Self {
+        let longest_replacement = converter
+            .replacements
+            .iter()
+            .map(|(latex, _)| latex.len())
+            .max()
+            .unwrap_or(0);
+        StreamBuffer {
+            converter,
+            definitions: Definitions::new(),
+            pending: String::new(),
+            incomplete_char: Vec::new(),
+            checked: 0,
+            split: 0,
+            lookbehind: longest_replacement + 4,
+            depth: 0,
+            escaped: false,
+        }
+    }
+
+    /// Append more input, which may end with an incomplete UTF-8 character.
+    ///
+    /// On error, the input is rejected and the buffer is left unchanged.
+    pub(crate) fn push(&mut self, mut bytes: &[u8]) -> io::Result<()> {
+        let mut completed_char = [0u8; 4];
+        let mut completed_len = 0;
+        if !self.incomplete_char.is_empty() {
+            let mut char_bytes = self.incomplete_char.clone();
+            while let Err(error) = std::str::from_utf8(&char_bytes) {
+                if error.error_len().is_some() {
+                    return Err(invalid_utf8());
+                }
+                let Some((&next, rest)) = bytes.split_first() else {
+                    // still incomplete
+                    self.incomplete_char = char_bytes;
+                    return Ok(());
+                };
+                char_bytes.push(next);
+                bytes = rest;
+            }
+            completed_len = char_bytes.len();
+            completed_char[..completed_len].copy_from_slice(&char_bytes);
+        }
+        let (text, incomplete) = match std::str::from_utf8(bytes) {
+            Ok(text) => (text, &[][..]),
+            Err(error) if error.error_len().is_none() => {
+                let (valid, incomplete) = bytes.split_at(error.valid_up_to());
+                // already validated by from_utf8
+                (std::str::from_utf8(valid).unwrap(), incomplete)
+            }
+            Err(_) => return Err(invalid_utf8()),
+        };
+        self.pending
+            .push_str(std::str::from_utf8(&completed_char[..completed_len]).unwrap());
+        self.pending.push_str(text);
+        self.incomplete_char.clear();
+        self.incomplete_char.extend_from_slice(incomplete);
+        Ok(())
+    }
+
+    /// Search the newly added text for the last safe split point.
+    fn find_split(&mut self) {
+        if self.converter.macros.is_some()
+            || self.converter.math_delimiters.is_some()
+            || self.converter.parse_latex
+            || self.converter.combining_groups
+            || self.converter.script_fallback.converts_groups()
+            || self.converter.passes.has_custom()
+        {
+            self.find_macro_split();
+            return;
+        }
+        let bytes = self.pending.as_bytes();
+        for index in self.checked..bytes.len() {
+            if !bytes[index].is_ascii_whitespace() {
+                continue;
+            }
+            if index > 0 && bytes[index - 1] == b'\\' {
+                // could be an escaped combining mark like `\ hat{`
+                continue;
+            }
+            let window_start = index.saturating_sub(self.lookbehind);
+            if memchr::memchr(b'{', &bytes[window_start..index]).is_none() {
+                self.split = index + 1;
+            }
+        }
+        self.checked = bytes.len();
+    }
+
+    /// Search the newly added text for the last blank line outside of braces,
+    /// which is the only safe split point for macro arguments.
+    ///
+    /// Math between delimiters never contains a blank line,
+    /// so without macros any blank line is safe.
+    fn find_macro_split(&mut self) {
+        let braces_matter = self.converter.macros.is_some()
+            || self.converter.parse_latex
+            || self.converter.combining_groups
+            || self.converter.script_fallback.converts_groups()
+            || self.converter.passes.has_custom();
+        let bytes = self.pending.as_bytes();
+        for index in self.checked..bytes.len() {
+            let byte = bytes[index];
+            if self.escaped {
+                self.escaped = false;
+                continue;
+            }
+            match byte {
+                b'\\' => self.escaped = true,
+                b'{' => self.depth += 1,
+                b'}' => self.depth = self.depth.saturating_sub(1),
+                b'\n' if self.depth == 0 || !braces_matter => {
+                    let line_start = bytes[..index]
+                        .iter()
+                        .rposition(|&b| !matches!(b, b' ' | b'\t' | b'\r'))
+                        .map_or(0, |last| last + 1);
+                    if line_start > 0 && bytes[line_start - 1] == b'\n' {
+                        self.split = index + 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.checked = bytes.len();
+    }
+
+    fn convert(&mut self, text_end: usize, output: &mut Vec<u8>) {
+        let conversion =
+            self.converter
+                .convert(&self.pending[..text_end], false, &mut self.definitions);
+        output.extend_from_slice(conversion.output.as_bytes());
+    }
+
+    /// Convert all the input that can be safely converted, appending it to the output.
+    pub(crate) fn convert_ready(&mut self, output: &mut Vec<u8>) {
+        self.find_split();
+        if self.split == 0 {
+            return;
+        }
+        self.convert(self.split, output);
+        self.pending.drain(..self.split);
+        self.checked -= self.split;
+        self.split = 0;
+    }
+
+    /// Convert all the remaining input, appending it to the output.
+    ///
+    /// Fails if the input ends with an incomplete UTF-8 character.
+    pub(crate) fn finish(&mut self, output: &mut Vec<u8>) -> io::Result<()> {
+        if !self.incomplete_char.is_empty() {
+            return Err(invalid_utf8());
+        }
+        self.convert(self.pending.len(), output);
+        self.pending.clear();
+        self.checked = 0;
+        self.split = 0;
+        Ok(())
+    }
+}
+
+fn invalid_utf8() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "stream did not contain valid UTF-8",
+    )
+}
+
+/// Converts LaTeX written to it, then writes the Unicode to an underlying writer.
+///
+/// Input is buffered until it can be converted identically to [`crate::replace`],
+/// so [`Write::flush`] only flushes the input that is ready.
+/// Call [`ReplaceWriter::finish`] to convert the remaining input.
+/// Dropping the writer also finishes the conversion,
+/// but ignores any errors.
+///
+/// # Splitting
+///
+/// The input is normally split after whitespace which is not part of a command or its argument.
+/// Some options of the [`Converter`] need more context,
+/// so the input is only split at blank lines outside of braces when using any of:
+///
+/// - [macros](crate::ConverterBuilder::macros),
+/// - [parsing](crate::ConverterBuilder::parse_latex) the input,
+/// - [combining groups](crate::ConverterBuilder::combining_groups),
+/// - a [script fallback](crate::ConverterBuilder::script_fallback) which keeps part of the group,
+/// - custom [passes](crate::Passes).
+///
+/// With only [math delimiters](crate::ConverterBuilder::math_delimiters),
+/// the input is split at any blank line.
+/// Each part is converted separately,
+/// so a command must not be separated from its arguments by a blank line.
+///
+/// ```
+/// use std::io::Write;
+/// use unicodeit::ReplaceWriter;
+/// let mut writer = ReplaceWriter::new(Vec::new());
+/// writer.write_all(br"\alp")?;
+/// writer.write_all(br"ha + \beta")?;
+/// let output = writer.finish()?;
+/// assert_eq!(output, "\u{3b1} + \u{3b2}".as_bytes());
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct ReplaceWriter<'a, W: Write> {
+    inner: Option<W>,
+    buffer: StreamBuffer<'a>,
+    /// Converted output that has not yet been written.
+    output: Vec<u8>,
+}
+
+impl<W: Write> ReplaceWriter<'static, W> {
+    /// Create a writer which converts using the default [`Converter`].
+    pub fn new(inner: W) -> Self {
+        ReplaceWriter::with_converter(&crate::converter::DEFAULT_CONVERTER, inner)
+    }
+}
+
+impl<'a, W: Write> ReplaceWriter<'a, W> {
+    /// Create a writer which converts using the specified [`Converter`].
+    pub fn with_converter(converter: &'a Converter, inner: W) -> Self {
+        ReplaceWriter {
+            inner: Some(inner),
+            buffer: StreamBuffer::new(converter),
+            output: Vec::new(),
+        }
+    }
+
+    /// Get a reference to the underlying writer.
+    #[inline]
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().expect("writer already finished")
+    }
+
+    /// Get a mutable reference to the underlying writer.
+    ///
+    /// Writing directly to the underlying writer may interleave with converted output.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.as_mut().expect("writer already finished")
+    }
+
+    fn write_output(&mut self) -> io::Result<()> {
+        let inner = self.inner.as_mut().expect("writer already finished");
+        let mut written = 0;
+        let result = loop {
+            if written >= self.output.len() {
+                break Ok(());
+            }
+            match inner.write(&self.output[written..]) {
+                Ok(0) => {
+                    break Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "failed to write converted output",
+                    ));
+                }
+                Ok(amount) => written += amount,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => break Err(error),
+            }
+        };
+        self.output.drain(..written);
+        result
+    }
+
+    fn do_finish(&mut self) -> io::Result<()> {
+        self.buffer.finish(&mut self.output)?;
+        self.write_output()?;
+        self.get_mut().flush()
+    }
+
+    /// Convert and write all the remaining input, returning the underlying writer.
+    ///
+    /// Fails if the input ends with an incomplete UTF-8 character.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.do_finish()?;
+        Ok(self.inner.take().unwrap())
+    }
+}
+
+impl<W: Write> Write for ReplaceWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // write any previous output before accepting more input
+        self.write_output()?;
+        self.buffer.push(buf)?;
+        self.buffer.convert_ready(&mut self.output);
+        // the input has been accepted, so any error is reported on the next write
+        let _ = self.write_output();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_output()?;
+        self.get_mut().flush()
+    }
+}
+
+impl<W: Write> Drop for ReplaceWriter<'_, W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            // errors are ignored, like in std::io::BufWriter
+            let _ = self.do_finish();
+        }
+    }
+}
+
+/// The size of the chunks read by a [`ReplaceReader`].
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Reads LaTeX from an underlying reader, converting it to Unicode.
+///
+/// Input is buffered until it can be converted identically to [`crate::replace`],
+/// and split like for a [`ReplaceWriter`](ReplaceWriter#splitting).
+///
+/// ```
+/// use std::io::Read;
+/// use unicodeit::ReplaceReader;
+/// let mut reader = ReplaceReader::new(&br"\alpha + \beta"[..]);
+/// let mut output = String::new();
+/// reader.read_to_string(&mut output)?;
+/// assert_eq!(output, "\u{3b1} + \u{3b2}");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct ReplaceReader<'a, R: Read> {
+    inner: R,
+    buffer: StreamBuffer<'a>,
+    chunk: Box<[u8]>,
+    /// Converted output that has not yet been read.
+    output: Vec<u8>,
+    output_pos: usize,
+    eof: bool,
+}
+
+impl<R: Read> ReplaceReader<'static, R> {
+    /// Create a reader which converts using the default [`Converter`].
+    pub fn new(inner: R) -> Self {
+        ReplaceReader::with_converter(&crate::converter::DEFAULT_CONVERTER, inner)
+    }
+}
+
+impl<'a, R: Read> ReplaceReader<'a, R> {
+    /// Create a reader which converts using the specified [`Converter`].
+    pub fn with_converter(converter: &'a Converter, inner: R) -> Self {
+        ReplaceReader {
+            inner,
+            buffer: StreamBuffer::new(converter),
+            chunk: vec![0; READ_CHUNK_SIZE].into_boxed_slice(),
+            output: Vec::new(),
+            output_pos: 0,
+            eof: false,
+        }
+    }
+
+    /// Get a reference to the underlying reader.
+    #[inline]
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying reader.
+    ///
+    /// Reading directly from the underlying reader will skip input.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwrap this reader, returning the underlying reader.
+    ///
+    /// Any buffered input or output is lost.
+    #[inline]
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for ReplaceReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.output_pos < self.output.len() {
+                let available = &self.output[self.output_pos..];
+                let amount = available.len().min(buf.len());
+                buf[..amount].copy_from_slice(&available[..amount]);
+                self.output_pos += amount;
+                return Ok(amount);
+            }
+            self.output.clear();
+            self.output_pos = 0;
+            if self.eof {
+                return Ok(0);
+            }
+            let amount = match self.inner.read(&mut self.chunk) {
+                Ok(amount) => amount,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error),
+            };
+            if amount == 0 {
+                self.eof = true;
+                self.buffer.finish(&mut self.output)?;
+            } else {
+                self.buffer.push(&self.chunk[..amount])?;
+                self.buffer.convert_ready(&mut self.output);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "futures-io")]
+mod async_io {
+    use super::{READ_CHUNK_SIZE, StreamBuffer};
+    use crate::Converter;
+    use futures_io::{AsyncRead, AsyncWrite};
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll, ready};
+
+    /// The asynchronous version of [`ReplaceWriter`](super::ReplaceWriter).
+    ///
+    /// Closing the writer converts the remaining input.
+    pub struct AsyncReplaceWriter<'a, W: AsyncWrite + Unpin> {
+        inner: W,
+        buffer: StreamBuffer<'a>,
+        /// Converted output that has not yet been written.
+        output: Vec<u8>,
+        finished: bool,
+    }
+
+    impl<W: AsyncWrite + Unpin> AsyncReplaceWriter<'static, W> {
+        /// Create a writer which converts using the default [`Converter`].
+        pub fn new(inner: W) -> Self {
+            AsyncReplaceWriter::with_converter(&crate::converter::DEFAULT_CONVERTER, inner)
+        }
+    }
+
+    impl<'a, W: AsyncWrite + Unpin> AsyncReplaceWriter<'a, W> {
+        /// Create a writer which converts using the specified [`Converter`].
+        pub fn with_converter(converter: &'a Converter, inner: W) -> Self {
+            AsyncReplaceWriter {
+                inner,
+                buffer: StreamBuffer::new(converter),
+                output: Vec::new(),
+                finished: false,
+            }
+        }
+
+        /// Get a reference to the underlying writer.
+        #[inline]
+        pub fn get_ref(&self) -> &W {
+            &self.inner
+        }
+
+        /// Get a mutable reference to the underlying writer.
+        ///
+        /// Writing directly to the underlying writer may interleave with converted output.
+        #[inline]
+        pub fn get_mut(&mut self) -> &mut W {
+            &mut self.inner
+        }
+
+        /// Unwrap this writer, returning the underlying writer.
+        ///
+        /// Any input or output that has not been written is lost,
+        /// so the writer should be closed first.
+        #[inline]
+        pub fn into_inner(self) -> W {
+            self.inner
+        }
+
+        fn poll_write_output(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            while !self.output.is_empty() {
+                match ready!(Pin::new(&mut self.inner).poll_write(cx, &self.output)) {
+                    Ok(0) => {
+                        return Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::WriteZero,
+                            "failed to write converted output",
+                        )));
+                    }
+                    Ok(amount) => {
+                        self.output.drain(..amount);
+                    }
+                    Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                    Err(error) => return Poll::Ready(Err(error)),
+                }
+            }
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncReplaceWriter<'_, W> {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            let this = self.get_mut();
+            ready!(this.poll_write_output(cx))?;
+            this.buffer.push(buf)?;
+            this.buffer.convert_ready(&mut this.output);
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            let this = self.get_mut();
+            ready!(this.poll_write_output(cx))?;
+            Pin::new(&mut this.inner).poll_flush(cx)
+        }
+
+        fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            let this = self.get_mut();
+            if !this.finished {
+                this.buffer.finish(&mut this.output)?;
+                this.finished = true;
+            }
+            ready!(this.poll_write_output(cx))?;
+            Pin::new(&mut this.inner).poll_close(cx)
+        }
+    }
+
+    /// The asynchronous version of [`ReplaceReader`](super::ReplaceReader).
+    pub struct AsyncReplaceReader<'a, R: AsyncRead + Unpin> {
+        inner: R,
+        buffer: StreamBuffer<'a>,
+        chunk: Box<[u8]>,
+        /// Converted output that has not yet been read.
+        output: Vec<u8>,
+        output_pos: usize,
+        eof: bool,
+    }
+
+    impl<R: AsyncRead + Unpin> AsyncReplaceReader<'static, R> {
+        /// Create a reader which converts using the default [`Converter`].
+        pub fn new(inner: R) -> Self {
+            AsyncReplaceReader::with_converter(&crate::converter::DEFAULT_CONVERTER, inner)
+        }
+    }
+
+    impl<'a, R: AsyncRead + Unpin> AsyncReplaceReader<'a, R> {
+        /// Create a reader which converts using the specified [`Converter`].
+        pub fn with_converter(converter: &'a Converter, inner: R) -> Self {
+            AsyncReplaceReader {
+                inner,
+                buffer: StreamBuffer::new(converter),
+                chunk: vec![0; READ_CHUNK_SIZE].into_boxed_slice(),
+                output: Vec::new(),
+                output_pos: 0,
+                eof: false,
+            }
+        }
+
+        /// Get a reference to the underlying reader.
+        #[inline]
+        pub fn get_ref(&self) -> &R {
+            &self.inner
+        }
+
+        /// Get a mutable reference to the underlying reader.
+        ///
+        /// Reading directly from the underlying reader will skip input.
+        #[inline]
+        pub fn get_mut(&mut self) -> &mut R {
+            &mut self.inner
+        }
+
+        /// Unwrap this reader, returning the underlying reader.
+        ///
+        /// Any buffered input or output is lost.
+        #[inline]
+        pub fn into_inner(self) -> R {
+            self.inner
+        }
+    }
+
+    impl<R: AsyncRead + Unpin> AsyncRead for AsyncReplaceReader<'_, R> {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            let this = self.get_mut();
+            loop {
+                if this.output_pos < this.output.len() {
+                    let available = &this.output[this.output_pos..];
+                    let amount = available.len().min(buf.len());
+                    buf[..amount].copy_from_slice(&available[..amount]);
+                    this.output_pos += amount;
+                    return Poll::Ready(Ok(amount));
+                }
+                this.output.clear();
+                this.output_pos = 0;
+                if this.eof {
+                    return Poll::Ready(Ok(0));
+                }
+                let amount = match ready!(Pin::new(&mut this.inner).poll_read(cx, &mut this.chunk))
+                {
+                    Ok(amount) => amount,
+                    Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(error) => return Poll::Ready(Err(error)),
+                };
+                if amount == 0 {
+                    this.eof = true;
+                    this.buffer.finish(&mut this.output)?;
+                } else {
+                    this.buffer.push(&this.chunk[..amount])?;
+                    this.buffer.convert_ready(&mut this.output);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "futures-io")]
+pub use self::async_io::{AsyncReplaceReader, AsyncReplaceWriter};
@@ -0,0 +1,239 @@
+//! Parsing LaTeX math into a syntax tree.
+
+use crate::scripts::ScriptKind;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// The deepest nesting of groups and scripts which is parsed.
+///
+/// The content of more deeply nested groups is kept as [`NodeKind::Unparsed`],
+/// so the depth of the tree is bounded regardless of the input.
+const MAX_DEPTH: usize = 64;
+
+/// A node of LaTeX math, parsed by [`parse`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Node {
+    /// The kind of node, including its children.
+    pub kind: NodeKind,
+    /// The range of the input covered by the node.
+    pub span: Range<usize>,
+}
+
+/// The kind of a [`Node`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NodeKind {
+    /// A run of ordinary characters, like `a + 1`.
+    Text,
+    /// A control word like `\alpha`.
+    Command,
+    /// A control symbol like `\{`, `\\` or `\,`.
+    Symbol,
+    /// A group in braces like `{a + 1}`.
+    Group {
+        /// The nodes between the braces.
+        children: Vec<Node>,
+        /// Whether the group has a closing brace,
+        /// which is missing if the input ends first.
+        closed: bool,
+    },
+    /// A subscript or superscript like `_1`, `^\alpha` or `^{n+1}`.
+    Script {
+        /// Whether this is a subscript or a superscript.
+        kind: ScriptKind,
+        /// The argument of the script, which is a single character, command or group.
+        ///
+        /// This is missing if the script is followed by a closing brace or the end of the input.
+        argument: Option<Box<Node>>,
+    },
+    /// A comment from `%` to the end of the line, not including the line break.
+    Comment,
+    /// A closing brace without a matching opening brace.
+    UnmatchedClose,
+    /// The content of a group nested too deeply to be parsed, which is left unchanged.
+    Unparsed,
+}
+
+/// Parse LaTeX math into a sequence of nodes.
+///
+/// Parsing never fails: unbalanced braces are recovered from by
+/// closing any open groups at the end of the input,
+/// and keeping any unmatched closing braces as [`NodeKind::UnmatchedClose`].
+/// The spans of the nodes cover the whole input without gaps.
+///
+/// ```
+/// use unicodeit::{NodeKind, parse};
+/// let nodes = parse(r"\hat{ab}^2 % note");
+/// assert_eq!(nodes[0].kind, NodeKind::Command);
+/// assert!(matches!(&nodes[1].kind, NodeKind::Group { children, closed: true } if children.len() == 1));
+/// assert!(matches!(&nodes[2].kind, NodeKind::Script { argument: Some(_), .. }));
+/// assert_eq!(nodes[3].span, 10..11);
+/// assert_eq!(nodes[4].kind, NodeKind::Comment);
+/// ```
+pub fn parse(text: &str) -> Vec<Node> {
+    let mut parser = Parser { text, index: 0 };
+    let mut nodes = Vec::new();
+    while parser.index < text.len() {
+        if text.as_bytes()[parser.index] == b'}' {
+            nodes.push(Node {
+                kind: NodeKind::UnmatchedClose,
+                span: parser.index..parser.index + 1,
+            });
+            parser.index += 1;
+        } else {
+            nodes.extend(parser.node(0));
+        }
+    }
+    nodes
+}
+
+/// Parses a single input, keeping track of the current position.
+struct Parser<'a> {
+    text: &'a str,
+    index: usize,
+}
+
+impl Parser<'_> {
+    /// Parse the node at the current position, which must not be a closing brace.
+    ///
+    /// Returns `None` at the end of the input.
+    fn node(&mut self, depth: usize) -> Option<Node> {
+        let start = self.index;
+        let c = self.text[start..].chars().next()?;
+        let kind = match c {
+            '\\' => self.control(),
+            '{' => self.group(depth),
+            '^' | '_' => {
+                self.index += 1;
+                let kind = ScriptKind::from_control_char(c).unwrap();
+                let argument = self.argument(depth);
+                NodeKind::Script { kind, argument }
+            }
+            '%' => {
+                self.index = self.text[start..]
+                    .find('\n')
+                    .map_or(self.text.len(), |end| start + end);
+                NodeKind::Comment
+            }
+            _ => {
+                self.index = self.text[start..]
+                    .find(['\\', '{', '}', '^', '_', '%'])
+                    .map_or(self.text.len(), |end| start + end);
+                NodeKind::Text
+            }
+        };
+        Some(Node {
+            kind,
+            span: start..self.index,
+        })
+    }
+
+    /// Parse a control word like `\alpha` or a control symbol like `\{`.
+    fn control(&mut self) -> NodeKind {
+        let name_start = self.index + 1;
+        let name_len = self.text[name_start..]
+            .bytes()
+            .take_while(u8::is_ascii_alphabetic)
+            .count();
+        if name_len > 0 {
+            self.index = name_start + name_len;
+            return NodeKind::Command;
+        }
+        match self.text[name_start..].chars().next() {
+            Some(symbol) => {
+                self.index = name_start + symbol.len_utf8();
+                NodeKind::Symbol
+            }
+            // a backslash at the end of the input is just text
+            None => {
+                self.index = name_start;
+                NodeKind::Text
+            }
+        }
+    }
+
+    /// Parse a group in braces, starting at the opening brace.
+    fn group(&mut self, depth: usize) -> NodeKind {
+        self.index += 1;
+        if depth >= MAX_DEPTH {
+            return self.flat_group();
+        }
+        let mut children = Vec::new();
+        loop {
+            match self.text.as_bytes().get(self.index) {
+                Some(b'}') => {
+                    self.index += 1;
+                    return NodeKind::Group {
+                        children,
+                        closed: true,
+                    };
+                }
+                Some(_) => children.extend(self.node(depth + 1)),
+                None => {
+                    return NodeKind::Group {
+                        children,
+                        closed: false,
+                    };
+                }
+            }
+        }
+    }
+
+    /// Parse a group which is nested too deeply, keeping its content unparsed.
+    fn flat_group(&mut self) -> NodeKind {
+        let start = self.index;
+        let bytes = self.text.as_bytes();
+        let mut depth = 0usize;
+        let mut closed = false;
+        while let Some(&byte) = bytes.get(self.index) {
+            match byte {
+                b'\\' => self.index += 1,
+                b'{' => depth += 1,
+                b'}' if depth == 0 => {
+                    closed = true;
+                    break;
+                }
+                b'}' => depth -= 1,
+                _ => {}
+            }
+            self.index += 1;
+        }
+        // skipping an escaped character may have stopped in the middle of it
+        self.index = self.index.min(bytes.len());
+        while !self.text.is_char_boundary(self.index) {
+            self.index += 1;
+        }
+        let children = if self.index > start {
+            alloc::vec![Node {
+                kind: NodeKind::Unparsed,
+                span: start..self.index,
+            }]
+        } else {
+            Vec::new()
+        };
+        if closed {
+            self.index += 1;
+        }
+        NodeKind::Group { children, closed }
+    }
+
+    /// Parse the argument of a script, which is a single character, command or group.
+    fn argument(&mut self, depth: usize) -> Option<Box<Node>> {
+        let start = self.index;
+        let c = self.text[start..].chars().next()?;
+        let node = match c {
+            // a script cannot be the argument of another script
+            '}' | '^' | '_' | '%' => return None,
+            '\\' | '{' => self.node(depth + 1)?,
+            _ => {
+                self.index += c.len_utf8();
+                Node {
+                    kind: NodeKind::Text,
+                    span: start..self.index,
+                }
+            }
+        };
+        Some(Box::new(node))
+    }
+}
@@ -0,0 +1,278 @@
+//! Custom symbol tables, which can be loaded from TOML or JSON files.
+//!
+//! The schema has three optional tables,
+//! each mapping LaTeX to Unicode in the same way as the built-in tables:
+//!
+//! ```toml
+//! # Symbol commands, like `\alpha`.
+//! [replacements]
+//! '\R' = "ℝ"
+//! '\eps' = "ϵ"
+//!
+//! # Combining marks, like `\hat{a}`.
+//! # The value must be a combining character.
+//! [combining_marks]
+//! '\ring' = "̊"
+//!
+//! # Subscripts and superscripts, like `x_1`.
+//! # The key must be `_` or `^` followed by a single character.
+//! [sub_super_scripts]
+//! '^Q' = "ꟴ"
+//! ```
+//!
+//! The equivalent JSON is an object with the same keys:
+//! `{"replacements": {"\\R": "ℝ"}}`.
+
+use std::fmt::{self, Display, Formatter};
+
+/// Custom symbol tables, to be added to a [`Converter`](crate::Converter)
+/// using [`ConverterBuilder::add_tables`](crate::ConverterBuilder::add_tables).
+///
+/// Each table is a list of `(latex, unicode)` pairs,
+/// like the built-in tables.
+/// The order of entries does not matter,
+/// as they are sorted when the converter is built.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SymbolTables {
+    /// Symbol commands like `\R`.
+    #[serde(deserialize_with = "de::pairs")]
+    pub replacements: Vec<(String, String)>,
+    /// Combining marks like `\ring`.
+    #[serde(deserialize_with = "de::pairs")]
+    pub combining_marks: Vec<(String, String)>,
+    /// Subscripts and superscripts like `^Q`.
+    #[serde(deserialize_with = "de::pairs")]
+    pub sub_super_scripts: Vec<(String, String)>,
+}
+
+impl SymbolTables {
+    /// Parse and [validate](Self::validate) tables from TOML.
+    ///
+    /// ```
+    /// let tables = unicodeit::SymbolTables::from_toml(r#"
+    ///     [replacements]
+    ///     '\R' = "ℝ"
+    /// "#)?;
+    /// let converter = unicodeit::Converter::builder().add_tables(tables).build();
+    /// assert_eq!(converter.replace(r"\R"), "ℝ");
+    /// # Ok::<(), unicodeit::TableError>(())
+    /// ```
+    pub fn from_toml(text: &str) -> Result<Self, TableError> {
+        let tables: SymbolTables = toml::from_str(text).map_err(|error| {
+            let message = error.message();
+            TableError::Syntax(match error.span() {
+                // the same format as serde_json
+                Some(span) => {
+                    let before = &text[..span.start];
+                    let line = before.matches('\n').count() + 1;
+                    let column = before
+                        .rsplit('\n')
+                        .next()
+                        .map_or(0, |last| last.chars().count())
+                        + 1;
+                    format!("{message} at line {line} column {column}")
+                }
+                None => message.into(),
+            })
+        })?;
+        tables.validate()?;
+        Ok(tables)
+    }
+
+    /// Parse and [validate](Self::validate) tables from JSON.
+    pub fn from_json(text: &str) -> Result<Self, TableError> {
+        let tables: SymbolTables =
+            serde_json::from_str(text).map_err(|error| TableError::Syntax(error.to_string()))?;
+        tables.validate()?;
+        Ok(tables)
+    }
+
+    /// Check the tables are valid, returning the first problem found.
+    ///
+    /// Every key must be unique within its table.
+    /// Replacement and combining mark keys must start with a backslash,
+    /// and must not contain whitespace.
+    /// Combining mark keys must be a backslash followed by ASCII letters,
+    /// and their values must be a single combining character.
+    /// Sub/superscript keys must be `_` or `^` followed by a single character.
+    /// No value can be empty.
+    pub fn validate(&self) -> Result<(), TableError> {
+        let tables = [
+            (TableKind::Replacements, &self.replacements),
+            (TableKind::CombiningMarks, &self.combining_marks),
+            (TableKind::SubSuperScripts, &self.sub_super_scripts),
+        ];
+        for (table, entries) in tables {
+            let mut keys = std::collections::HashSet::new();
+            for (latex, unicode) in entries {
+                let error = |kind| TableError::Invalid {
+                    table,
+                    key: latex.clone(),
+                    kind,
+                };
+                if !keys.insert(latex) {
+                    return Err(error(InvalidEntry::DuplicateKey));
+                }
+                match table {
+                    TableKind::Replacements if !latex.starts_with('\\') => {
+                        return Err(error(InvalidEntry::MissingBackslash));
+                    }
+                    TableKind::CombiningMarks
+                        if !latex.strip_prefix('\\').is_some_and(|name| {
+                            !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphabetic())
+                        }) =>
+                    {
+                        return Err(error(if latex.starts_with('\\') {
+                            InvalidEntry::InvalidKey
+                        } else {
+                            InvalidEntry::MissingBackslash
+                        }));
+                    }
+                    TableKind::SubSuperScripts if !is_script_key(latex) => {
+                        return Err(error(InvalidEntry::InvalidKey));
+                    }
+                    _ => {}
+                }
+                if latex.contains(char::is_whitespace) {
+                    return Err(error(InvalidEntry::InvalidKey));
+                }
+                if unicode.is_empty() {
+                    return Err(error(InvalidEntry::EmptyValue));
+                }
+                if table == TableKind::CombiningMarks {
+                    let mut chars = unicode.chars();
+                    let is_combining = chars
+                        .next()
+                        .is_some_and(unicode_normalization::char::is_combining_mark)
+                        && chars.next().is_none();
+                    if !is_combining {
+                        return Err(error(InvalidEntry::NotCombining));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Determine if the key is a valid script like `_1`.
+fn is_script_key(latex: &str) -> bool {
+    let mut chars = latex.chars();
+    matches!(
+        (chars.next(), chars.next(), chars.next()),
+        (Some('_' | '^'), Some(base), None) if !base.is_whitespace()
+    )
+}
+
+/// One of the tables in [`SymbolTables`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TableKind {
+    /// [`SymbolTables::replacements`]
+    Replacements,
+    /// [`SymbolTables::combining_marks`]
+    CombiningMarks,
+    /// [`SymbolTables::sub_super_scripts`]
+    SubSuperScripts,
+}
+
+impl Display for TableKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TableKind::Replacements => "replacements",
+            TableKind::CombiningMarks => "combining_marks",
+            TableKind::SubSuperScripts => "sub_super_scripts",
+        })
+    }
+}
+
+/// The problem with an invalid entry in a [`SymbolTables`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum InvalidEntry {
+    /// The key appears more than once in the same table.
+    DuplicateKey,
+    /// The key of a replacement or combining mark does not start with a backslash.
+    MissingBackslash,
+    /// The key is otherwise malformed,
+    /// like a combining mark which is not a control word
+    /// or a script which is not a single character.
+    InvalidKey,
+    /// The value is empty.
+    EmptyValue,
+    /// The value of a combining mark is not a single combining character.
+    NotCombining,
+}
+
+impl Display for InvalidEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            InvalidEntry::DuplicateKey => "duplicate key",
+            InvalidEntry::MissingBackslash => "key does not start with a backslash",
+            InvalidEntry::InvalidKey => "invalid key",
+            InvalidEntry::EmptyValue => "empty value",
+            InvalidEntry::NotCombining => "value is not a combining character",
+        })
+    }
+}
+
+/// An error loading [`SymbolTables`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TableError {
+    /// The file could not be parsed, or does not match the schema,
+    /// with a message including the line and column of the error if known.
+    Syntax(String),
+    /// An entry in one of the tables is invalid.
+    Invalid {
+        /// The table containing the entry.
+        table: TableKind,
+        /// The key of the entry.
+        key: String,
+        /// The problem with the entry.
+        kind: InvalidEntry,
+    },
+}
+
+impl Display for TableError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TableError::Syntax(message) => write!(f, "invalid symbol table: {message}"),
+            TableError::Invalid { table, key, kind } => {
+                write!(f, "invalid entry {key:?} in `{table}`: {kind}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TableError {}
+
+mod de {
+    use serde::Deserializer;
+    use serde::de::{MapAccess, Visitor};
+    use std::fmt::{self, Formatter};
+
+    /// Deserialize a map as a list of pairs,
+    /// keeping duplicate keys so they can be reported.
+    pub(super) fn pairs<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<(String, String)>, D::Error> {
+        struct PairsVisitor;
+        impl<'de> Visitor<'de> for PairsVisitor {
+            type Value = Vec<(String, String)>;
+
+            fn expecting(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                f.write_str("a map from LaTeX to Unicode")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut pairs = Vec::with_capacity(map.size_hint().unwrap_or(0));
+                while let Some(entry) = map.next_entry()? {
+                    pairs.push(entry);
+                }
+                Ok(pairs)
+            }
+        }
+        deserializer.deserialize_map(PairsVisitor)
+    }
+}
@@ -0,0 +1,50 @@
+//! Tests that converting into an existing buffer does not allocate.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Counts the allocations made by each thread, since tests run in parallel.
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+#[test]
+fn replace_into_does_not_allocate() {
+    let mut out = String::with_capacity(1024);
+    // the default tables are built on first use
+    unicodeit::replace_into(r"\alpha", &mut out).unwrap();
+    for text in [
+        r"\alpha + \beta \mathbb{R}",
+        r"x^2 + y_{12} \sphat{n}",
+        r"\not\in \not=",
+        r"\hat{a} \bar{\hat{x}} \vec{\alpha}",
+        r"\hat{ab} \hat{a \tilde{",
+        r"\unknown{x}",
+    ] {
+        out.clear();
+        let before = allocations();
+        unicodeit::replace_into(text, &mut out).unwrap();
+        assert_eq!(allocations(), before, "{text:?}");
+        assert_eq!(out, unicodeit::replace(text));
+    }
+}
@@ -0,0 +1,223 @@
+//! Tests for applying combining marks to groups and grapheme clusters, and converting wide accents.
+#![cfg(feature = "std")]
+
+use common::assert_streams_like_replace;
+use unicodeit::{Converter, Token};
+
+mod common;
+
+fn groups() -> Converter {
+    Converter::builder().combining_groups(true).build()
+}
+
+#[test]
+fn every_character() {
+    let converter = groups();
+    assert_eq!(converter.replace(r"\overline{AB}"), "A\u{305}B\u{305}");
+    assert_eq!(
+        converter.replace(r"\vec{\alpha x} = 0"),
+        "\u{3b1}\u{20d7} x\u{20d7} = 0"
+    );
+    // whitespace is left unmarked
+    assert_eq!(converter.replace(r"\hat{a b}"), "a\u{302} b\u{302}");
+    assert_eq!(
+        converter.replace(r"\hat{\dot{ab}}"),
+        "a\u{307}\u{302}b\u{307}\u{302}"
+    );
+    assert_eq!(Converter::default().replace(r"\hat{ab}"), r"\hat{ab}");
+}
+
+#[test]
+fn single_tokens_unchanged() {
+    let converter = groups();
+    for text in [
+        r"\hat{a}",
+        r"\dot{\alpha}",
+        r"\hat{\dot{a}}",
+        r"\breve{}",
+        r"\hat{a",
+    ] {
+        assert_eq!(converter.replace(text), unicodeit::replace(text));
+    }
+}
+
+#[test]
+fn invalid_groups() {
+    let converter = groups();
+    assert_eq!(converter.replace(r"\hat{ab"), r"\hat{ab");
+    assert_eq!(converter.replace(r"\hat{a{b}}"), r"\hat{a{b}}");
+    assert_eq!(converter.replace(r"\hat{a\dot{b}}"), "\\hat{ab\u{307}}");
+}
+
+#[test]
+fn wide_accents() {
+    let converter = Converter::builder().wide_accents(true).build();
+    assert_eq!(converter.replace(r"\widehat{a}"), "a\u{302}");
+    assert_eq!(converter.replace(r"\widetilde{\alpha}"), "\u{3b1}\u{303}");
+    assert_eq!(converter.replace(r"\overrightarrow{v}"), "v\u{20d7}");
+    assert_eq!(converter.replace(r"\cancel{0}"), "0\u{338}");
+    assert_eq!(converter.replace(r"\hat{a}"), "a\u{302}");
+    assert_ne!(unicodeit::replace(r"\widehat{a}"), "a\u{302}");
+
+    let converter = Converter::builder()
+        .wide_accents(true)
+        .combining_groups(true)
+        .build();
+    assert_eq!(
+        converter.replace(r"\underbrace{a+b}"),
+        "a\u{332}+\u{332}b\u{332}"
+    );
+    let converter = Converter::builder()
+        .wide_accents(true)
+        .parse_latex(true)
+        .build();
+    assert_eq!(converter.replace(r"\overbrace{xy}"), "x\u{305}y\u{305}");
+}
+
+#[test]
+fn wide_accents_with_custom_marks() {
+    let converter = Converter::builder()
+        .wide_accents(true)
+        .add_combining_mark(r"\hat", "\u{2c6}")
+        .add_combining_mark(r"\widehat", "\u{311}")
+        .build();
+    assert_eq!(converter.replace(r"\widehat{a}"), "a\u{311}");
+    assert_eq!(converter.replace(r"\widetilde{a}"), "a\u{303}");
+    let converter = Converter::builder()
+        .wide_accents(true)
+        .combining_marks(false)
+        .build();
+    assert_eq!(
+        converter.replace(r"\widetilde{a}"),
+        unicodeit::replace(r"\widetilde{a}")
+    );
+}
+
+#[test]
+fn tokens() {
+    let converter = groups();
+    let tokens: Vec<Token> = converter.tokens(r"x \hat{ab}").collect();
+    assert_eq!(
+        tokens,
+        [
+            Token::Literal("x "),
+            Token::CombiningGroup {
+                latex: r"\hat{ab}",
+                base: "ab".into(),
+                mark: "\u{302}".into(),
+                combined: "a\u{302}b\u{302}".into(),
+            },
+        ]
+    );
+    let text = r"\vec{\alpha_1 b} \hat{\dot{cd}}";
+    let converted: String = converter
+        .tokens(text)
+        .map(|token| token.unicode())
+        .collect();
+    assert_eq!(converted, converter.replace(text));
+}
+
+#[test]
+fn mapping_and_limits() {
+    let (output, map) = groups().replace_with_mapping(r"x \hat{ab} y");
+    assert_eq!(output, "x a\u{302}b\u{302} y");
+    assert_eq!(map.input_range(2..4), 2..10);
+    assert_eq!(map.output_range(10..12), 8..10);
+    let converter = Converter::builder()
+        .combining_groups(true)
+        .max_output_len(6)
+        .build();
+    assert_eq!(converter.replace(r"x \hat{ab}"), "x ");
+    assert!(converter.try_replace(r"x \hat{ab}").is_err());
+}
+
+#[test]
+fn streaming() {
+    let converter = groups();
+    let text = "\\overline{A B} x\n\\hat{a\nb}\n\n\\vec{xy}";
+    assert_streams_like_replace(&converter, text);
+}
+
+#[test]
+fn multi_codepoint_bases() {
+    for converter in [
+        Converter::default(),
+        groups(),
+        Converter::builder().parse_latex(true).build(),
+    ] {
+        assert_eq!(converter.replace("\\hat{e\u{301}}"), "e\u{301}\u{302}");
+        assert_eq!(
+            converter.replace("\\tilde{\\alpha\u{308}}"),
+            "\u{3b1}\u{308}\u{303}"
+        );
+        assert_eq!(converter.replace("\\vec{\\mathbf{v}}"), "\u{1d42f}\u{20d7}");
+        assert_eq!(
+            converter.replace("\\hat{\u{1f469}\u{200d}\u{1f4bb}}"),
+            "\u{1f469}\u{200d}\u{1f4bb}\u{302}"
+        );
+        assert_eq!(
+            converter.replace("\\bar{\u{1100}\u{1161}}"),
+            "\u{1100}\u{1161}\u{305}"
+        );
+    }
+    // the marks go after each cluster of a group
+    assert_eq!(
+        groups().replace("\\hat{e\u{301}b}"),
+        "e\u{301}\u{302}b\u{302}"
+    );
+    // two separate characters are not a single base
+    assert_eq!(Converter::default().replace(r"\hat{eb}"), r"\hat{eb}");
+    // pairs of regional indicators need more than the last character as context
+    let flag = "\u{1f1e9}\u{1f1ea}";
+    for (flags, expected) in [
+        (
+            format!("{}\u{1f1e9}", flag.repeat(5)),
+            format!("{flag}\u{302}").repeat(6),
+        ),
+        (
+            flag.repeat(5),
+            format!("\\hat{{{}}}", flag.repeat(5) + "\u{1f1ea}"),
+        ),
+    ] {
+        let converter = Converter::builder()
+            .add_replacement(r"\flags", flags)
+            .build();
+        assert_eq!(converter.replace("\\hat{\\flags\u{1f1ea}}"), expected);
+    }
+}
+
+#[test]
+fn stacked_marks() {
+    for converter in [
+        Converter::default(),
+        groups(),
+        Converter::builder().parse_latex(true).build(),
+    ] {
+        assert_eq!(converter.replace(r"\dot{\hat{a}}"), "a\u{302}\u{307}");
+        assert_eq!(converter.replace(r"\hat{\dot{a}}"), "a\u{307}\u{302}");
+        assert_eq!(
+            converter.replace("\\vec{\\dot{e\u{301}}}"),
+            "e\u{301}\u{307}\u{20d7}"
+        );
+    }
+    assert_eq!(
+        Converter::builder()
+            .parse_latex(true)
+            .build()
+            .replace("\\hat e\u{301}x"),
+        "e\u{301}\u{302}x"
+    );
+}
+
+#[test]
+fn cluster_tokens() {
+    let tokens: Vec<Token> = unicodeit::tokens("\\hat{e\u{301}}").collect();
+    assert_eq!(
+        tokens,
+        [Token::Combining {
+            latex: "\\hat{e\u{301}}",
+            base: "e\u{301}".into(),
+            mark: "\u{302}".into(),
+        }]
+    );
+}
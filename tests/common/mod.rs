@@ -1,7 +1,35 @@
 //! Common test code
+// not every test uses every helper
+#![allow(dead_code)]
+
+#[cfg(feature = "std")]
+use std::io::Write;
+#[cfg(feature = "std")]
+use unicodeit::{Converter, ReplaceWriter};
 
 pub type ReplaceFunc = fn(&str) -> String;
 
+/// Convert the text by concatenating its tokens.
+pub fn replace_tokens(text: &str) -> String {
+    unicodeit::tokens(text)
+        .map(|token| token.unicode())
+        .collect()
+}
+
+/// Check that streaming the text gives the same output as converting it at once,
+/// wherever the input is split.
+#[cfg(feature = "std")]
+pub fn assert_streams_like_replace(converter: &Converter, text: &str) {
+    let expected = converter.replace(text);
+    for split in 0..=text.len() {
+        let mut writer = ReplaceWriter::with_converter(converter, Vec::new());
+        writer.write_all(&text.as_bytes()[..split]).unwrap();
+        writer.write_all(&text.as_bytes()[split..]).unwrap();
+        let output = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert_eq!(output, expected, "split at {split}");
+    }
+}
+
 #[macro_export]
 macro_rules! assert_func {
     ($replace:expr) => {
@@ -32,6 +60,23 @@ macro_rules! declare_tests {
                         }
                     }
                 }
+
+                #[test]
+                #[cfg_attr(not(feature = "optimized-impl"), ignore)]
+                fn [<test_optimized_ $name>]() {
+                    cfg_if::cfg_if! {
+                        if #[cfg(feature = "optimized-impl")] {
+                            [<do_test_ $name>](unicodeit::replace_optimized)
+                        } else {
+                            unreachable!("feature disabled")
+                        }
+                    }
+                }
+
+                #[test]
+                fn [<test_tokens_ $name>]() {
+                    [<do_test_ $name>]($crate::common::replace_tokens)
+                }
             )*
         }
     };
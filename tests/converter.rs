@@ -0,0 +1,167 @@
+//! Tests for configuring the phases of a [`Converter`].
+#![cfg(feature = "std")]
+
+use std::panic::{RefUnwindSafe, UnwindSafe};
+use unicodeit::{Converter, ConverterBuilder};
+
+#[test]
+fn send_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Converter>();
+}
+
+#[test]
+fn unwind_safe() {
+    fn assert_unwind_safe<T: UnwindSafe + RefUnwindSafe>() {}
+    assert_unwind_safe::<Converter>();
+    assert_unwind_safe::<ConverterBuilder>();
+}
+
+#[test]
+fn default_matches_replace() {
+    let converter = Converter::default();
+    for text in [
+        r"\alpha + \beta",
+        r"\not\in",
+        r"\hat{a} x_{12} \mathbb{R}",
+        r"\breve{",
+    ] {
+        assert_eq!(converter.replace(text), unicodeit::replace(text));
+    }
+}
+
+#[test]
+fn disable_not_rewrite() {
+    let converter = Converter::builder().not_rewrite(false).build();
+    assert_eq!(converter.replace(r"\not\in"), "\u{338}\u{2208}");
+}
+
+#[test]
+fn disable_combining_marks() {
+    let converter = Converter::builder().combining_marks(false).build();
+    assert_eq!(converter.replace(r"\dot{a}"), r"\dot{a}");
+    assert_eq!(converter.replace(r"\breve{}"), "\u{2d8}");
+    assert_eq!(converter.replace(r"\alpha"), "\u{3b1}");
+}
+
+#[test]
+fn disable_replacements() {
+    let converter = Converter::builder().replacements(false).build();
+    assert_eq!(converter.replace(r"\alpha x^2"), r"\alpha x²");
+    assert_eq!(converter.replace(r"\dot{a}"), "a\u{307}");
+}
+
+#[test]
+fn disable_math_alphabets() {
+    let converter = Converter::builder().math_alphabets(false).build();
+    assert_eq!(converter.replace(r"\mathbb{R}"), r"\mathbb{R}");
+    assert_eq!(converter.replace(r"\mathfrak{g}"), r"\mathfrak{g}");
+    assert_eq!(converter.replace(r"\alpha"), "\u{3b1}");
+}
+
+#[test]
+fn disable_script_groups() {
+    let converter = Converter::builder().script_groups(false).build();
+    assert_eq!(converter.replace("a_{12}"), "a_{12}");
+    assert_eq!(converter.replace("a_1"), "a₁");
+}
+
+#[test]
+fn disable_sub_super_scripts() {
+    let converter = Converter::builder().sub_super_scripts(false).build();
+    assert_eq!(converter.replace("a_{12} a^2"), "a_{12} a^2");
+    assert_eq!(converter.replace(r"\alpha"), "\u{3b1}");
+}
+
+#[test]
+fn custom_replacements() {
+    let converter = Converter::builder()
+        .add_replacement(r"\R", "\u{211d}")
+        .add_replacement(r"\eps", "\u{3f5}")
+        // contains the built-in `\alpha`, so must be replaced first
+        .add_replacement(r"\alphabet", "\u{2135}")
+        .build();
+    assert_eq!(
+        converter.replace(r"\R \Rightarrow \Re \eps \epsilon \alphabet \alpha"),
+        "\u{211d} \u{21d2} \u{211c} \u{3f5} \u{3b5} \u{2135} \u{3b1}"
+    );
+    assert_eq!(converter.replace(r"\ket"), r"\ket");
+}
+
+#[test]
+fn custom_override() {
+    let converter = Converter::builder()
+        .add_replacement(r"\epsilon", "\u{3f5}")
+        .add_replacement(r"\R", "R")
+        .add_replacement(r"\R", "\u{211d}")
+        .build();
+    assert_eq!(converter.replace(r"\epsilon \R"), "\u{3f5} \u{211d}");
+    assert_eq!(unicodeit::replace(r"\epsilon"), "\u{3b5}");
+}
+
+#[test]
+fn custom_without_builtins() {
+    let converter = Converter::builder()
+        .replacements(false)
+        .combining_marks(false)
+        .add_replacement(r"\R", "\u{211d}")
+        .add_combining_mark(r"\ring", "\u{30a}")
+        .build();
+    assert_eq!(
+        converter.replace(r"\R \alpha \ring{a} \hat{a}"),
+        "\u{211d} \\alpha a\u{30a} \\hat{a}"
+    );
+}
+
+#[test]
+fn custom_combining_marks() {
+    let converter = Converter::builder()
+        .add_combining_mark(r"\ring", "\u{30a}")
+        .add_combining_mark(r"\hat", "\u{302}")
+        .build();
+    assert_eq!(converter.replace(r"\ring{\alpha}"), "\u{3b1}\u{30a}");
+    assert_eq!(converter.replace(r"\hat{a}"), "a\u{302}");
+    assert_eq!(converter.replace(r"\ring{"), r"\ring{");
+}
+
+#[test]
+#[should_panic = "invalid combining mark"]
+fn invalid_combining_mark() {
+    let _ = Converter::builder().add_combining_mark("ring", "\u{30a}");
+}
+
+#[test]
+fn custom_sub_super_scripts() {
+    let converter = Converter::builder()
+        .add_sub_super_script("^Q", "\u{a7f4}")
+        .add_sub_super_script("_2", "2")
+        .build();
+    assert_eq!(converter.replace("x^Q y_2 z^2"), "x\u{a7f4} y2 z\u{b2}");
+}
+
+#[test]
+fn replace_cow() {
+    use std::borrow::Cow;
+    let converter = Converter::default();
+    assert!(matches!(
+        converter.replace_cow("a + b"),
+        Cow::Borrowed("a + b")
+    ));
+    assert_eq!(converter.replace_cow("a - b"), "a \u{2212} b");
+    assert_eq!(converter.replace_cow("x^2"), "x\u{b2}");
+    // custom commands without a backslash
+    let converter = Converter::builder()
+        .add_replacement("->", "\u{2192}")
+        .build();
+    assert_eq!(converter.replace_cow("a -> b"), "a \u{2192} b");
+    assert!(matches!(unicodeit::replace_cow(""), Cow::Borrowed("")));
+}
+
+#[test]
+fn replace_into() {
+    let mut buffer = String::from("label: ");
+    unicodeit::replace_to_string(r"\alpha", &mut buffer);
+    unicodeit::replace_to_string(" and ", &mut buffer);
+    unicodeit::replace_into(r"\beta", &mut buffer).unwrap();
+    assert_eq!(buffer, "label: \u{3b1} and \u{3b2}");
+}
@@ -0,0 +1,125 @@
+//! Tests for converting only the math between delimiters.
+#![cfg(feature = "std")]
+
+use common::assert_streams_like_replace;
+use std::borrow::Cow;
+use unicodeit::{Converter, DiagnosticKind, MathDelimiters};
+
+mod common;
+
+fn converter() -> Converter {
+    Converter::builder()
+        .math_delimiters(MathDelimiters::new())
+        .build()
+}
+
+#[test]
+fn standard_delimiters() {
+    let converter = converter();
+    assert_eq!(converter.replace(r"$x_1$"), "x\u{2081}");
+    assert_eq!(converter.replace(r"$$\alpha$$"), "\u{3b1}");
+    assert_eq!(converter.replace(r"\(\beta\)"), "\u{3b2}");
+    assert_eq!(converter.replace(r"\[a \to b\]"), "a \u{2192} b");
+    assert_eq!(
+        converter.replace(r"if $a \in A$ then \(a^2 \geq 0\)."),
+        "if a \u{2208} A then a\u{b2} \u{2265} 0."
+    );
+}
+
+#[test]
+fn prose_unchanged() {
+    let converter = converter();
+    assert!(matches!(
+        converter.replace_cow("snake_case_name"),
+        Cow::Borrowed(_)
+    ));
+    for prose in [
+        "snake_case_name",
+        r"C:\not\bar",
+        "a^b -> c",
+        r"\alpha without delimiters",
+    ] {
+        assert_eq!(converter.replace(prose), prose);
+        assert_eq!(converter.try_replace(prose).unwrap(), prose);
+    }
+}
+
+#[test]
+fn escaped_delimiters() {
+    let converter = converter();
+    assert_eq!(converter.replace(r"costs \$5"), "costs $5");
+    assert_eq!(
+        converter.replace(r"\$5 and $\alpha$ and \$6"),
+        "$5 and \u{3b1} and $6"
+    );
+    assert_eq!(converter.replace(r"$a \$ b$"), "a $ b");
+    assert_eq!(converter.replace(r"\\(a\\)"), r"\\(a\\)");
+}
+
+#[test]
+fn unclosed() {
+    let converter = converter();
+    assert_eq!(converter.replace(r"costs $5"), "costs $5");
+    assert_eq!(converter.replace(r"\(\alpha"), r"\(\alpha");
+    assert_eq!(
+        converter.replace("$5 for\n\nthe $\\alpha$"),
+        "$5 for\n\nthe \u{3b1}"
+    );
+    assert_eq!(converter.replace("$\\alpha\n \n$"), "$\\alpha\n \n$");
+    assert_eq!(converter.replace("$\\alpha\n$"), "\u{3b1}\n");
+}
+
+#[test]
+fn custom_delimiters() {
+    let converter = Converter::builder()
+        .math_delimiters(MathDelimiters::empty().add("<<", ">>").add("<", ">"))
+        .build();
+    assert_eq!(
+        converter.replace(r"<\alpha> <<\beta>> $\gamma$"),
+        "\u{3b1} \u{3b2} $\\gamma$"
+    );
+    assert_eq!(converter.replace(r"\<a\>"), r"<a\>");
+}
+
+#[test]
+fn mapping() {
+    let (output, map) = converter().replace_with_mapping(r"a $\alpha$ \$b");
+    assert_eq!(output, "a \u{3b1} $b");
+    assert_eq!(map.output_range(0..2), 0..2);
+    assert_eq!(map.output_range(3..9), 2..4);
+    assert_eq!(map.output_range(11..13), 5..6);
+    assert_eq!(map.input_range(5..6), 11..13);
+}
+
+#[test]
+fn diagnostics_only_in_math() {
+    let converter = converter();
+    let text = r"C:\unknown and $\alpha + \rightarow$ and {";
+    let (output, diagnostics) = converter.replace_with_diagnostics(text);
+    assert_eq!(output, "C:\\unknown and \u{3b1} + \\rightarow and {");
+    assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+    assert_eq!(diagnostics[0].span, 25..35);
+    assert!(matches!(
+        diagnostics[0].kind,
+        DiagnosticKind::UnknownCommand { .. }
+    ));
+}
+
+#[test]
+fn output_limit() {
+    let converter = Converter::builder()
+        .math_delimiters(MathDelimiters::new())
+        .max_output_len(6)
+        .build();
+    assert_eq!(converter.replace(r"ab $\alpha\beta\gamma$ c"), "ab \u{3b1}");
+    assert_eq!(converter.replace(r"abcdefgh $\alpha$"), "abcdef");
+    assert!(converter.try_replace(r"abcdefgh $\alpha$").is_err());
+    assert_eq!(converter.try_replace(r"ab $\alpha$").unwrap(), "ab \u{3b1}");
+}
+
+#[test]
+fn streaming() {
+    let converter = converter();
+    let text = "costs \\$5 or $5, but\n\n$\\alpha_1$ and \\(\\beta\\)\n\nsnake_case $x\ny$";
+    assert_streams_like_replace(&converter, text);
+}
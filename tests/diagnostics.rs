@@ -0,0 +1,144 @@
+//! Tests for [`unicodeit::replace_with_diagnostics`].
+#![cfg(feature = "std")]
+
+use unicodeit::{Diagnostic, DiagnosticKind, ScriptKind, Severity};
+
+fn diagnostics(text: &str) -> Vec<Diagnostic> {
+    let (output, diagnostics) = unicodeit::replace_with_diagnostics(text);
+    assert_eq!(output, unicodeit::replace(text));
+    diagnostics
+}
+
+#[test]
+fn clean_input() {
+    for text in [
+        r"\alpha + \beta",
+        r"x_{12} + y^2",
+        r"\hat{a} \mathbb{R}",
+        r"\not\in",
+        r"a \\ b",
+        "plain text",
+    ] {
+        assert_eq!(diagnostics(text), vec![], "{text:?}");
+    }
+}
+
+#[test]
+fn unknown_command() {
+    let found = diagnostics(r"x \unknowncmd y");
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].span, 2..13);
+    assert_eq!(found[0].severity, Severity::Warning);
+    assert!(matches!(
+        &found[0].kind,
+        DiagnosticKind::UnknownCommand { name, .. } if name == r"\unknowncmd"
+    ));
+}
+
+#[test]
+fn typo_suggestions() {
+    let found = diagnostics(r"\brevee{}");
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].span, 0..7);
+    match &found[0].kind {
+        DiagnosticKind::UnknownCommand { name, suggestions } => {
+            assert_eq!(name, r"\brevee");
+            assert_eq!(suggestions[0], r"\breve");
+        }
+        kind => panic!("unexpected {kind:?}"),
+    }
+    assert!(found[0].to_string().contains(r"did you mean `\breve`"));
+}
+
+#[test]
+fn unknown_argument() {
+    let found = diagnostics(r"\mathbb{!}");
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].span, 0..10);
+    assert!(matches!(
+        &found[0].kind,
+        DiagnosticKind::UnknownCommand { name, .. } if name == r"\mathbb{!}"
+    ));
+}
+
+#[test]
+fn dangling_combining_mark() {
+    let found = diagnostics(r"a \breve");
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].span, 2..8);
+    assert_eq!(found[0].severity, Severity::Error);
+    assert_eq!(
+        found[0].kind,
+        DiagnosticKind::DanglingCombiningMark {
+            name: r"\breve".into()
+        }
+    );
+}
+
+#[test]
+fn unsupported_script() {
+    assert_eq!(
+        diagnostics("x^Q")[0].kind,
+        DiagnosticKind::UnsupportedScript {
+            kind: ScriptKind::Superscript,
+            unsupported: vec!['Q'],
+        }
+    );
+    let found = diagnostics("y_{1Q}");
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].span, 1..6);
+    assert_eq!(
+        found[0].kind,
+        DiagnosticKind::UnsupportedScript {
+            kind: ScriptKind::Subscript,
+            unsupported: vec!['Q'],
+        }
+    );
+    // expanded groups are reported as a whole
+    let found = diagnostics("y_{1,2}");
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].span, 1..7);
+    assert_eq!(
+        found[0].kind,
+        DiagnosticKind::UnsupportedScript {
+            kind: ScriptKind::Subscript,
+            unsupported: vec![','],
+        }
+    );
+    // nested groups are part of the group containing them
+    let found = diagnostics("x^{a^{Q}}");
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].span, 1..8);
+    assert_eq!(
+        found[0].kind,
+        DiagnosticKind::UnsupportedScript {
+            kind: ScriptKind::Superscript,
+            unsupported: vec!['^', '{', 'Q'],
+        }
+    );
+}
+
+#[test]
+fn long_malformed_input() {
+    // every unclosed group used to scan to the end of the text
+    for (unit, count) in [(r"\mathbb{a", 2), ("x^{Q", 1), ("^{", 1), (r"\foo{", 2)] {
+        let text = unit.repeat(20_000);
+        assert_eq!(diagnostics(&text).len(), count * 20_000, "{unit:?}");
+    }
+}
+
+#[test]
+fn unbalanced_braces() {
+    let found = diagnostics(r"\breve{");
+    assert!(found.contains(&Diagnostic {
+        span: 6..7,
+        severity: Severity::Error,
+        kind: DiagnosticKind::UnbalancedBrace { brace: '{' },
+    }));
+    let found = diagnostics("a}");
+    assert_eq!(found[0].span, 1..2);
+    assert_eq!(
+        found[0].kind,
+        DiagnosticKind::UnbalancedBrace { brace: '}' }
+    );
+}
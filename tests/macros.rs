@@ -0,0 +1,189 @@
+//! Tests for expanding [`Macros`] before converting.
+#![cfg(feature = "std")]
+
+use std::io::Write;
+use unicodeit::{Converter, DiagnosticKind, MacroLimit, Macros, ReplaceWriter};
+
+fn with_preamble(preamble: &str) -> Converter {
+    Converter::builder()
+        .macros(Macros::new().preamble(preamble))
+        .build()
+}
+
+#[test]
+fn newcommand() {
+    let converter = with_preamble(
+        r"
+        \newcommand{\R}{\mathbb{R}}
+        \newcommand\eps{\epsilon}
+        \newcommand{\norm}[1]{\Vert #1 \Vert}
+        \newcommand*{\inner}[2]{\langle #1, #2 \rangle}
+        ",
+    );
+    assert_eq!(
+        converter.replace(r"\norm{x} \in \R, \inner{\eps}y"),
+        "\u{2016} x \u{2016} \u{2208} \u{211d}, \u{3008} \u{3b5}, y \u{3009}"
+    );
+    // not an invocation of `\R`
+    assert_eq!(converter.replace(r"\Rightarrow"), "\u{21d2}");
+}
+
+#[test]
+fn optional_argument() {
+    let converter = with_preamble(r"\newcommand{\seq}[2][n]{#2_#1}");
+    assert_eq!(converter.replace(r"\seq{x}"), "x\u{2099}");
+    assert_eq!(converter.replace(r"\seq[2]{x}"), "x\u{2082}");
+}
+
+#[test]
+fn renewcommand_and_operators() {
+    let converter = with_preamble(
+        r"
+        \newcommand{\x}{a}
+        \renewcommand{\x}{\alpha}
+        \DeclareMathOperator{\tr}{tr}
+        \DeclareMathOperator*{\argmax}{arg\,max}
+        ",
+    );
+    assert_eq!(converter.replace(r"\tr \x"), "tr \u{3b1}");
+    assert_eq!(converter.replace(r"\argmax"), "arg\u{2009}max");
+}
+
+#[test]
+fn def() {
+    let converter = with_preamble(r"\def\pair#1#2{(#1, #2)}");
+    assert_eq!(
+        converter.replace(r"\pair\alpha{\beta}"),
+        "(\u{3b1}, \u{3b2})"
+    );
+    // delimited parameters are not supported
+    let converter = with_preamble(r"\def\x#1.{#1}");
+    assert_eq!(converter.replace(r"\x a."), r"\x a.");
+}
+
+#[test]
+fn definitions_in_text() {
+    let converter = with_preamble("");
+    assert_eq!(
+        converter.replace("\\newcommand{\\R}{\\mathbb{R}}\nx \\in \\R"),
+        "\nx \u{2208} \u{211d}"
+    );
+    // definitions only apply to the text they are in
+    assert_eq!(converter.replace(r"\R"), r"\R");
+    // disabled by default
+    assert_eq!(unicodeit::replace(r"\def\R{x} \R"), r"\def\R{x} \R");
+}
+
+#[test]
+fn nested_and_recursive() {
+    let converter = with_preamble(
+        r"
+        \newcommand{\inner}[1]{\langle #1 \rangle}
+        \newcommand{\self}[1]{\inner{#1, #1}}
+        ",
+    );
+    assert_eq!(
+        converter.replace(r"\self{\alpha}"),
+        "\u{3008} \u{3b1}, \u{3b1} \u{3009}"
+    );
+}
+
+#[test]
+fn limits() {
+    let macros = Macros::new();
+    let error = macros.expand(r"a \def\x{\x} \x b").unwrap_err();
+    assert_eq!(error.span, 13..15);
+    assert_eq!(error.name, r"\x");
+    assert_eq!(error.limit, MacroLimit::Depth);
+    let error = macros
+        .expand(r"\def\x{\x\x} \x")
+        .expect_err("exponential expansion");
+    assert_eq!(error.span, 13..15);
+    let error = macros
+        .clone()
+        .max_len(10)
+        .expand(r"\def\x{123456} \x\x")
+        .unwrap_err();
+    assert_eq!((error.span, error.limit), (17..19, MacroLimit::Length));
+    // nested expansions count towards the depth, even without recursion
+    assert_eq!(
+        macros
+            .max_depth(1)
+            .expand(r"\def\x{y}\def\y{\x} \y")
+            .unwrap_err()
+            .limit,
+        MacroLimit::Depth
+    );
+}
+
+#[test]
+fn unclosed_groups() {
+    let macros = Macros::new().preamble(r"\newcommand{\f}[1]{(#1)} \newcommand{\g}[2][a]{#1#2}");
+    for text in [r"\def\a{ \f{x", r"\newcommand{\b}[1]{", r"\newcommand{\b}"] {
+        assert_eq!(macros.expand(text).unwrap(), text);
+    }
+    // brackets within braces don't close an optional argument
+    assert_eq!(macros.expand(r"\g[b{]}").unwrap(), "a[b{]}");
+    assert_eq!(macros.expand(r"\g[{x]}y]z}").unwrap(), "{x]}yz}");
+    assert_eq!(macros.expand(r"\g[}]x").unwrap(), "a[}]x");
+    assert_eq!(
+        macros.expand(r"\f{\{} \g[a[b]c]{d}").unwrap(),
+        r"(\{) a[bc]{d}"
+    );
+    // every unclosed brace used to scan to the end of the text
+    for unit in [r"\def\a{", r"\newcommand{", r"\newcommand{\b}[1]{", r"\f{"] {
+        let text = unit.repeat(20_000);
+        assert_eq!(macros.expand(&text).unwrap(), text);
+    }
+    // and so did every unclosed bracket
+    let text = r"\g[".repeat(20_000);
+    assert_eq!(macros.expand(&text).unwrap(), "a[".repeat(20_000));
+}
+
+#[test]
+fn limit_diagnostics() {
+    let converter = with_preamble(r"\def\x{\x}");
+    let (output, diagnostics) = converter.replace_with_diagnostics(r"\alpha \x");
+    assert_eq!(output, "\u{3b1} \\x");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].span, 7..9);
+    assert_eq!(
+        diagnostics[0].kind,
+        DiagnosticKind::MacroLimitExceeded {
+            name: r"\x".into(),
+            limit: MacroLimit::Depth,
+        }
+    );
+    assert!(converter.try_replace(r"\x").is_err());
+}
+
+#[test]
+fn known_to_diagnostics() {
+    let converter = with_preamble(r"\newcommand{\R}{\mathbb{R}}");
+    let (_, diagnostics) =
+        converter.replace_with_diagnostics(r"\def\eps{\epsilon} \R \eps \unknown");
+    assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+    assert_eq!(diagnostics[0].span, 27..35);
+}
+
+#[test]
+fn mapping() {
+    let converter = with_preamble(r"\newcommand{\R}{\mathbb{R}}");
+    let (output, map) = converter.replace_with_mapping(r"x \in \R");
+    assert_eq!(output, "x \u{2208} \u{211d}");
+    assert_eq!(map.input_range(output.len() - 3..output.len()), 6..8);
+}
+
+#[test]
+fn streaming() {
+    let converter = with_preamble(r"\newcommand{\norm}[1]{\Vert #1 \Vert}");
+    let text = "\\def\\pair#1#2{(#1, #2)}\n\n\\norm{a b}\n\\pair\n{x}\n{y}\n\n\\pair a b";
+    let expected = converter.replace(text);
+    for split in 0..=text.len() {
+        let mut writer = ReplaceWriter::with_converter(&converter, Vec::new());
+        writer.write_all(&text.as_bytes()[..split]).unwrap();
+        writer.write_all(&text.as_bytes()[split..]).unwrap();
+        let output = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert_eq!(output, expected, "split at {split}");
+    }
+}
@@ -0,0 +1,84 @@
+//! Tests for mapping offsets between the input and output of a conversion.
+#![cfg(feature = "std")]
+
+use unicodeit::{OffsetMap, replace_with_mapping};
+
+/// Check the segments are contiguous and unchanged segments match exactly.
+fn check_invariants(input: &str, output: &str, map: &OffsetMap) {
+    let (mut input_pos, mut output_pos) = (0, 0);
+    for segment in map.segments() {
+        assert_eq!(segment.input.start, input_pos, "{map:?}");
+        assert_eq!(segment.output.start, output_pos, "{map:?}");
+        if segment.unchanged {
+            assert_eq!(
+                &input[segment.input.clone()],
+                &output[segment.output.clone()]
+            );
+        }
+        input_pos = segment.input.end;
+        output_pos = segment.output.end;
+    }
+    assert_eq!(input_pos, input.len());
+    assert_eq!(output_pos, output.len());
+}
+
+fn mapped(input: &str) -> (String, OffsetMap) {
+    let (output, map) = replace_with_mapping(input);
+    assert_eq!(output, unicodeit::replace(input));
+    check_invariants(input, &output, &map);
+    (output, map)
+}
+
+#[test]
+fn invariants() {
+    for input in [
+        "",
+        "plain text",
+        r"\alpha + \beta = \gamma",
+        r"a^{12} + cm_{-1} \mathbb{R}",
+        r"\dot{a} \hat{\alpha} \breve{} \breve{",
+        r"x_1 y^2 \not\subset \not\in",
+        r"x_{1} y_{2} z^{3} w^{ab}",
+    ] {
+        mapped(input);
+    }
+}
+
+#[test]
+fn replacements() {
+    let (output, map) = mapped(r"x = \alpha + \beta");
+    assert_eq!(output, "x = \u{3b1} + \u{3b2}");
+    assert_eq!(map.output_range(4..10), 4..6);
+    assert_eq!(map.output_range(13..18), 9..11);
+    assert_eq!(map.input_range(9..11), 13..18);
+    // an offset inside a command covers the whole symbol
+    assert_eq!(map.output_range(6..6), 4..6);
+    // unchanged text maps exactly
+    assert_eq!(map.output_range(10..13), 6..9);
+    assert_eq!(map.input_range(0..4), 0..4);
+}
+
+#[test]
+fn script_groups() {
+    let (output, map) = mapped("a_{12} + b");
+    assert_eq!(output, "a₁₂ + b");
+    assert_eq!(map.output_range(1..6), 1..7);
+    assert_eq!(map.input_range(output.len() - 1..output.len()), 9..10);
+}
+
+#[test]
+fn combining_marks() {
+    let (output, map) = mapped(r"\hat{a} + \dot{\alpha}");
+    assert_eq!(output, "a\u{302} + \u{3b1}\u{307}");
+    assert_eq!(map.output_range(0..7), 0..3);
+    assert_eq!(map.output_range(10..22), 6..10);
+    assert_eq!(map.input_range(3..6), 7..10);
+}
+
+#[test]
+fn not_rewrite() {
+    let (_, map) = mapped(r"x \not\in y");
+    assert_eq!(map.input_range(0..2), 0..2);
+    let symbol = map.output_range(2..9);
+    assert_eq!(map.input_range(symbol), 2..9);
+}
@@ -0,0 +1,120 @@
+//! Tests for converting only the text and math in Markdown.
+#![cfg(feature = "markdown")]
+
+use pulldown_cmark::{Event, Parser, Tag};
+use unicodeit::{Converter, Macros, MathDelimiters};
+
+#[test]
+fn text_and_math() {
+    assert_eq!(
+        unicodeit::replace_markdown("# Sets\n\nLet \\alpha \\in A, and *emphasize* \\beta."),
+        "# Sets\n\nLet \u{3b1} \u{2208} A, and *emphasize* \u{3b2}."
+    );
+    assert_eq!(
+        unicodeit::replace_markdown("inline $x_1$ and\n\n$$\\sum_i a_i$$\n"),
+        "inline x\u{2081} and\n\n\u{2211}\u{1d62} a\u{1d62}\n"
+    );
+    assert_eq!(
+        unicodeit::replace_markdown("- [link \\to](https://example.com/a_b)\n- | \\alpha |"),
+        "- [link \u{2192}](https://example.com/a_b)\n- | \u{3b1} |"
+    );
+}
+
+#[test]
+fn code_unchanged() {
+    for markdown in [
+        "`a_b \\n`",
+        "```\nsnake_case_name \\alpha\n```\n",
+        "~~~rust\nlet x_1 = \"\\n\";\n~~~",
+        "    indented_code \\alpha\n",
+    ] {
+        assert_eq!(unicodeit::replace_markdown(markdown), markdown);
+    }
+    assert_eq!(
+        unicodeit::replace_markdown("`a_b` and \\alpha\n\n```\n\\beta\n```"),
+        "`a_b` and \u{3b1}\n\n```\n\\beta\n```"
+    );
+}
+
+#[test]
+fn urls_and_html_unchanged() {
+    for markdown in [
+        "<https://example.com/snake_case>",
+        "<user_name@example.com>",
+        "<span title=\"a_b\">",
+        "<div>\n\\alpha_1\n</div>\n",
+        "[\\alpha]: https://example.com/a_b",
+        "---\ntitle: a_b\n---\n",
+    ] {
+        assert_eq!(unicodeit::replace_markdown(markdown), markdown);
+    }
+    // the text of shortcut and collapsed links is also their label
+    for markdown in [
+        "[a_1]\n\n[a_1]: http://x.com\n",
+        "[a_1][]\n\n[a_1]: http://x.com\n",
+    ] {
+        let output = unicodeit::replace_markdown(markdown);
+        assert_eq!(output, markdown);
+        let resolves = Parser::new(&output).any(|event| {
+            matches!(event, Event::Start(Tag::Link { dest_url, .. }) if &*dest_url == "http://x.com")
+        });
+        assert!(resolves, "{output:?}");
+    }
+    // bare URLs end at whitespace, so the surrounding text is still converted
+    assert_eq!(
+        unicodeit::replace_markdown("see https://x.com/a_1 and \\alpha_1, ftp://h/\\beta"),
+        "see https://x.com/a_1 and \u{3b1}\u{2081}, ftp://h/\\beta"
+    );
+    // only the tags of inline HTML are left unchanged
+    assert_eq!(
+        unicodeit::replace_markdown("<b title=\"a_b\">\\alpha</b>"),
+        "<b title=\"a_b\">\u{3b1}</b>"
+    );
+}
+
+#[test]
+fn escapes_unchanged() {
+    assert_eq!(unicodeit::replace_markdown(r"snake\_case"), r"snake\_case");
+    assert_eq!(unicodeit::replace_markdown(r"\\alpha"), r"\\alpha");
+    assert_eq!(unicodeit::replace_markdown(r"\$x_1\$"), "\\$x\u{2081}\\$");
+    assert_eq!(
+        unicodeit::replace_markdown("&amp;alpha \\\\_1"),
+        "&amp;alpha \\\\\u{2081}"
+    );
+}
+
+#[test]
+fn reproduces_document() {
+    let markdown = "> quoted \\alpha\n> across_lines\n\n1. one\n2. `two`\n\n| a | b |\n|---|---|\n| `c_d` | e |\n";
+    assert_eq!(
+        unicodeit::replace_markdown(markdown),
+        "> quoted \u{3b1}\n> across\u{2097}ines\n\n1. one\n2. `two`\n\n| a | b |\n|---|---|\n| `c_d` | e |\n"
+    );
+}
+
+#[test]
+fn converter_options() {
+    let converter = Converter::builder().macros(Macros::new()).build();
+    assert_eq!(
+        converter.replace_markdown("\\newcommand{\\R}{\\mathbb{R}}\n\n$x \\in \\R$"),
+        "\n\nx \u{2208} \u{211d}"
+    );
+    let converter = Converter::builder()
+        .math_delimiters(MathDelimiters::empty().add("<<", ">>"))
+        .build();
+    assert_eq!(
+        converter.replace_markdown("snake_case and <<\\alpha>> and $\\beta$"),
+        "snake_case and \u{3b1} and \u{3b2}"
+    );
+}
+
+#[test]
+fn length_limits() {
+    let converter = Converter::builder().max_output_len(10).build();
+    assert_eq!(
+        converter.replace_markdown("`code` $\\alpha\\beta$"),
+        "`code` \u{3b1}"
+    );
+    let converter = Converter::builder().max_input_len(4).build();
+    assert_eq!(converter.replace_markdown("`code`"), "");
+}
@@ -0,0 +1,83 @@
+//! Tests for normalizing the output of combining marks.
+#![cfg(feature = "normalization")]
+
+use unicodeit::{Converter, Normalization};
+
+fn normalized(normalization: Normalization) -> Converter {
+    Converter::builder().normalization(normalization).build()
+}
+
+#[test]
+fn nfc() {
+    let converter = normalized(Normalization::Nfc);
+    assert_eq!(converter.replace(r"\acute{e}"), "\u{e9}");
+    assert_eq!(converter.replace(r"\ddot{u} + \hat{a}"), "\u{fc} + \u{e2}");
+    assert_eq!(converter.replace(r"\not\in \not="), "\u{2209} \u{2260}");
+    // there is no precomposed character for these
+    assert_eq!(converter.replace(r"\vec{v}"), "v\u{20d7}");
+    assert_eq!(converter.replace(r"\dot{\hat{a}}"), "\u{e2}\u{307}");
+}
+
+#[test]
+fn nfd() {
+    let converter = normalized(Normalization::Nfd);
+    assert_eq!(converter.replace("\\hat{\u{e9}}"), "e\u{301}\u{302}");
+    assert_eq!(converter.replace(r"\acute{e}"), "e\u{301}");
+    assert_eq!(converter.replace(r"\not\in"), "\u{2208}\u{338}");
+}
+
+#[test]
+fn symbols_unchanged() {
+    for normalization in [Normalization::Nfc, Normalization::Nfd] {
+        let converter = normalized(normalization);
+        for text in [
+            r"\Angstrom",
+            r"\nexists",
+            r"\mathring{\mathrm{A}}",
+            "e\u{301}",
+        ] {
+            assert_eq!(converter.replace(text), unicodeit::replace(text));
+        }
+    }
+    let converter = normalized(Normalization::None);
+    assert_eq!(
+        converter.replace(r"\acute{e}"),
+        unicodeit::replace(r"\acute{e}")
+    );
+}
+
+#[test]
+fn other_options() {
+    let converter = Converter::builder()
+        .normalization(Normalization::Nfc)
+        .parse_latex(true)
+        .build();
+    assert_eq!(
+        converter.replace(r"\hat{ab} \not\in"),
+        "\u{e2}b\u{302} \u{2209}"
+    );
+    let converter = Converter::builder()
+        .normalization(Normalization::Nfc)
+        .combining_groups(true)
+        .build();
+    assert_eq!(converter.replace(r"\ddot{aou}"), "\u{e4}\u{f6}\u{fc}");
+}
+
+#[test]
+fn mapping_and_limits() {
+    let converter = normalized(Normalization::Nfc);
+    let (output, map) = converter.replace_with_mapping(r"x \acute{e} y");
+    assert_eq!(output, "x \u{e9} y");
+    assert_eq!(map.input_range(2..4), 2..11);
+    assert_eq!(map.output_range(11..13), 4..6);
+    let converter = Converter::builder()
+        .normalization(Normalization::Nfc)
+        .max_output_len(2)
+        .build();
+    assert_eq!(converter.replace(r"\acute{e}"), "\u{e9}");
+    let tokens: String = converter
+        .tokens(r"\acute{e}")
+        .map(|token| token.unicode())
+        .collect();
+    assert_eq!(tokens, "e\u{301}");
+}
@@ -0,0 +1,239 @@
+//! Tests for parsing LaTeX and converting by walking the syntax tree.
+#![cfg(feature = "std")]
+
+use common::assert_streams_like_replace;
+use unicodeit::{Converter, Node, NodeKind, ScriptKind, parse};
+
+mod common;
+
+fn converter() -> Converter {
+    Converter::builder().parse_latex(true).build()
+}
+
+/// Describe the nodes with the text they cover, so the tests are easier to read.
+fn describe<'a>(text: &'a str, nodes: &[Node]) -> Vec<(&'static str, &'a str)> {
+    nodes
+        .iter()
+        .map(|node| {
+            let kind = match node.kind {
+                NodeKind::Text => "text",
+                NodeKind::Command => "command",
+                NodeKind::Symbol => "symbol",
+                NodeKind::Group { closed: true, .. } => "group",
+                NodeKind::Group { closed: false, .. } => "unclosed group",
+                NodeKind::Script { .. } => "script",
+                NodeKind::Comment => "comment",
+                NodeKind::UnmatchedClose => "unmatched close",
+                NodeKind::Unparsed => "unparsed",
+                _ => unreachable!(),
+            };
+            (kind, &text[node.span.clone()])
+        })
+        .collect()
+}
+
+#[test]
+fn parse_nodes() {
+    let text = r"a + \alpha\{\\ {b}^2_{n} % c";
+    assert_eq!(
+        describe(text, &parse(text)),
+        [
+            ("text", "a + "),
+            ("command", r"\alpha"),
+            ("symbol", r"\{"),
+            ("symbol", r"\\"),
+            ("text", " "),
+            ("group", "{b}"),
+            ("script", "^2"),
+            ("script", "_{n}"),
+            ("text", " "),
+            ("comment", "% c"),
+        ]
+    );
+    assert!(parse("").is_empty());
+}
+
+#[test]
+fn parse_scripts() {
+    let text = r"x^\alpha_{(n+1)}^";
+    let nodes = parse(text);
+    let arguments: Vec<_> = nodes[1..]
+        .iter()
+        .map(|node| match &node.kind {
+            NodeKind::Script { kind, argument } => (
+                *kind,
+                argument
+                    .as_ref()
+                    .map(|argument| &text[argument.span.clone()]),
+            ),
+            _ => panic!("expected a script: {node:?}"),
+        })
+        .collect();
+    assert_eq!(
+        arguments,
+        [
+            (ScriptKind::Superscript, Some(r"\alpha")),
+            (ScriptKind::Subscript, Some("{(n+1)}")),
+            (ScriptKind::Superscript, None),
+        ]
+    );
+    // only the first character of text is the argument
+    assert_eq!(
+        describe("x^23", &parse("x^23"))[1..],
+        [("script", "^2"), ("text", "3")]
+    );
+}
+
+#[test]
+fn parse_unbalanced() {
+    let text = "}{a{b";
+    let nodes = parse(text);
+    assert_eq!(
+        describe(text, &nodes),
+        [("unmatched close", "}"), ("unclosed group", "{a{b")]
+    );
+    let NodeKind::Group { children, .. } = &nodes[1].kind else {
+        unreachable!()
+    };
+    assert_eq!(
+        describe(text, children),
+        [("text", "a"), ("unclosed group", "{b")]
+    );
+}
+
+#[test]
+fn parse_deeply_nested() {
+    let text = format!("{}x{}", "{".repeat(100_000), "}".repeat(100_000));
+    let mut nodes = parse(&text);
+    assert_eq!(nodes.len(), 1);
+    assert_eq!(nodes[0].span, 0..text.len());
+    let mut depth = 0;
+    while let [
+        Node {
+            kind: NodeKind::Group { children, .. },
+            ..
+        },
+    ] = &nodes[..]
+    {
+        nodes = children.clone();
+        depth += 1;
+    }
+    assert_eq!(depth, 65);
+    assert_eq!(describe(&text, &nodes)[0].0, "unparsed");
+    let text = "^{".repeat(100_000);
+    assert_eq!(parse(&text)[0].span, 0..text.len());
+    let converter = converter();
+    for text in [r"\hat{", "x^{", "{"] {
+        let text = text.repeat(100_000);
+        assert_eq!(converter.replace(&text), text);
+    }
+}
+
+#[test]
+fn convert_deeply_nested() {
+    let converter = converter();
+    // content nested too deeply is left unchanged, with the commands around it
+    for (open, close) in [(r"\hat{", "}"), ("x^{", "}"), (r"\hat{x_{", "}}")] {
+        for depth in [70, 2_000, 16_000] {
+            let text = format!("{}a{}", open.repeat(depth), close.repeat(depth));
+            let output = converter.replace(&text);
+            assert_eq!(output.len(), text.len(), "{open:?} * {depth}");
+            assert_eq!(output, text);
+        }
+    }
+    // shallower nesting is still converted
+    let text = format!("{}a{}", r"\hat{".repeat(3), "}".repeat(3));
+    assert_eq!(converter.replace(&text), "a\u{302}\u{302}\u{302}");
+}
+
+#[test]
+fn whole_commands() {
+    let converter = converter();
+    assert_eq!(converter.replace(r"\alpha\beta"), "\u{3b1}\u{3b2}");
+    assert_eq!(converter.replace(r"\alphax \alpha x"), "\\alphax \u{3b1} x");
+    assert_eq!(
+        converter.replace(r"\mathbb{R} \mathbb{R}x"),
+        "\u{211d} \u{211d}x"
+    );
+    assert_eq!(converter.replace(r"\sfrac{1}{2}"), "\u{bd}");
+    assert_eq!(
+        converter.replace(r"a-b \{ \not= 25\%"),
+        "a\u{2212}b { \u{2260} \u{2591}"
+    );
+    assert_eq!(converter.replace(r"\not\in"), "\u{2208}\u{338}");
+}
+
+#[test]
+fn combining_marks() {
+    let converter = converter();
+    assert_eq!(converter.replace(r"\hat{ab}"), "a\u{302}b\u{302}");
+    assert_eq!(converter.replace(r"\dot{\alpha}"), "\u{3b1}\u{307}");
+    assert_eq!(
+        converter.replace(r"\hat\alpha \hat a"),
+        "\u{3b1}\u{302} a\u{302}"
+    );
+    assert_eq!(converter.replace(r"\hat{\dot{a}}"), "a\u{307}\u{302}");
+    assert_eq!(converter.replace(r"\breve{}"), "\u{2d8}");
+    assert_eq!(converter.replace(r"\hat{a"), r"\hat{a");
+}
+
+#[test]
+fn scripts() {
+    let converter = converter();
+    assert_eq!(
+        converter.replace(r"x^{(n+1)}"),
+        "x\u{207d}\u{207f}\u{207a}\u{b9}\u{207e}"
+    );
+    assert_eq!(
+        converter.replace(r"x_{\beta\gamma} x^23"),
+        "x\u{1d66}\u{1d67} x\u{b2}3"
+    );
+    // the argument is converted even if it has no script
+    assert_eq!(converter.replace(r"x^{\alpha q}"), "x^{\u{3b1} q}");
+    assert_eq!(converter.replace(r"x_{a_1}"), "x_{a\u{2081}}");
+    assert_eq!(converter.replace("x^"), "x^");
+}
+
+#[test]
+fn unbalanced_braces() {
+    let converter = converter();
+    assert_eq!(converter.replace(r"}\alpha{\beta"), "}\u{3b1}{\u{3b2}");
+    assert_eq!(converter.replace(r"{\alpha}}"), "{\u{3b1}}}");
+}
+
+#[test]
+fn comments_unchanged() {
+    assert_eq!(
+        converter().replace("\\alpha % \\beta\n\\gamma"),
+        "\u{3b1} % \\beta\n\u{3b3}"
+    );
+}
+
+#[test]
+fn matches_replace_on_simple_input() {
+    let converter = converter();
+    for text in [r"\alpha + \beta", r"x^2 + y_1", r"\dot{a}", r"\mathcal{L}"] {
+        assert_eq!(converter.replace(text), unicodeit::replace(text));
+    }
+}
+
+#[test]
+fn mapping_and_limits() {
+    let (output, map) = converter().replace_with_mapping(r"a \hat{bc} d");
+    assert_eq!(output, "a b\u{302}c\u{302} d");
+    assert_eq!(map.output_range(2..9), 2..8);
+    assert_eq!(map.input_range(8..9), 10..11);
+    let converter = Converter::builder()
+        .parse_latex(true)
+        .max_output_len(5)
+        .build();
+    assert_eq!(converter.replace(r"{\alpha\beta\gamma}"), "{\u{3b1}\u{3b2}");
+    assert!(converter.try_replace(r"{\alpha\beta\gamma}").is_err());
+}
+
+#[test]
+fn streaming() {
+    let converter = converter();
+    let text = "\\hat{a\n b}^{12}\n\n\\alpha % \\beta\n\n{\\gamma\n\n}";
+    assert_streams_like_replace(&converter, text);
+}
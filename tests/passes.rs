@@ -0,0 +1,137 @@
+//! Tests for running custom [`Passes`] around the built-in passes.
+#![cfg(feature = "std")]
+
+use common::assert_streams_like_replace;
+use std::borrow::Cow;
+use unicodeit::{BuiltinPass, Converter, Macros, Pass, Passes};
+
+mod common;
+
+fn with_passes(passes: Passes) -> Converter {
+    Converter::builder().passes(passes).build()
+}
+
+/// Replace one piece of text with another, borrowing the text if it is not found.
+struct Rewrite(&'static str, &'static str);
+
+impl Pass for Rewrite {
+    fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if text.contains(self.0) {
+            Cow::Owned(text.replace(self.0, self.1))
+        } else {
+            Cow::Borrowed(text)
+        }
+    }
+}
+
+#[test]
+fn default_passes() {
+    let converter = with_passes(Passes::new());
+    for text in [r"\alpha + \beta", r"\hat{a} x_{12}", r"\not\in \mathbb{R}"] {
+        assert_eq!(converter.replace(text), unicodeit::replace(text));
+    }
+    assert_eq!(
+        format!("{:?}", Passes::default()),
+        "Passes { passes: [Macros, Symbols] }"
+    );
+}
+
+#[test]
+fn before_and_after() {
+    let converter = with_passes(
+        Passes::new()
+            .add_before(BuiltinPass::Symbols, Rewrite(r"\displaystyle", ""))
+            .add_before(BuiltinPass::Symbols, Rewrite(r"\mathrm{d}", "d"))
+            .add_after(BuiltinPass::Symbols, Rewrite("\u{2212}", "-")),
+    );
+    assert_eq!(
+        converter.replace(r"\displaystyle\int_0^1 x \mathrm{d}x - 1"),
+        "\u{222b}\u{2080}\u{b9} x dx - 1"
+    );
+}
+
+#[test]
+fn order() {
+    let passes = Passes::new()
+        .add_after(BuiltinPass::Symbols, |text: &str| format!("{text}1"))
+        .add_after(BuiltinPass::Symbols, |text: &str| format!("{text}2"))
+        .add_before(BuiltinPass::Macros, |text: &str| format!("{text}^"))
+        .custom(|text: &str| format!("{text}3"));
+    assert_eq!(
+        format!("{passes:?}"),
+        "Passes { passes: [Custom, Macros, Symbols, Custom, Custom, Custom] }"
+    );
+    // the script is added before the symbols are converted
+    assert_eq!(with_passes(passes).replace("x"), "x^123");
+}
+
+#[test]
+fn reordered_builtins() {
+    let macros = Macros::new().preamble(r"\newcommand{\half}{\frac12}");
+    let converter = Converter::builder()
+        .macros(macros.clone())
+        .passes(Passes::empty().builtin(BuiltinPass::Symbols))
+        .build();
+    // the macro is never expanded, so it is converted like any other text
+    assert_eq!(
+        converter.replace(r"\half \alpha"),
+        unicodeit::replace(r"\half \alpha")
+    );
+    let converter = Converter::builder()
+        .macros(macros)
+        .passes(
+            Passes::empty()
+                .builtin(BuiltinPass::Macros)
+                .custom(Rewrite(r"\frac12", r"\frac{1}{2}"))
+                .builtin(BuiltinPass::Symbols),
+        )
+        .build();
+    assert_eq!(
+        converter.replace(r"\half"),
+        unicodeit::replace(r"\frac{1}{2}")
+    );
+    assert_eq!(with_passes(Passes::empty()).replace(r"\alpha"), r"\alpha");
+}
+
+#[test]
+#[should_panic = "added twice"]
+fn builtin_twice() {
+    let _ = Passes::new().builtin(BuiltinPass::Macros);
+}
+
+#[test]
+#[should_panic = "not in the list"]
+fn missing_builtin() {
+    let _ = Passes::empty().add_after(BuiltinPass::Symbols, Rewrite("a", "b"));
+}
+
+#[test]
+fn mapping() {
+    let converter =
+        with_passes(Passes::new().add_before(BuiltinPass::Symbols, Rewrite(r"\displaystyle ", "")));
+    let (output, map) = converter.replace_with_mapping(r"a \displaystyle \alpha b");
+    assert_eq!(output, "a \u{3b1} b");
+    assert_eq!(map.output_range(0..2), 0..2);
+    assert_eq!(map.input_range(2..4), 2..22);
+    assert_eq!(map.input_range(4..6), 22..24);
+}
+
+#[test]
+fn output_limit() {
+    let converter = Converter::builder()
+        .passes(Passes::new().custom(|text: &str| text.repeat(3)))
+        .max_output_len(5)
+        .build();
+    assert_eq!(converter.replace(r"\alpha"), "\u{3b1}\u{3b1}");
+    assert!(converter.try_replace(r"\alpha").is_err());
+    assert_eq!(converter.replace("a"), "aaa");
+}
+
+#[test]
+fn streaming() {
+    let converter =
+        with_passes(Passes::new().add_before(BuiltinPass::Symbols, Rewrite(r"\mathrm{d} x", "dx")));
+    let text = "\\int f \\mathrm{d} x\n\n\\alpha \\mathrm{d} x";
+    assert_eq!(converter.replace(text), "\u{222b} f dx\n\n\u{3b1} dx");
+    assert_streams_like_replace(&converter, text);
+}
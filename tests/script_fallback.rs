@@ -0,0 +1,170 @@
+//! Tests for converting groups of scripts with unsupported characters using a [`ScriptFallback`].
+#![cfg(feature = "std")]
+
+use common::assert_streams_like_replace;
+use unicodeit::{Converter, DiagnosticKind, ScriptFallback, ScriptKind, Token};
+
+mod common;
+
+fn with_fallback(fallback: ScriptFallback) -> Converter {
+    Converter::builder().script_fallback(fallback).build()
+}
+
+#[test]
+fn unchanged() {
+    let converter = with_fallback(ScriptFallback::Unchanged);
+    for text in [r"x^{Q}", r"a_{bc}", r"x^{2Q} + y_{12}", r"x^{\alpha Q_1}"] {
+        assert_eq!(converter.replace(text), unicodeit::replace(text));
+    }
+}
+
+#[test]
+fn prefix() {
+    let converter = with_fallback(ScriptFallback::Prefix);
+    assert_eq!(converter.replace("x^{2Q}"), "x\u{b2}^{Q}");
+    assert_eq!(converter.replace("a_{1bc}"), "a\u{2081}_{bc}");
+    assert_eq!(converter.replace(r"x^{2\beta Q2}"), "x\u{b2}\u{1d5d}^{ Q2}");
+    // nothing can be converted
+    assert_eq!(converter.replace("a_{bc}"), "a_{bc}");
+    assert_eq!(converter.replace(r"x^{\alpha Q}"), "x^{\u{3b1} Q}");
+    // groups which can be expanded are unaffected
+    assert_eq!(converter.replace("x^{12}"), "x\u{b9}\u{b2}");
+}
+
+#[test]
+fn parenthesized() {
+    let converter = with_fallback(ScriptFallback::Parenthesized);
+    assert_eq!(converter.replace("x^{Q}"), "x^(Q)");
+    assert_eq!(converter.replace("a_{bc}"), "a_(bc)");
+    assert_eq!(
+        converter.replace(r"a_{b c} + x^{\alpha Q}"),
+        "a_(b c) + x^(\u{3b1} Q)"
+    );
+    assert_eq!(converter.replace("x^{12}"), "x\u{b9}\u{b2}");
+    // single scripts, empty groups and nested groups are left unchanged
+    assert_eq!(converter.replace("x^Q"), "x^Q");
+    assert_eq!(converter.replace("x^{}"), "x^{}");
+    assert_eq!(converter.replace("x^{Q_1}"), "x^{Q\u{2081}}");
+    assert_eq!(converter.replace("x^{Q{1}}"), "x^{Q{1}}");
+    assert_eq!(converter.replace(r"x^{\hat{a}Q}"), "x^{a\u{302}Q}");
+}
+
+#[test]
+fn closest() {
+    let converter = with_fallback(ScriptFallback::Closest);
+    assert_eq!(converter.replace("x^{Q}"), "x\u{a7f4}");
+    assert_eq!(
+        converter.replace("x^{2Y} + x^q"),
+        "x\u{b2}\u{107b2} + x\u{107a5}"
+    );
+    assert_eq!(converter.replace("T_{AX}"), "T\u{2090}\u{2093}");
+    // there is no alternative for every character
+    assert_eq!(converter.replace("A_{Sb}"), "A_{Sb}");
+
+    let converter = Converter::builder()
+        .script_fallback(ScriptFallback::Closest)
+        .script_groups(false)
+        .add_sub_super_script("^Q", "Q")
+        .build();
+    assert_eq!(converter.replace("x^Q + x^S"), "xQ + x\u{2e2}");
+    assert_eq!(converter.replace("x^{S}"), "x^{S}");
+    let converter = Converter::builder()
+        .script_fallback(ScriptFallback::Closest)
+        .sub_super_scripts(false)
+        .build();
+    assert_eq!(converter.replace("x^Q"), "x^Q");
+}
+
+#[test]
+fn custom_scripts_in_groups() {
+    let builder = Converter::builder().add_sub_super_script("_Q", "q");
+    assert_eq!(builder.clone().build().replace("x_{1Q}"), "x_{1Q}");
+    let converter = builder.script_fallback(ScriptFallback::Prefix).build();
+    assert_eq!(converter.replace("x_{1Q}"), "x\u{2081}q");
+}
+
+#[test]
+fn parsing() {
+    let parsed = |fallback| {
+        Converter::builder()
+            .script_fallback(fallback)
+            .parse_latex(true)
+            .build()
+    };
+    let converter = parsed(ScriptFallback::Prefix);
+    assert_eq!(converter.replace("x^{2Q}"), "x\u{b2}^{Q}");
+    assert_eq!(converter.replace("a_{bc}"), "a_{bc}");
+    let converter = parsed(ScriptFallback::Parenthesized);
+    assert_eq!(converter.replace("x^{Q_1} + x^Q"), "x^(Q\u{2081}) + x^Q");
+    assert_eq!(converter.replace("x^{}"), "x^{}");
+    let converter = parsed(ScriptFallback::Closest);
+    assert_eq!(
+        converter.replace("x^{2Q} + x^Y"),
+        "x\u{b2}\u{a7f4} + x\u{107b2}"
+    );
+}
+
+#[test]
+fn tokens() {
+    let converter = with_fallback(ScriptFallback::Parenthesized);
+    let tokens: Vec<Token> = converter.tokens("x^{Q}").collect();
+    assert_eq!(
+        tokens,
+        [
+            Token::Literal("x"),
+            Token::ScriptFallback {
+                latex: "^{Q}",
+                kind: ScriptKind::Superscript,
+                unicode: "^(Q)".into(),
+            },
+        ]
+    );
+    let converter = with_fallback(ScriptFallback::Prefix);
+    let text = r"x^{2Q} + \alpha_{1bc}";
+    let converted: String = converter
+        .tokens(text)
+        .map(|token| token.unicode())
+        .collect();
+    assert_eq!(converted, converter.replace(text));
+}
+
+#[test]
+fn mapping_and_diagnostics() {
+    let converter = with_fallback(ScriptFallback::Prefix);
+    let (output, map) = converter.replace_with_mapping("x^{2Q} y");
+    assert_eq!(output, "x\u{b2}^{Q} y");
+    assert_eq!(map.output_range(1..6), 1..7);
+    assert_eq!(map.input_range(7..9), 6..8);
+
+    let (_, diagnostics) = converter.replace_with_diagnostics("y_{1bc}");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].span, 1..7);
+    assert_eq!(
+        diagnostics[0].kind,
+        DiagnosticKind::UnsupportedScript {
+            kind: ScriptKind::Subscript,
+            unsupported: vec!['b', 'c'],
+        }
+    );
+    let converter = with_fallback(ScriptFallback::Parenthesized);
+    let (_, diagnostics) = converter.replace_with_diagnostics("x^{2Q}");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].span, 1..6);
+    assert_eq!(
+        diagnostics[0].kind,
+        DiagnosticKind::UnsupportedScript {
+            kind: ScriptKind::Superscript,
+            unsupported: vec!['Q'],
+        }
+    );
+    let converter = with_fallback(ScriptFallback::Closest);
+    assert!(converter.try_replace("x^{Q}").is_ok());
+}
+
+#[test]
+fn streaming() {
+    let converter = with_fallback(ScriptFallback::Parenthesized);
+    let text = "a_{b c} x^{2Q}\n\ny_{1 2} z^{Q\n}";
+    assert_eq!(converter.replace(text), "a_(b c) x^(2Q)\n\ny_(1 2) z^(Q\n)");
+    assert_streams_like_replace(&converter, text);
+}
@@ -20,4 +20,60 @@ fn do_test_combining(replace: ReplaceFunc) {
     do_assert(r#"\breve{"#, r#"\breve{"#);
 }
 
-declare_tests!(basic, combining);
+fn do_test_combining_multibyte(replace: ReplaceFunc) {
+    let do_assert = assert_func!(replace);
+    do_assert(r#"\hat{é}"#, "é\u{302}");
+    do_assert(r#"\hat{é}x"#, "é\u{302}x");
+}
+
+/// Malformed input must never panic, and is converted like the original.
+fn do_test_malformed(replace: ReplaceFunc) {
+    let do_assert = assert_func!(replace);
+    do_assert(r#"\hat{"#, r#"\hat{"#);
+    do_assert(r#"^{"#, r#"^{"#);
+    do_assert(r#"_{é"#, r#"_{é"#);
+    do_assert(r#"x^{é}"#, r#"x^{é}"#);
+    do_assert(r#"\not\"#, "\u{338}\\");
+    do_assert(r#"\not\é"#, "\u{338}\\é");
+    do_assert("\\\u{1f600}", "\\\u{1f600}");
+    do_assert("^\u{1f600}", "^\u{1f600}");
+}
+
+/// Malformed combining marks, with the output of the original,
+/// which differs as documented on [`unicodeit::replace`].
+const MALFORMED_COMBINING: &[(&str, &str, &str)] = &[
+    (r#"\hat{\dot{a}}"#, "a\u{307}\u{302}", "a\u{302}}"),
+    (r#"\hat{x^2}"#, "\\hat{x\u{b2}}", "x\u{302}}"),
+    (r#"\hat{\alpha"#, "\\hat{\u{3b1}", "\u{3b1}\u{302}"),
+    (r#"\hat{é"#, r#"\hat{é"#, "é\u{302}"),
+];
+
+#[test]
+fn test_malformed_combining() {
+    for &(text, expected, _) in MALFORMED_COMBINING {
+        assert_eq!(
+            unicodeit::replace(text),
+            expected,
+            "Failed to convert `{text}`"
+        );
+        assert_eq!(
+            common::replace_tokens(text),
+            expected,
+            "Failed to convert `{text}`"
+        );
+    }
+}
+
+#[test]
+#[cfg(feature = "naive-impl")]
+fn test_naive_malformed_combining() {
+    for &(text, _, original) in MALFORMED_COMBINING {
+        assert_eq!(
+            unicodeit::replace_naive(text),
+            original,
+            "Failed to convert `{text}`"
+        );
+    }
+}
+
+declare_tests!(basic, combining, combining_multibyte, malformed);
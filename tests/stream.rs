@@ -0,0 +1,116 @@
+//! Tests for streaming conversion, split at every possible chunk boundary.
+#![cfg(feature = "std")]
+
+use std::io::{Read, Write};
+use unicodeit::{ReplaceReader, ReplaceWriter};
+
+const SAMPLES: &[&str] = &[
+    r"\alpha + \beta = \gamma",
+    r"a^{12} + cm_{-1} \not\in \mathbb{R}",
+    r"\dot{a} \hat{\alpha} \breve{} \breve{",
+    r"\hat{ } \hat{\alpha } \hat{_{\beta} }",
+    "multiple\nlines with \\rightarrow\tand tabs \\ hat{a}",
+    "non-ascii \u{3b1}\u{3b2} text \\mathfrak{g}",
+];
+
+/// Yields a reader that returns a single byte at a time.
+struct ByteReader<'a>(&'a [u8]);
+
+impl Read for ByteReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match (self.0.split_first(), buf.first_mut()) {
+            (Some((&byte, rest)), Some(target)) => {
+                *target = byte;
+                self.0 = rest;
+                Ok(1)
+            }
+            _ => Ok(0),
+        }
+    }
+}
+
+#[test]
+fn writer_split_chunks() {
+    for sample in SAMPLES {
+        let expected = unicodeit::replace(sample);
+        for split in 0..=sample.len() {
+            let (first, second) = sample.as_bytes().split_at(split);
+            let mut writer = ReplaceWriter::new(Vec::new());
+            writer.write_all(first).unwrap();
+            writer.write_all(second).unwrap();
+            let output = writer.finish().unwrap();
+            assert_eq!(
+                String::from_utf8(output).unwrap(),
+                expected,
+                "Failed to convert {sample:?} split at {split}"
+            );
+        }
+    }
+}
+
+#[test]
+fn writer_single_bytes() {
+    for sample in SAMPLES {
+        let mut writer = ReplaceWriter::new(Vec::new());
+        for byte in sample.bytes() {
+            writer.write_all(&[byte]).unwrap();
+        }
+        let output = writer.finish().unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            unicodeit::replace(sample)
+        );
+    }
+}
+
+#[test]
+fn writer_flushes_ready_input() {
+    let mut writer = ReplaceWriter::new(Vec::new());
+    writer.write_all(br"\alpha \bet").unwrap();
+    writer.flush().unwrap();
+    assert_eq!(writer.get_ref(), "\u{3b1} ".as_bytes());
+    writer.write_all(b"a").unwrap();
+    assert_eq!(writer.finish().unwrap(), "\u{3b1} \u{3b2}".as_bytes());
+}
+
+#[test]
+fn writer_invalid_utf8() {
+    let mut writer = ReplaceWriter::new(Vec::new());
+    assert!(writer.write_all(b"\xff").is_err());
+    writer.write_all(b"\xce").unwrap();
+    assert!(writer.finish().is_err());
+}
+
+#[test]
+fn reader_single_bytes() {
+    for sample in SAMPLES {
+        let mut reader = ReplaceReader::new(ByteReader(sample.as_bytes()));
+        let mut output = String::new();
+        reader.read_to_string(&mut output).unwrap();
+        assert_eq!(output, unicodeit::replace(sample));
+    }
+}
+
+#[test]
+#[cfg(feature = "futures-io")]
+fn async_round_trip() {
+    use futures_lite::io::{AsyncReadExt, AsyncWriteExt};
+    use unicodeit::{AsyncReplaceReader, AsyncReplaceWriter};
+
+    futures_lite::future::block_on(async {
+        for sample in SAMPLES {
+            let expected = unicodeit::replace(sample);
+            let mut writer = AsyncReplaceWriter::new(Vec::new());
+            for chunk in sample.as_bytes().chunks(3) {
+                writer.write_all(chunk).await.unwrap();
+            }
+            writer.close().await.unwrap();
+            assert_eq!(String::from_utf8(writer.into_inner()).unwrap(), expected);
+
+            let mut reader = AsyncReplaceReader::new(sample.as_bytes());
+            let mut output = String::new();
+            reader.read_to_string(&mut output).await.unwrap();
+            assert_eq!(output, expected);
+        }
+    });
+}
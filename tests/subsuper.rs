@@ -24,9 +24,19 @@ fn do_test_subscript_minus1(replace: ReplaceFunc) {
     do_assert("cm_{-1}", "cm₋₁")
 }
 
+/// Like the original, the output of escapes is read as part of a script.
+fn do_test_escaped_controls(replace: ReplaceFunc) {
+    let do_assert = assert_func!(replace);
+    do_assert(r"x\_1", "x₁");
+    do_assert(r"x\sphat{12}", "x¹²");
+    do_assert(r"x_\{12\}", "x₁₂");
+    do_assert(r"x^\lbrace n\rbrace", "x^{ n}");
+}
+
 declare_tests!(
     superscript_12,
     superscript_minus1,
     subscript_12,
-    subscript_minus1
+    subscript_minus1,
+    escaped_controls
 );
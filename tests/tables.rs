@@ -0,0 +1,130 @@
+//! Tests for loading [`SymbolTables`] from files.
+#![cfg(feature = "serde")]
+
+use unicodeit::{Converter, InvalidEntry, SymbolTables, TableError, TableKind};
+
+const TOML: &str = r#"
+[replacements]
+'\R' = "ℝ"
+'\eps' = "ϵ"
+
+[combining_marks]
+'\ring' = "̊"
+
+[sub_super_scripts]
+'^Q' = "ꟴ"
+"#;
+
+fn invalid(table: TableKind, key: &str, kind: InvalidEntry) -> TableError {
+    TableError::Invalid {
+        table,
+        key: key.into(),
+        kind,
+    }
+}
+
+#[test]
+fn load_toml() {
+    let tables = SymbolTables::from_toml(TOML).unwrap();
+    assert_eq!(
+        tables.replacements,
+        [(r"\R".into(), "ℝ".into()), (r"\eps".into(), "ϵ".into())]
+    );
+    let converter = Converter::builder().add_tables(tables).build();
+    assert_eq!(
+        converter.replace(r"\R \eps \ring{a} x^Q \alpha"),
+        "ℝ ϵ a\u{30a} xꟴ \u{3b1}"
+    );
+}
+
+#[test]
+fn load_json() {
+    let json = r#"{
+        "replacements": {"\\R": "ℝ", "\\eps": "ϵ"},
+        "combining_marks": {"\\ring": "̊"},
+        "sub_super_scripts": {"^Q": "ꟴ"}
+    }"#;
+    assert_eq!(
+        SymbolTables::from_json(json).unwrap(),
+        SymbolTables::from_toml(TOML).unwrap()
+    );
+    assert_eq!(
+        SymbolTables::from_json("{}").unwrap(),
+        SymbolTables::default()
+    );
+}
+
+#[test]
+fn duplicate_keys() {
+    let json = r#"{"replacements": {"\\R": "ℝ", "\\R": "R"}}"#;
+    assert_eq!(
+        SymbolTables::from_json(json),
+        Err(invalid(
+            TableKind::Replacements,
+            r"\R",
+            InvalidEntry::DuplicateKey
+        ))
+    );
+    // rejected by the TOML parser itself
+    let toml = "[replacements]\n'\\R' = 'a'\n'\\R' = 'b'";
+    match SymbolTables::from_toml(toml) {
+        Err(TableError::Syntax(message)) => {
+            assert!(message.ends_with("at line 3 column 1"), "{message}");
+        }
+        result => panic!("unexpected {result:?}"),
+    }
+}
+
+#[test]
+fn invalid_entries() {
+    for (toml, expected) in [
+        (
+            "[replacements]\nR = 'ℝ'",
+            invalid(TableKind::Replacements, "R", InvalidEntry::MissingBackslash),
+        ),
+        (
+            "[combining_marks]\nring = '\u{30a}'",
+            invalid(
+                TableKind::CombiningMarks,
+                "ring",
+                InvalidEntry::MissingBackslash,
+            ),
+        ),
+        (
+            "[replacements]\n'\\R' = ''",
+            invalid(TableKind::Replacements, r"\R", InvalidEntry::EmptyValue),
+        ),
+        (
+            "[combining_marks]\n'\\ring' = 'o'",
+            invalid(
+                TableKind::CombiningMarks,
+                r"\ring",
+                InvalidEntry::NotCombining,
+            ),
+        ),
+        (
+            "[combining_marks]\n'\\ring' = '\u{30a}\u{30a}'",
+            invalid(
+                TableKind::CombiningMarks,
+                r"\ring",
+                InvalidEntry::NotCombining,
+            ),
+        ),
+        (
+            "[sub_super_scripts]\n'^QQ' = 'ꟴ'",
+            invalid(TableKind::SubSuperScripts, "^QQ", InvalidEntry::InvalidKey),
+        ),
+        (
+            "[replacements]\n'\\a b' = 'x'",
+            invalid(TableKind::Replacements, r"\a b", InvalidEntry::InvalidKey),
+        ),
+    ] {
+        assert_eq!(SymbolTables::from_toml(toml), Err(expected), "{toml:?}");
+    }
+}
+
+#[test]
+fn unknown_table() {
+    let error = SymbolTables::from_toml("[symbols]\n'\\R' = 'ℝ'").unwrap_err();
+    assert!(matches!(error, TableError::Syntax(_)), "{error:?}");
+}
@@ -0,0 +1,34 @@
+//! Tests for the reverse conversion from Unicode to LaTeX.
+#![cfg(feature = "std")]
+
+use unicodeit::to_latex;
+
+#[test]
+fn symbols() {
+    assert_eq!(to_latex("\u{3b1} + \u{3b2}"), r"\alpha + \beta");
+    assert_eq!(to_latex("\u{3b1}b"), r"\alpha\mathrm{b}");
+    assert_eq!(to_latex("plain ascii text"), "plain ascii text");
+}
+
+#[test]
+fn canonical_command() {
+    // \mathbfit{\varepsilon} and \mathbold{\varepsilon} both produce this
+    assert_eq!(to_latex("\u{1d73a}"), r"\mbfitepsilon");
+    // \bar and \overline are the same combining mark
+    assert_eq!(to_latex("a\u{305}"), r"\bar{a}");
+}
+
+#[test]
+fn script_groups() {
+    assert_eq!(to_latex("x₁₂"), "x_{12}");
+    assert_eq!(to_latex("x₁"), "x_1");
+    assert_eq!(to_latex("cm⁻¹"), "cm^{-1}");
+    assert_eq!(to_latex("x₁²"), "x_1^2");
+}
+
+#[test]
+fn combining_marks() {
+    assert_eq!(to_latex("a\u{302}"), r"\hat{a}");
+    assert_eq!(to_latex("\u{3b1}\u{307}"), r"\dot{\alpha}");
+    assert_eq!(to_latex("a\u{302}\u{307}"), r"\dot{\hat{a}}");
+}
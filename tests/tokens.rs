@@ -0,0 +1,138 @@
+//! Tests for splitting text into [`Token`]s.
+#![cfg(feature = "std")]
+
+use unicodeit::{Converter, ScriptKind, Token};
+
+fn tokens(text: &str) -> Vec<Token<'_>> {
+    unicodeit::tokens(text).collect()
+}
+
+#[test]
+fn symbols_and_literals() {
+    assert_eq!(tokens(""), []);
+    assert_eq!(tokens("plain text"), [Token::Literal("plain text")]);
+    assert_eq!(
+        tokens(r"\alpha+\beta a-b"),
+        [
+            Token::Symbol {
+                latex: r"\alpha",
+                unicode: "\u{3b1}"
+            },
+            Token::Literal("+"),
+            Token::Symbol {
+                latex: r"\beta",
+                unicode: "\u{3b2}"
+            },
+            Token::Literal(" a"),
+            Token::Symbol {
+                latex: "-",
+                unicode: "\u{2212}"
+            },
+            Token::Literal("b"),
+        ]
+    );
+}
+
+#[test]
+fn scripts() {
+    assert_eq!(
+        tokens("x_{12}y^Q"),
+        [
+            Token::Literal("x"),
+            Token::Script {
+                latex: "_{12}",
+                kind: ScriptKind::Subscript,
+                chars: "\u{2081}\u{2082}".into()
+            },
+            Token::Literal("y"),
+            Token::Unknown("^"),
+            Token::Literal("Q"),
+        ]
+    );
+}
+
+#[test]
+fn combining() {
+    assert_eq!(
+        tokens(r"\hat{\alpha}\not\in"),
+        [
+            Token::Combining {
+                latex: r"\hat{\alpha}",
+                base: "\u{3b1}".into(),
+                mark: "\u{302}".into()
+            },
+            Token::Combining {
+                latex: r"\not\in",
+                base: "\u{2208}".into(),
+                mark: "\u{338}".into()
+            },
+        ]
+    );
+    assert_eq!(
+        tokens(r"\hat{\dot{a}}"),
+        [Token::Combining {
+            latex: r"\hat{\dot{a}}",
+            base: "a".into(),
+            mark: "\u{307}\u{302}".into()
+        }]
+    );
+    assert_eq!(
+        tokens(r"\hat{\dot{a}"),
+        [
+            Token::Unknown(r"\hat{"),
+            Token::Combining {
+                latex: r"\dot{a}",
+                base: "a".into(),
+                mark: "\u{307}".into()
+            },
+        ]
+    );
+}
+
+#[test]
+fn unknown() {
+    assert_eq!(
+        tokens(r"\unknown{x} \hat{"),
+        [
+            Token::Unknown(r"\unknown"),
+            Token::Literal("{x} "),
+            Token::Unknown(r"\hat{"),
+        ]
+    );
+}
+
+#[test]
+fn latex_covers_input() {
+    for text in [
+        r"\alpha^{2}_\beta \hat{} \breve{ \not\in x_{a_1} \\ \mathbb{R}^n",
+        "\u{3b1}\\u{3b2} ^_",
+        r"\hat{\dot{\unknown}} 25\% \sqrt[3]{x}",
+    ] {
+        let latex: String = tokens(text).iter().map(Token::latex).collect();
+        assert_eq!(latex, text);
+        let unicode: String = tokens(text).iter().map(Token::unicode).collect();
+        assert_eq!(unicode, unicodeit::replace(text));
+        // a token is never followed by an empty literal
+        assert!(!tokens(text).contains(&Token::Literal("")));
+    }
+}
+
+#[test]
+fn converter_tables() {
+    let converter = Converter::builder()
+        .add_replacement(r"\R", "\u{211d}")
+        .sub_super_scripts(false)
+        .build();
+    let tokens: Vec<_> = converter.tokens(r"\R^2").collect();
+    assert_eq!(
+        tokens,
+        [
+            Token::Symbol {
+                latex: r"\R",
+                unicode: "\u{211d}"
+            },
+            Token::Unknown("^"),
+            Token::Literal("2"),
+        ]
+    );
+}
@@ -0,0 +1,116 @@
+//! Tests for [`unicodeit::try_replace`] and strict mode.
+#![cfg(feature = "std")]
+
+use unicodeit::{ConversionLimit, Converter, DiagnosticKind, Severity};
+
+#[test]
+fn success() {
+    for text in [r"\alpha + \beta", r"x_{12} \hat{a}", "plain text"] {
+        assert_eq!(
+            unicodeit::try_replace(text).unwrap(),
+            unicodeit::replace(text)
+        );
+    }
+}
+
+#[test]
+fn lists_every_failure() {
+    let text = r"\unknown x^Q \breve";
+    let error = unicodeit::try_replace(text).unwrap_err();
+    let spans = error
+        .failures()
+        .iter()
+        .map(|failure| failure.span.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(spans, vec![0..8, 10..12, 13..19]);
+    assert_eq!(error.span(), 0..8);
+    assert_eq!(error.output(), unicodeit::replace(text));
+    let message = error.to_string();
+    assert!(message.contains("3 problems"), "{message}");
+    assert!(message.contains(r"`\unknown`"), "{message}");
+}
+
+#[test]
+fn lenient_converter() {
+    let converter = Converter::default();
+    assert_eq!(
+        converter.try_replace(r"\unknown x^Q").unwrap(),
+        r"\unknown x^Q"
+    );
+    let error = converter.try_replace(r"\unknown \breve").unwrap_err();
+    assert_eq!(error.failures().len(), 1);
+    assert_eq!(error.failures()[0].severity, Severity::Error);
+    assert!(matches!(
+        error.failures()[0].kind,
+        DiagnosticKind::DanglingCombiningMark { .. }
+    ));
+}
+
+#[test]
+fn error_trait() {
+    fn convert(text: &str) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(unicodeit::try_replace(text)?)
+    }
+    assert!(convert(r"\alpha").is_ok());
+    assert!(convert(r"\alph").is_err());
+}
+
+#[test]
+fn input_limit() {
+    let converter = Converter::builder().max_input_len(10).build();
+    assert_eq!(converter.try_replace(r"\alpha^2").unwrap(), "α²");
+    let text = r"\alpha + \beta";
+    let error = converter.try_replace(text).unwrap_err();
+    assert_eq!(error.failures().len(), 1);
+    assert_eq!(error.span(), 0..text.len());
+    assert_eq!(
+        error.failures()[0].kind,
+        DiagnosticKind::LimitExceeded {
+            limit: ConversionLimit::InputLength,
+            max_len: 10,
+        }
+    );
+    assert_eq!(error.output(), "");
+    assert_eq!(converter.replace(text), "");
+    assert_eq!(converter.replace("plain text that is too long"), "");
+}
+
+#[test]
+fn output_limit() {
+    let converter = Converter::builder().max_output_len(5).build();
+    // each of these converts to two bytes
+    let text = r"\alpha\beta\gamma";
+    let error = converter.try_replace(text).unwrap_err();
+    assert_eq!(error.span(), 11..text.len());
+    assert_eq!(
+        error.failures()[0].kind,
+        DiagnosticKind::LimitExceeded {
+            limit: ConversionLimit::OutputLength,
+            max_len: 5,
+        }
+    );
+    assert_eq!(error.output(), "αβ");
+    assert_eq!(converter.replace(text), "αβ");
+    let mut output = String::new();
+    converter.replace_into(text, &mut output).unwrap();
+    assert_eq!(output, "αβ");
+    // unchanged text is cut off at a character boundary
+    assert_eq!(converter.replace("ab\u{e9}\u{e9}"), "ab\u{e9}");
+    assert_eq!(converter.replace("abcdefgh"), "abcde");
+    let (output, map) = converter.replace_with_mapping(text);
+    assert_eq!(output, "αβ");
+    assert_eq!(map.input_len(), text.len());
+    assert_eq!(map.output_len(), output.len());
+}
+
+#[test]
+fn output_limit_with_macros() {
+    let converter = Converter::builder()
+        .macros(unicodeit::Macros::new().preamble(r"\newcommand{\ab}{\alpha\beta}"))
+        .max_output_len(3)
+        .build();
+    let text = r"x \ab \gamma";
+    let error = converter.try_replace(text).unwrap_err();
+    assert_eq!(error.output(), "x ");
+    assert_eq!(error.span(), 2..text.len());
+}